@@ -0,0 +1,196 @@
+use std::{path::Path, fs::File, io::{self, Read, Seek, SeekFrom}, time::Duration, fmt, error::Error};
+use symphonia::core::{
+    io::{MediaSourceStream, MediaSourceStreamOptions, BufReader},
+    probe::Hint,
+    formats::FormatOptions,
+    meta::{MetadataOptions, MetadataRevision, MetadataBuilder, StandardTagKey},
+    codecs::CODEC_TYPE_MP3,
+    errors::Error as SymphoniaError,
+};
+use symphonia_metadata::id3v1::read_id3v1;
+use crate::fily_err::{Context, FilyError};
+#[allow(unused_imports)]
+use log::{trace, debug, info, warn, error};
+
+#[derive(Debug)]
+pub enum AudioTagsError {
+    /// The file isn't an audio file or has a format that isn't supported. Supported
+    /// formats are MP3, FLAC and OGG Vorbis
+    UnsupportedFormat,
+
+    /// Failed to read the file
+    IOError(std::io::Error),
+}
+
+impl Error for AudioTagsError {}
+
+impl fmt::Display for AudioTagsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl From<SymphoniaError> for AudioTagsError {
+    fn from(err: SymphoniaError) -> Self {
+        match err {
+            SymphoniaError::IoError(e) if e.kind() != std::io::ErrorKind::UnexpectedEof => AudioTagsError::IOError(e),
+            _ => AudioTagsError::UnsupportedFormat,
+        }
+    }
+}
+
+/// The tags of an audio file
+///
+/// Every tag is `None` if the file doesn't have it
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct AudioTags {
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub title: Option<String>,
+
+    /// Read from the date the track was recorded or released
+    pub year: Option<i32>,
+
+    pub genre: Option<String>,
+
+    /// The length of the audio. This is `None` if the file doesn't store it, which
+    /// can be the case for MP3 files without a Xing or VBRI header
+    pub duration: Option<Duration>,
+}
+
+impl AudioTags {
+    /// Reads the tags of the MP3, FLAC or OGG Vorbis file `path` points to
+    ///
+    /// `ID3v1` and `ID3v2` tags are read from MP3 files and Vorbis comments from FLAC and OGG files.
+    /// If there are multiple values for the same tag the first one is used. `ID3v1` tags at the end of
+    /// an MP3 file are only used for the tags `ID3v2` doesn't have
+    ///
+    /// # Errors
+    ///
+    /// Fails if the file can't be read or isn't an audio file in one of the supported formats
+    pub fn read(path: impl AsRef<Path>) -> Result<Self, FilyError<AudioTagsError>> {
+        let path = path.as_ref();
+
+        trace!("AudioTags::read path: {:?}", path.display());
+
+        let file = File::open(path)
+            .map_err(AudioTagsError::IOError)
+            .with_context(|| format!("Failed to open {:?}", path.display()))?;
+
+        let media_source_stream = MediaSourceStream::new(Box::new(file), MediaSourceStreamOptions::default());
+
+        let mut hint = Hint::new();
+
+        if let Some(extension) = path.extension().and_then(|extension| extension.to_str()) {
+            hint.with_extension(extension);
+        }
+
+        let mut probe_result = symphonia::default::get_probe()
+            .format(&hint, media_source_stream, &FormatOptions::default(), &MetadataOptions::default())
+            .map_err(AudioTagsError::from)
+            .with_context(|| format!("Failed to read {:?} as an audio file", path.display()))?;
+
+        let mut audio_tags = AudioTags::default();
+
+        // Tags in front of the actual format (i.e. ID3v2 in front of MP3) come first
+        if let Some(metadata_revision) = probe_result.metadata.get().as_ref().and_then(|metadata| metadata.current()) {
+            audio_tags.add_missing_tags(metadata_revision);
+        }
+
+        if let Some(metadata_revision) = probe_result.format.metadata().current() {
+            audio_tags.add_missing_tags(metadata_revision);
+        }
+
+        if let Some(track) = probe_result.format.default_track() {
+            let codec_params = &track.codec_params;
+
+            if let (Some(time_base), Some(n_frames)) = (codec_params.time_base, codec_params.n_frames) {
+                let time = time_base.calc_time(n_frames);
+                audio_tags.duration = Some(Duration::from_secs(time.seconds) + Duration::from_secs_f64(time.frac));
+            }
+
+            // The MP3 reader doesn't look at the end of the file where ID3v1 tags are
+            if codec_params.codec == CODEC_TYPE_MP3 {
+                if let Some(metadata_revision) = read_id3v1_tag(path)? {
+                    audio_tags.add_missing_tags(&metadata_revision);
+                }
+            }
+        }
+
+        Ok(audio_tags)
+    }
+
+    /// Sets every tag that isn't set yet and is in `metadata_revision`
+    ///
+    /// Whitespace around a value is removed since `ID3v1` pads them with spaces
+    fn add_missing_tags(&mut self, metadata_revision: &MetadataRevision) {
+        for tag in metadata_revision.tags() {
+            let value = tag.value.to_string().trim().to_string();
+
+            if value.is_empty() {
+                continue;
+            }
+
+            let field = match tag.std_key {
+                Some(StandardTagKey::Artist) => &mut self.artist,
+                Some(StandardTagKey::Album) => &mut self.album,
+                Some(StandardTagKey::TrackTitle) => &mut self.title,
+                Some(StandardTagKey::Genre) => &mut self.genre,
+                Some(StandardTagKey::Date | StandardTagKey::ReleaseDate | StandardTagKey::OriginalDate) => {
+                    if self.year.is_none() {
+                        self.year = parse_year(&value);
+                    }
+
+                    continue;
+                }
+                _ => continue,
+            };
+
+            if field.is_none() {
+                *field = Some(value);
+            }
+        }
+    }
+}
+
+/// Reads the `ID3v1` tag in the last 128 bytes of the file `path` points to
+///
+/// Returns `None` if the file doesn't end with one
+fn read_id3v1_tag(path: &Path) -> Result<Option<MetadataRevision>, FilyError<AudioTagsError>> {
+    let read_last_bytes = || -> io::Result<Option<[u8; 128]>> {
+        let mut file = File::open(path)?;
+
+        if file.metadata()?.len() < 128 {
+            return Ok(None);
+        }
+
+        let mut tag = [0; 128];
+        file.seek(SeekFrom::End(-128))?;
+        file.read_exact(&mut tag)?;
+
+        Ok(Some(tag))
+    };
+
+    let tag = read_last_bytes()
+        .map_err(AudioTagsError::IOError)
+        .with_context(|| format!("Failed to read the ID3v1 tag of {:?}", path.display()))?;
+
+    let mut metadata_builder = MetadataBuilder::new();
+
+    match tag {
+        Some(tag) if read_id3v1(&mut BufReader::new(&tag), &mut metadata_builder).is_ok() => Ok(Some(metadata_builder.metadata())),
+        _ => Ok(None),
+    }
+}
+
+/// Gets the year out of dates like `2021`, `2021-06-01` or `2021-06-01T12:00:00`
+fn parse_year(date: &str) -> Option<i32> {
+    let date = date.trim();
+    let year_length = date.find(|c: char| !c.is_ascii_digit()).unwrap_or(date.len());
+
+    if year_length != 4 {
+        return None;
+    }
+
+    date[..year_length].parse().ok()
+}
@@ -0,0 +1,167 @@
+use std::{path::Path, fs::File, io::{self, BufReader}, fmt, error::Error, convert::TryFrom, ops::Range};
+use exif::{Reader, Exif, Tag, In, Value, DateTime};
+use chrono::{NaiveDate, NaiveDateTime, FixedOffset, Duration, format::{Parsed, StrftimeItems}};
+use crate::fily_err::{Context, FilyError};
+#[allow(unused_imports)]
+use log::{trace, debug, info, warn, error};
+
+#[derive(Debug)]
+pub enum ExifTagsError {
+    /// The file isn't an image or doesn't contain EXIF data. EXIF data can be
+    /// read from JPEG, TIFF, PNG, WebP and HEIF files
+    NoExif,
+
+    /// Failed to read the file
+    IOError(io::Error),
+}
+
+impl Error for ExifTagsError {}
+
+impl fmt::Display for ExifTagsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl From<exif::Error> for ExifTagsError {
+    fn from(err: exif::Error) -> Self {
+        match err {
+            exif::Error::Io(e) if e.kind() != io::ErrorKind::UnexpectedEof => ExifTagsError::IOError(e),
+            _ => ExifTagsError::NoExif,
+        }
+    }
+}
+
+/// The EXIF tags of a photo
+///
+/// Every tag is `None` if the file doesn't have it
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct ExifTags {
+    /// The time the photo was taken at as the camera showed it
+    ///
+    /// Cameras usually store the local time without a timezone. If they store one it is in `offset_time_original`
+    pub date_time_original: Option<NaiveDateTime>,
+
+    /// The offset from UTC of `date_time_original`
+    pub offset_time_original: Option<FixedOffset>,
+
+    /// The manufacturer of the camera
+    pub make: Option<String>,
+
+    /// The model of the camera
+    pub model: Option<String>,
+
+    /// The model of the lens
+    pub lens_model: Option<String>,
+
+    /// `true` if the photo has a GPS position
+    pub has_gps: bool,
+
+    /// How the image has to be rotated or flipped to be displayed correctly. Goes from 1 to 8,
+    /// 1 means that it is displayed as is
+    pub orientation: Option<u16>,
+}
+
+impl ExifTags {
+    /// Reads the EXIF tags of the image `path` points to
+    ///
+    /// # Errors
+    ///
+    /// Fails if the file can't be read or doesn't contain EXIF data
+    pub fn read(path: impl AsRef<Path>) -> Result<Self, FilyError<ExifTagsError>> {
+        let path = path.as_ref();
+
+        trace!("ExifTags::read path: {:?}", path.display());
+
+        let file = File::open(path)
+            .map_err(ExifTagsError::IOError)
+            .with_context(|| format!("Failed to open {:?}", path.display()))?;
+
+        let exif = Reader::new()
+            .read_from_container(&mut BufReader::new(file))
+            .map_err(ExifTagsError::from)
+            .with_context(|| format!("Failed to read the EXIF data of {:?}", path.display()))?;
+
+        Ok(ExifTags {
+            date_time_original: date_time_original(&exif),
+            offset_time_original: offset_time_original(&exif),
+            make: ascii_field(&exif, Tag::Make),
+            model: ascii_field(&exif, Tag::Model),
+            lens_model: ascii_field(&exif, Tag::LensModel),
+            has_gps: exif.get_field(Tag::GPSLatitude, In::PRIMARY).is_some() && exif.get_field(Tag::GPSLongitude, In::PRIMARY).is_some(),
+            orientation: exif.get_field(Tag::Orientation, In::PRIMARY)
+                .and_then(|field| field.value.get_uint(0))
+                .and_then(|orientation| u16::try_from(orientation).ok()),
+        })
+    }
+}
+
+/// Returns the first string of an ASCII field without the padding some cameras add
+fn ascii_field(exif: &Exif, tag: Tag) -> Option<String> {
+    let field = exif.get_field(tag, In::PRIMARY)?;
+
+    match field.value {
+        Value::Ascii(ref values) => values.first()
+            .map(|value| String::from_utf8_lossy(value).trim_matches(|c: char| c.is_whitespace() || c == '\0').to_string())
+            .filter(|value| !value.is_empty()),
+        _ => None,
+    }
+}
+
+fn date_time_original(exif: &Exif) -> Option<NaiveDateTime> {
+    let date_time = match exif.get_field(Tag::DateTimeOriginal, In::PRIMARY)?.value {
+        Value::Ascii(ref values) => values.first().and_then(|value| DateTime::from_ascii(value).ok())?,
+        _ => return None,
+    };
+
+    NaiveDate::from_ymd_opt(i32::from(date_time.year), u32::from(date_time.month), u32::from(date_time.day))?
+        .and_hms_opt(u32::from(date_time.hour), u32::from(date_time.minute), u32::from(date_time.second))
+}
+
+/// Offsets look like `+09:00`. A broken offset is treated as if there was none
+fn offset_time_original(exif: &Exif) -> Option<FixedOffset> {
+    let offset = match exif.get_field(Tag::OffsetTimeOriginal, In::PRIMARY)?.value {
+        Value::Ascii(ref values) => std::str::from_utf8(values.first()?).ok()?,
+        _ => return None,
+    };
+
+    let mut parsed = Parsed::new();
+    chrono::format::parse(&mut parsed, offset.trim_end_matches('\0'), StrftimeItems::new("%:z")).ok()?;
+
+    parsed.to_fixed_offset().ok()
+}
+
+/// Parses a date like `2021-04-05` or a date with a time like `2021-04-05T12:00` or `2021-04-05T12:00:30`.
+/// A date without a time is midnight at the start of that day
+///
+/// # Errors
+///
+/// Fails if `text` isn't in one of these formats or isn't a valid date
+pub fn parse_date_time(text: &str) -> Result<NaiveDateTime, chrono::ParseError> {
+    if let Some(midnight) = NaiveDate::parse_from_str(text, "%Y-%m-%d").ok().and_then(|date| date.and_hms_opt(0, 0, 0)) {
+        return Ok(midnight);
+    }
+
+    NaiveDateTime::parse_from_str(text, "%Y-%m-%dT%H:%M:%S")
+        .or_else(|_| NaiveDateTime::parse_from_str(text, "%Y-%m-%dT%H:%M"))
+}
+
+/// Parses a date or a date with a time like `parse_date_time`, but returns every time it stands for: the whole day for
+/// `2021-04-05`, the whole minute for `2021-04-05T12:00` and a single second for `2021-04-05T12:00:30`
+///
+/// # Errors
+///
+/// Fails if `text` isn't in one of these formats or isn't a valid date
+pub fn parse_date_time_range(text: &str) -> Result<Range<NaiveDateTime>, chrono::ParseError> {
+    let start = parse_date_time(text)?;
+
+    let length = if NaiveDate::parse_from_str(text, "%Y-%m-%d").is_ok() {
+        Duration::days(1)
+    } else if NaiveDateTime::parse_from_str(text, "%Y-%m-%dT%H:%M:%S").is_ok() {
+        Duration::seconds(1)
+    } else {
+        Duration::minutes(1)
+    };
+
+    Ok(start..start + length)
+}
@@ -0,0 +1,322 @@
+use std::{
+    path::{Path, PathBuf, Component},
+    fs::File,
+    io::{self, Read, Seek, Cursor, BufReader},
+    convert::TryFrom,
+};
+use filetime::FileTime;
+use flate2::read::GzDecoder;
+use super::{FindOptions, Ignore, Condition, ConditionEvalError, SearchEntry};
+use crate::fily_err::{Context, FilyError};
+#[allow(unused_imports)]
+use log::{trace, debug, info, warn, error};
+
+/// Separates the path to an archive from the path of a file inside of it
+pub const ARCHIVE_PATH_SEPARATOR: &str = "!/";
+
+/// Archives inside of other archives have to be read into memory to search them. Those that
+/// are larger than this many bytes (100 MiB) are skipped with an error
+pub const MAX_NESTED_ARCHIVE_SIZE: u64 = 100 * 1024 * 1024;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum ArchiveKind {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+impl ArchiveKind {
+    /// Guesses the kind of archive from the filename
+    // The filename is lowercased before comparing so this isn't case sensitive
+    #[allow(clippy::case_sensitive_file_extension_comparisons)]
+    fn from_path(path: &Path) -> Option<Self> {
+        let filename = path.file_name()?.to_str()?.to_lowercase();
+
+        if filename.ends_with(".zip") {
+            Some(ArchiveKind::Zip)
+        } else if filename.ends_with(".tar") {
+            Some(ArchiveKind::Tar)
+        } else if filename.ends_with(".tar.gz") || filename.ends_with(".tgz") {
+            Some(ArchiveKind::TarGz)
+        } else {
+            None
+        }
+    }
+}
+
+/// A file inside of an archive
+///
+/// Its path is the path to the archive followed by `!/` and the path of the file inside of the
+/// archive, i.e. `assets.zip!/textures/wall.png`
+///
+/// Archives only store the modification time, so getting the time a member was
+/// accessed or created always fails
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ArchiveMember {
+    path: PathBuf,
+    is_dir: bool,
+    is_file: bool,
+    depth: usize,
+    size: u64,
+    modified: Option<FileTime>,
+}
+
+impl SearchEntry for ArchiveMember {
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn is_dir(&self) -> bool {
+        self.is_dir
+    }
+
+    fn is_file(&self) -> bool {
+        self.is_file
+    }
+
+    fn is_symlink(&self) -> bool {
+        false
+    }
+
+    fn depth(&self) -> usize {
+        self.depth
+    }
+
+    fn size(&self) -> Result<u64, FilyError<io::Error>> {
+        Ok(self.size)
+    }
+
+    fn modified(&self) -> Result<FileTime, FilyError<io::Error>> {
+        self.modified
+            .ok_or_else(|| FilyError::new_with_context(io::Error::new(io::ErrorKind::Other, "Unsupported"), || format!("No modification time stored for {:?}", self.path.display())))
+    }
+
+    fn accessed(&self) -> Result<FileTime, FilyError<io::Error>> {
+        Err(FilyError::new_with_context(io::Error::new(io::ErrorKind::Other, "Unsupported"), || format!("Archives don't store the access time of {:?}", self.path.display())))
+    }
+
+    fn created(&self) -> Result<FileTime, FilyError<io::Error>> {
+        Err(FilyError::new_with_context(io::Error::new(io::ErrorKind::Other, "Unsupported"), || format!("Archives don't store the creation time of {:?}", self.path.display())))
+    }
+}
+
+/// An archive that is searched
+#[derive(Debug, Copy, Clone)]
+struct Archive<'a> {
+    path: &'a Path,
+
+    /// How deep the archive is below the path that is searched in
+    depth: usize,
+
+    /// How many archives deep this one is, 1 for an archive that isn't inside of another one
+    nesting: usize,
+}
+
+/// Returns `true` if `path` looks like an archive `find` can search in
+pub(crate) fn is_archive(path: &Path) -> bool {
+    ArchiveKind::from_path(path).is_some()
+}
+
+/// Searches the archive `path` points to for members that match `find_options`
+///
+/// `depth` is how deep the archive is below the path that is searched in
+///
+/// Archives inside of the archive are searched as well as long as they are
+/// at most `find_options.max_archive_depth` archives deep
+///
+/// Nothing is searched if `find_options.max_archive_depth` is 0 or a condition needs
+/// the files on disk since no member of an archive could match then
+pub(crate) fn search_archive(path: &Path, depth: usize, find_options: &FindOptions, results: &mut Vec<PathBuf>, errors: &mut Vec<(PathBuf, ConditionEvalError)>) {
+    trace!("search_archive path: {:?} depth: {}", path.display(), depth);
+
+    if find_options.max_archive_depth == 0 {
+        return;
+    }
+
+    if find_options.options.iter().any(Condition::needs_filesystem) {
+        debug!("Not searching in {:?} since a condition needs the files on disk", path.display());
+        return;
+    }
+
+    if let Some(kind) = ArchiveKind::from_path(path) {
+        let file = match File::open(path).with_context(|| format!("Failed to open archive {:?}", path.display())) {
+            Ok(file) => file,
+            Err(e) => {
+                errors.push((path.to_path_buf(), e.into()));
+                return;
+            }
+        };
+
+        let archive = Archive {
+            path,
+            depth,
+            nesting: 1,
+        };
+
+        if let Err(e) = search_archive_reader(BufReader::new(file), kind, archive, find_options, results, errors) {
+            errors.push((path.to_path_buf(), e.into()));
+        }
+    }
+}
+
+fn search_archive_reader<R: Read + Seek>(reader: R, kind: ArchiveKind, archive: Archive<'_>, find_options: &FindOptions, results: &mut Vec<PathBuf>, errors: &mut Vec<(PathBuf, ConditionEvalError)>) -> Result<(), FilyError<io::Error>> {
+    match kind {
+        ArchiveKind::Zip => {
+            let mut zip_archive = zip::ZipArchive::new(reader)
+                .map_err(io::Error::from)
+                .with_context(|| format!("Failed to read zip archive {:?}", archive.path.display()))?;
+
+            for i in 0..zip_archive.len() {
+                let mut member = zip_archive.by_index(i)
+                    .map_err(io::Error::from)
+                    .with_context(|| format!("Failed to read a file in {:?}", archive.path.display()))?;
+
+                let modified = member.last_modified();
+
+                let archive_member = ArchiveMember {
+                    path: member_path(archive.path, member.name()),
+                    is_dir: member.is_dir(),
+                    is_file: member.is_file(),
+                    depth: member_depth(archive, member.name()),
+                    size: member.size(),
+                    modified: FileTime::from_unix_time(zip_date_time_to_unix_seconds(modified), 0).into(),
+                };
+
+                check_member(&archive_member, &mut member, archive.nesting, find_options, results, errors);
+            }
+        }
+        ArchiveKind::Tar => search_tar(reader, archive, find_options, results, errors)?,
+        ArchiveKind::TarGz => search_tar(GzDecoder::new(reader), archive, find_options, results, errors)?,
+    }
+
+    Ok(())
+}
+
+fn search_tar<R: Read>(reader: R, archive: Archive<'_>, find_options: &FindOptions, results: &mut Vec<PathBuf>, errors: &mut Vec<(PathBuf, ConditionEvalError)>) -> Result<(), FilyError<io::Error>> {
+    let mut tar_archive = tar::Archive::new(reader);
+
+    let members = tar_archive.entries()
+        .with_context(|| format!("Failed to read tar archive {:?}", archive.path.display()))?;
+
+    for member in members {
+        let mut member = member.with_context(|| format!("Failed to read a file in {:?}", archive.path.display()))?;
+
+        let name = member.path()
+            .with_context(|| format!("Failed to read the path of a file in {:?}", archive.path.display()))?
+            .to_string_lossy()
+            .into_owned();
+
+        let header = member.header();
+
+        let archive_member = ArchiveMember {
+            path: member_path(archive.path, &name),
+            is_dir: header.entry_type().is_dir(),
+            is_file: header.entry_type().is_file(),
+            depth: member_depth(archive, &name),
+            size: header.size().unwrap_or(0),
+            modified: header.mtime().ok().and_then(|mtime| i64::try_from(mtime).ok()).map(|mtime| FileTime::from_unix_time(mtime, 0)),
+        };
+
+        check_member(&archive_member, &mut member, archive.nesting, find_options, results, errors);
+    }
+
+    Ok(())
+}
+
+/// Checks if a member of an archive matches and searches it if it is an archive itself
+fn check_member(archive_member: &ArchiveMember, contents: &mut impl Read, archive_depth: usize, find_options: &FindOptions, results: &mut Vec<PathBuf>, errors: &mut Vec<(PathBuf, ConditionEvalError)>) {
+    if member_matches(archive_member, find_options, errors) {
+        results.push(archive_member.path.clone());
+    }
+
+    if archive_member.is_dir || archive_depth >= find_options.max_archive_depth {
+        return;
+    }
+
+    if let Some(kind) = ArchiveKind::from_path(&archive_member.path) {
+        // Zip archives need to be able to seek so the whole archive has to be read into memory.
+        // The size in the header could be wrong so it also stops reading after the limit
+        let too_large = || FilyError::new_with_context(io::Error::new(io::ErrorKind::Other, "Too large"), || format!("Skipped archive {:?} since it is larger than {} bytes", archive_member.path.display(), MAX_NESTED_ARCHIVE_SIZE)).into();
+
+        if archive_member.size > MAX_NESTED_ARCHIVE_SIZE {
+            errors.push((archive_member.path.clone(), too_large()));
+            return;
+        }
+
+        let mut buffer = Vec::new();
+
+        if let Err(e) = contents.take(MAX_NESTED_ARCHIVE_SIZE + 1).read_to_end(&mut buffer) {
+            errors.push((archive_member.path.clone(), FilyError::new_with_context(e, || format!("Failed to read archive {:?}", archive_member.path.display())).into()));
+            return;
+        }
+
+        if buffer.len() as u64 > MAX_NESTED_ARCHIVE_SIZE {
+            errors.push((archive_member.path.clone(), too_large()));
+            return;
+        }
+
+        let archive = Archive {
+            path: &archive_member.path,
+            depth: archive_member.depth,
+            nesting: archive_depth + 1,
+        };
+
+        if let Err(e) = search_archive_reader(Cursor::new(buffer), kind, archive, find_options, results, errors) {
+            errors.push((archive_member.path.clone(), e.into()));
+        }
+    }
+}
+
+/// Same as `entry_matches` but for files inside of archives
+fn member_matches(archive_member: &ArchiveMember, find_options: &FindOptions, errors: &mut Vec<(PathBuf, ConditionEvalError)>) -> bool {
+    if let Some(ignore) = find_options.ignore {
+        match ignore {
+            Ignore::Files => if !archive_member.is_dir {
+                return false;
+            }
+            Ignore::Folders => if archive_member.is_dir {
+                return false;
+            }
+        }
+    }
+
+    if find_options.ignore_hidden_files {
+        if let Some(name) = archive_member.path.file_name().and_then(|name| name.to_str()) {
+            if name.starts_with('.') {
+                return false;
+            }
+        }
+    }
+
+    find_options.options.iter().all(|option| option.evaluate(archive_member).unwrap_or_else(|err| {
+        errors.push((archive_member.path.clone(), err));
+        false
+    }))
+}
+
+fn member_path(archive_path: &Path, member_name: &str) -> PathBuf {
+    PathBuf::from(format!("{}{}{}", archive_path.display(), ARCHIVE_PATH_SEPARATOR, member_name.trim_end_matches('/')))
+}
+
+/// Members are one folder deeper than the archive for every folder they are in inside of it
+fn member_depth(archive: Archive<'_>, member_name: &str) -> usize {
+    archive.depth + Path::new(member_name).components().filter(|component| matches!(component, Component::Normal(_))).count()
+}
+
+/// Zip archives store the time without a timezone. It's treated as UTC
+fn zip_date_time_to_unix_seconds(date_time: zip::DateTime) -> i64 {
+    let year = i64::from(date_time.year());
+    let month = i64::from(date_time.month());
+    let day = i64::from(date_time.day());
+
+    // Days since the unix epoch from a date in the proleptic Gregorian calendar
+    // http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+
+    days * 86_400 + i64::from(date_time.hour()) * 3600 + i64::from(date_time.minute()) * 60 + i64::from(date_time.second())
+}
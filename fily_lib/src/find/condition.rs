@@ -1,8 +1,20 @@
-use std::{io, fmt, error::Error};
-use super::{Filename, Filesize, FilePath, Modified, Accessed, Created, SearchCriteria};
+use std::{io, fmt, ffi::OsStr, fs::{read_dir, symlink_metadata}, path::{Path, PathBuf}, error::Error};
+use super::{Filename, Filesize, FilePath, Modified, Accessed, Created, DirectoryContains, Sibling, Reference, TextOrBinary, LineCount, TextEncoding, LineEnding, NameLint, Depth, Entropy, SearchCriteria};
+use super::file_contents::{detect_encoding, count_lines, detect_line_ending, entropy};
+#[cfg(feature = "xattr")]
+use super::{Xattr, get_xattr, list_xattrs};
+#[cfg(any(feature = "audio_tags", feature = "exif_tags"))]
+use super::TagText;
+#[cfg(feature = "audio_tags")]
+use super::AudioTag;
+#[cfg(feature = "exif_tags")]
+use super::ExifTag;
+#[cfg(feature = "exif_tags")]
+use crate::exif_tags::{ExifTags, ExifTagsError};
+#[cfg(feature = "audio_tags")]
+use crate::audio_tags::{AudioTags, AudioTagsError};
 use regex::Regex;
-use filetime::FileTime;
-use walkdir::DirEntry;
+use super::SearchEntry;
 use crate::fily_err::{Context, FilyError, PathOrFilenameError};
 #[allow(unused_imports)]
 use log::{trace, debug, info, warn, error};
@@ -86,6 +98,29 @@ impl<T> Condition<T> {
 pub enum ConditionEvalError {
     PathErr(FilyError<PathOrFilenameError>),
     IOErr(FilyError<io::Error>),
+
+    /// Failed to access a file or folder while walking the directory tree. The file
+    /// and, if it is a folder, everything in it was skipped
+    TraversalErr(FilyError<io::Error>),
+
+    /// Following a symlink lead to a folder that also contains the symlink. `ancestor` is
+    /// the folder the symlink points to and `child` is the path of the symlink
+    SymlinkLoop {
+        ancestor: PathBuf,
+        child: PathBuf,
+    },
+
+    /// Failed to read the tags of an audio file
+    #[cfg(feature = "audio_tags")]
+    AudioTagsErr(FilyError<AudioTagsError>),
+
+    /// Failed to read the EXIF data of a photo
+    #[cfg(feature = "exif_tags")]
+    ExifTagsErr(FilyError<ExifTagsError>),
+
+    /// Failed to read the git repository a file is in
+    #[cfg(feature = "git")]
+    GitErr(FilyError<git2::Error>),
 }
 
 impl Error for ConditionEvalError {}
@@ -96,6 +131,24 @@ impl fmt::Display for ConditionEvalError {
     }
 }
 
+impl From<walkdir::Error> for ConditionEvalError {
+    fn from(err: walkdir::Error) -> Self {
+        if let (Some(ancestor), Some(child)) = (err.loop_ancestor(), err.path()) {
+            return ConditionEvalError::SymlinkLoop {
+                ancestor: ancestor.to_path_buf(),
+                child: child.to_path_buf(),
+            };
+        }
+
+        let context = match err.path() {
+            Some(path) => format!("Failed to access {:?} while searching", path.display()),
+            None => "Failed to access a file while searching".to_string(),
+        };
+
+        ConditionEvalError::TraversalErr(FilyError::new(io::Error::from(err), context))
+    }
+}
+
 impl From<FilyError<PathOrFilenameError>> for ConditionEvalError {
     fn from(err: FilyError<PathOrFilenameError>) -> Self {
         ConditionEvalError::PathErr(err)
@@ -108,35 +161,86 @@ impl From<FilyError<io::Error>> for ConditionEvalError {
     }
 }
 
+#[cfg(feature = "audio_tags")]
+impl From<FilyError<AudioTagsError>> for ConditionEvalError {
+    fn from(err: FilyError<AudioTagsError>) -> Self {
+        ConditionEvalError::AudioTagsErr(err)
+    }
+}
+
+#[cfg(feature = "exif_tags")]
+impl From<FilyError<ExifTagsError>> for ConditionEvalError {
+    fn from(err: FilyError<ExifTagsError>) -> Self {
+        ConditionEvalError::ExifTagsErr(err)
+    }
+}
+
+#[cfg(feature = "git")]
+impl From<FilyError<git2::Error>> for ConditionEvalError {
+    fn from(err: FilyError<git2::Error>) -> Self {
+        ConditionEvalError::GitErr(err)
+    }
+}
+
 impl Condition<SearchCriteria> {
-    /// Checks if the file that `dir_entry` points to matches the condition
+    /// Returns `true` if any of the criterias in the condition needs the file on disk
+    ///
+    /// Look at `SearchCriteria::needs_filesystem` for more
+    #[must_use]
+    pub fn needs_filesystem(&self) -> bool {
+        match self {
+            Self::And(condition1, condition2) | Self::Or(condition1, condition2) => condition1.needs_filesystem() || condition2.needs_filesystem(),
+            Self::Not(condition) => condition.needs_filesystem(),
+            Self::Value(search_criteria) => search_criteria.needs_filesystem(),
+        }
+    }
+
+    /// Checks if the file that `entry` points to matches the condition
     ///
     /// Returns `true` if it does and `false` if it doesn't
     ///
     /// # Errors
     ///
     /// Fails if a file operation fails. i.e. Getting the filename, filesize...
-    pub fn evaluate(&self, dir_entry: &DirEntry) -> Result<bool, ConditionEvalError> {
+    pub fn evaluate(&self, entry: &impl SearchEntry) -> Result<bool, ConditionEvalError> {
         match self {
-            Self::And(condition1, condition2) => Ok(condition1.evaluate(dir_entry)? && condition2.evaluate(dir_entry)?),
-            Self::Not(condition) => Ok(!condition.evaluate(dir_entry)?),
-            Self::Or(condition1, condition2) => Ok(condition1.evaluate(dir_entry)? || condition2.evaluate(dir_entry)?),
+            Self::And(condition1, condition2) => Ok(condition1.evaluate(entry)? && condition2.evaluate(entry)?),
+            Self::Not(condition) => Ok(!condition.evaluate(entry)?),
+            Self::Or(condition1, condition2) => Ok(condition1.evaluate(entry)? || condition2.evaluate(entry)?),
             Self::Value(search_criteria) => {
                 Ok(match search_criteria {
-                    SearchCriteria::Filename(filename_options) => Self::filename_matches(dir_entry, filename_options)?,
-                    SearchCriteria::Filesize(filesize_options) => Self::filesize_matches(dir_entry, filesize_options)?,
-                    SearchCriteria::FilePath(filepath_options) => Self::filepath_matches(dir_entry, filepath_options)?,
-                    SearchCriteria::FilenameRegex(filename_regex) => Self::filename_regex_matches(dir_entry, filename_regex)?,
-                    SearchCriteria::Modified(modified_options) => Self::modification_time_matches(dir_entry, modified_options)?,
-                    SearchCriteria::Accessed(access_options) => Self::access_time_matches(dir_entry, access_options)?,
-                    SearchCriteria::Created(creation_options) => Self::creation_time_matches(dir_entry, creation_options)?,
+                    SearchCriteria::Filename(filename_options) => Self::filename_matches(entry, filename_options)?,
+                    SearchCriteria::Filesize(filesize_options) => Self::filesize_matches(entry, filesize_options)?,
+                    SearchCriteria::FilePath(filepath_options) => Self::filepath_matches(entry, filepath_options)?,
+                    SearchCriteria::FilenameRegex(filename_regex) => Self::filename_regex_matches(entry, filename_regex)?,
+                    SearchCriteria::Modified(modified_options) => Self::modification_time_matches(entry, modified_options)?,
+                    SearchCriteria::Accessed(access_options) => Self::access_time_matches(entry, access_options)?,
+                    SearchCriteria::Created(creation_options) => Self::creation_time_matches(entry, creation_options)?,
+                    SearchCriteria::DirectoryContains(contains_options) => Self::directory_contains_matches(entry, contains_options)?,
+                    SearchCriteria::Sibling(sibling_options) => Self::sibling_matches(entry, sibling_options)?,
+                    SearchCriteria::Reference(reference_options) => Self::reference_matches(entry, reference_options)?,
+                    SearchCriteria::TextOrBinary(text_or_binary) => Self::text_or_binary_matches(entry, *text_or_binary)?,
+                    SearchCriteria::LineCount(line_count_options) => Self::line_count_matches(entry, line_count_options)?,
+                    SearchCriteria::Encoding(encoding) => Self::encoding_matches(entry, *encoding)?,
+                    SearchCriteria::LineEnding(line_ending) => Self::line_ending_matches(entry, *line_ending)?,
+                    SearchCriteria::NameLint(name_lint) => Self::name_lint_matches(entry, *name_lint),
+                    SearchCriteria::Depth(depth_options) => Self::depth_matches(entry, depth_options),
+                    SearchCriteria::Entropy(entropy_options) => Self::entropy_matches(entry, entropy_options)?,
+                    #[cfg(feature = "audio_tags")]
+                    SearchCriteria::AudioTag(audio_tag_options) => Self::audio_tag_matches(entry, audio_tag_options)?,
+                    #[cfg(feature = "exif_tags")]
+                    SearchCriteria::ExifTag(exif_tag_options) => Self::exif_tag_matches(entry, exif_tag_options)?,
+                    #[cfg(feature = "xattr")]
+                    SearchCriteria::Xattr(xattr_options) => Self::xattr_matches(entry, xattr_options)?,
+                    #[cfg(feature = "git")]
+                    SearchCriteria::Git(git_options) => git_options.matches(entry.path(), entry.is_dir())?,
                 })
             }
         }
     }
 
-    fn filename_matches(dir_entry: &DirEntry, filename_options: &Filename) -> Result<bool, FilyError<PathOrFilenameError>> {
-        let path = dir_entry.path();
+    fn filename_matches(entry: &impl SearchEntry, filename_options: &Filename) -> Result<bool, FilyError<PathOrFilenameError>> {
+        let path = entry.path();
         let filename = path.file_name()
             .ok_or_else(|| FilyError::new_with_context(PathOrFilenameError::NoFilename, || format!("Failed to get filename of {:?}", path.display())))?
             .to_str()
@@ -148,11 +252,8 @@ impl Condition<SearchCriteria> {
         })
     }
 
-    fn filesize_matches(dir_entry: &DirEntry, filesize_options: &Filesize) -> Result<bool, FilyError<io::Error>> {
-        let filesize = dir_entry.metadata()
-            .map_err(io::Error::from)
-            .with_context(|| format!("Failed to get metadata of {:?}", dir_entry.path().display()))?
-            .len();
+    fn filesize_matches(entry: &impl SearchEntry, filesize_options: &Filesize) -> Result<bool, FilyError<io::Error>> {
+        let filesize = entry.size()?;
 
         Ok(match *filesize_options {
             Filesize::Exact(exact_size) => filesize == exact_size,
@@ -161,8 +262,8 @@ impl Condition<SearchCriteria> {
         })
     }
 
-    fn filepath_matches(dir_entry: &DirEntry, filepath_options: &FilePath) -> Result<bool, FilyError<PathOrFilenameError>> {
-        let path = dir_entry.path();
+    fn filepath_matches(entry: &impl SearchEntry, filepath_options: &FilePath) -> Result<bool, FilyError<PathOrFilenameError>> {
+        let path = entry.path();
         let path = path.to_str()
             .ok_or_else(|| FilyError::new_with_context(PathOrFilenameError::UTF8ConversionFailed, || format!("Failed to convert path {:?} to UTF-8", path.display())))?;
 
@@ -172,8 +273,8 @@ impl Condition<SearchCriteria> {
         })
     }
 
-    fn filename_regex_matches(dir_entry: &DirEntry, filename_regex: &Regex) -> Result<bool, FilyError<PathOrFilenameError>> {
-        let path = dir_entry.path();
+    fn filename_regex_matches(entry: &impl SearchEntry, filename_regex: &Regex) -> Result<bool, FilyError<PathOrFilenameError>> {
+        let path = entry.path();
         let filename = path.file_name()
             .ok_or_else(|| FilyError::new_with_context(PathOrFilenameError::NoFilename, || format!("Failed to get filename of {:?}", path.display())))?
             .to_str()
@@ -182,12 +283,8 @@ impl Condition<SearchCriteria> {
         Ok(filename_regex.is_match(filename))
     }
 
-    fn modification_time_matches(dir_entry: &DirEntry, modified_options: &Modified) -> Result<bool, FilyError<io::Error>> {
-        let metadata = dir_entry.metadata()
-            .map_err(io::Error::from)
-            .with_context(|| format!("Failed to get metadata of {:?}", dir_entry.path().display()))?;
-
-        let last_modification_time = FileTime::from_last_modification_time(&metadata).unix_seconds();
+    fn modification_time_matches(entry: &impl SearchEntry, modified_options: &Modified) -> Result<bool, FilyError<io::Error>> {
+        let last_modification_time = entry.modified()?.unix_seconds();
 
         Ok(match *modified_options {
             Modified::At(at_this_time) => last_modification_time == at_this_time,
@@ -196,12 +293,8 @@ impl Condition<SearchCriteria> {
         })
     }
 
-    fn access_time_matches(dir_entry: &DirEntry, access_options: &Accessed) -> Result<bool, FilyError<io::Error>> {
-        let metadata = dir_entry.metadata()
-            .map_err(io::Error::from)
-            .with_context(|| format!("Failed to get metadata of {:?}", dir_entry.path().display()))?;
-
-        let last_access_time = FileTime::from_last_access_time(&metadata).unix_seconds();
+    fn access_time_matches(entry: &impl SearchEntry, access_options: &Accessed) -> Result<bool, FilyError<io::Error>> {
+        let last_access_time = entry.accessed()?.unix_seconds();
 
         Ok(match *access_options {
             Accessed::At(at_this_time) => last_access_time == at_this_time,
@@ -210,14 +303,8 @@ impl Condition<SearchCriteria> {
         })
     }
 
-    fn creation_time_matches(dir_entry: &DirEntry, creation_options: &Created) -> Result<bool, FilyError<io::Error>> {
-        let metadata = dir_entry.metadata()
-            .map_err(io::Error::from)
-            .with_context(|| format!("Failed to get metadata of {:?}", dir_entry.path().display()))?;
-
-        let creation_time = FileTime::from_creation_time(&metadata)
-            .ok_or_else(|| FilyError::new_with_context(io::Error::new(io::ErrorKind::Other, "Unsupported"), || format!("Failed to get creation time of {:?}", dir_entry.path().display())))?
-            .unix_seconds();
+    fn creation_time_matches(entry: &impl SearchEntry, creation_options: &Created) -> Result<bool, FilyError<io::Error>> {
+        let creation_time = entry.created()?.unix_seconds();
 
         Ok(match *creation_options {
             Created::At(at_this_time) => creation_time == at_this_time,
@@ -225,4 +312,254 @@ impl Condition<SearchCriteria> {
             Created::After(after_this_time) => creation_time > after_this_time,
         })
     }
+
+    fn directory_contains_matches(entry: &impl SearchEntry, contains_options: &DirectoryContains) -> Result<bool, FilyError<io::Error>> {
+        if !entry.is_dir() {
+            return Ok(false);
+        }
+
+        let path = entry.path();
+
+        Ok(match contains_options {
+            DirectoryContains::Exact(name) => DirectoryContains::is_file_name(name) && Self::path_exists(&path.join(name))?,
+            DirectoryContains::Regex(regex) => {
+                let entries = read_dir(path)
+                    .with_context(|| format!("Failed to read contents of {:?}", path.display()))?;
+
+                let mut found = false;
+
+                for entry in entries {
+                    let entry = entry.with_context(|| format!("Failed to read an entry of {:?}", path.display()))?;
+
+                    if entry.file_name().to_str().is_some_and(|name| regex.is_match(name)) {
+                        found = true;
+                        break;
+                    }
+                }
+
+                found
+            }
+        })
+    }
+
+    fn sibling_matches(entry: &impl SearchEntry, sibling_options: &Sibling) -> Result<bool, FilyError<io::Error>> {
+        let path = entry.path();
+
+        let sibling_path = match sibling_options {
+            Sibling::Exact(name) => path.with_file_name(name),
+            Sibling::WithExtension(extension) => path.with_extension(extension),
+        };
+
+        if sibling_path == path {
+            return Ok(false);
+        }
+
+        Self::path_exists(&sibling_path)
+    }
+
+    fn reference_matches(entry: &impl SearchEntry, reference_options: &Reference) -> Result<bool, FilyError<io::Error>> {
+        let last_modification_time = entry.modified()?;
+
+        Ok(match reference_options {
+            Reference::NewerThan(reference_file) => last_modification_time > reference_file.modified(),
+            Reference::OlderThan(reference_file) => last_modification_time < reference_file.modified(),
+        })
+    }
+
+    fn text_or_binary_matches(entry: &impl SearchEntry, text_or_binary: TextOrBinary) -> Result<bool, FilyError<io::Error>> {
+        if !entry.is_file() {
+            return Ok(false);
+        }
+
+        let is_text = detect_encoding(entry.path())?.is_some();
+
+        Ok(match text_or_binary {
+            TextOrBinary::Text => is_text,
+            TextOrBinary::Binary => !is_text,
+        })
+    }
+
+    fn line_count_matches(entry: &impl SearchEntry, line_count_options: &LineCount) -> Result<bool, FilyError<io::Error>> {
+        if !entry.is_file() {
+            return Ok(false);
+        }
+
+        let line_count = count_lines(entry.path())?;
+
+        Ok(match *line_count_options {
+            LineCount::Exact(exact_count) => line_count == exact_count,
+            LineCount::Over(over_this_count) => line_count > over_this_count,
+            LineCount::Under(under_this_count) => line_count < under_this_count,
+        })
+    }
+
+    fn encoding_matches(entry: &impl SearchEntry, encoding: TextEncoding) -> Result<bool, FilyError<io::Error>> {
+        if !entry.is_file() {
+            return Ok(false);
+        }
+
+        Ok(match (detect_encoding(entry.path())?, encoding) {
+            (None, _) => false,
+            (Some(TextEncoding::Ascii), TextEncoding::Utf8) => true,
+            (Some(detected_encoding), encoding) => detected_encoding == encoding,
+        })
+    }
+
+    fn line_ending_matches(entry: &impl SearchEntry, line_ending: LineEnding) -> Result<bool, FilyError<io::Error>> {
+        if !entry.is_file() {
+            return Ok(false);
+        }
+
+        Ok(detect_line_ending(entry.path())? == line_ending)
+    }
+
+    fn name_lint_matches(entry: &impl SearchEntry, name_lint: NameLint) -> bool {
+        let path = entry.path();
+
+        if let NameLint::PathLongerThan(max_length) = name_lint {
+            return path.as_os_str().len() > max_length;
+        }
+
+        path.file_name().is_some_and(|name| Self::name_has_problem(name, name_lint))
+    }
+
+    fn name_has_problem(name: &OsStr, name_lint: NameLint) -> bool {
+        /// Names that can't be used on Windows, no matter the extension
+        const WINDOWS_RESERVED_NAMES: &[&str] = &[
+            "CON", "PRN", "AUX", "NUL",
+            "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9",
+            "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+        ];
+
+        if let NameLint::NameLongerThan(max_length) = name_lint {
+            return name.len() > max_length;
+        }
+
+        // Invalid unicode gets replaced with U+FFFD which is not ASCII
+        let name = name.to_string_lossy();
+
+        match name_lint {
+            NameLint::LeadingOrTrailingWhitespace => name.starts_with(char::is_whitespace) || name.ends_with(char::is_whitespace),
+            NameLint::ControlCharacters => name.chars().any(char::is_control),
+            NameLint::NonAscii => !name.is_ascii(),
+            NameLint::WindowsReservedName => {
+                // Everything after the first dot is ignored and so are spaces before it
+                let stem = name.split('.').next().unwrap_or_default().trim_end_matches(' ');
+
+                WINDOWS_RESERVED_NAMES.iter().any(|reserved_name| stem.eq_ignore_ascii_case(reserved_name))
+            }
+            NameLint::InvalidWindowsCharacters => {
+                name.chars().any(|c| c.is_ascii_control() || matches!(c, '<' | '>' | ':' | '"' | '\\' | '|' | '?' | '*'))
+                    || name.ends_with('.')
+                    || name.ends_with(' ')
+            }
+            NameLint::NameLongerThan(_) | NameLint::PathLongerThan(_) => unreachable!("Handled above"),
+        }
+    }
+
+    fn depth_matches(entry: &impl SearchEntry, depth_options: &Depth) -> bool {
+        let depth = entry.depth();
+
+        match *depth_options {
+            Depth::Exact(exact_depth) => depth == exact_depth,
+            Depth::Over(over_this_depth) => depth > over_this_depth,
+            Depth::Under(under_this_depth) => depth < under_this_depth,
+        }
+    }
+
+    fn entropy_matches(entry: &impl SearchEntry, entropy_options: &Entropy) -> Result<bool, FilyError<io::Error>> {
+        if !entry.is_file() {
+            return Ok(false);
+        }
+
+        Ok(match *entropy_options {
+            Entropy::Over(over_this_entropy, sample) => entropy(entry.path(), sample)? > over_this_entropy,
+            Entropy::Under(under_this_entropy, sample) => entropy(entry.path(), sample)? < under_this_entropy,
+        })
+    }
+
+    #[cfg(feature = "audio_tags")]
+    fn audio_tag_matches(entry: &impl SearchEntry, audio_tag_options: &AudioTag) -> Result<bool, FilyError<AudioTagsError>> {
+        if !entry.is_file() {
+            return Ok(false);
+        }
+
+        let audio_tags = match AudioTags::read(entry.path()) {
+            Ok(audio_tags) => audio_tags,
+            Err(e) if matches!(e.get_error(), AudioTagsError::UnsupportedFormat) => return Ok(false),
+            Err(e) => return Err(e),
+        };
+
+        Ok(match audio_tag_options {
+            AudioTag::Artist(tag_text) => Self::tag_text_matches(audio_tags.artist.as_deref(), tag_text),
+            AudioTag::Album(tag_text) => Self::tag_text_matches(audio_tags.album.as_deref(), tag_text),
+            AudioTag::Title(tag_text) => Self::tag_text_matches(audio_tags.title.as_deref(), tag_text),
+            AudioTag::Genre(tag_text) => Self::tag_text_matches(audio_tags.genre.as_deref(), tag_text),
+            AudioTag::YearAt(at_this_year) => audio_tags.year == Some(*at_this_year),
+            AudioTag::YearBefore(before_this_year) => audio_tags.year.is_some_and(|year| year < *before_this_year),
+            AudioTag::YearAfter(after_this_year) => audio_tags.year.is_some_and(|year| year > *after_this_year),
+            AudioTag::DurationOver(over_this_duration) => audio_tags.duration.is_some_and(|duration| duration > *over_this_duration),
+            AudioTag::DurationUnder(under_this_duration) => audio_tags.duration.is_some_and(|duration| duration < *under_this_duration),
+        })
+    }
+
+    #[cfg(feature = "exif_tags")]
+    fn exif_tag_matches(entry: &impl SearchEntry, exif_tag_options: &ExifTag) -> Result<bool, FilyError<ExifTagsError>> {
+        if !entry.is_file() {
+            return Ok(false);
+        }
+
+        let exif_tags = match ExifTags::read(entry.path()) {
+            Ok(exif_tags) => exif_tags,
+            Err(e) if matches!(e.get_error(), ExifTagsError::NoExif) => return Ok(false),
+            Err(e) => return Err(e),
+        };
+
+        Ok(match exif_tag_options {
+            ExifTag::TakenAt(at_this_time) => exif_tags.date_time_original.is_some_and(|taken| at_this_time.contains(&taken)),
+            ExifTag::TakenBefore(before_this_time) => exif_tags.date_time_original.is_some_and(|taken| taken < *before_this_time),
+            ExifTag::TakenAfter(after_this_time) => exif_tags.date_time_original.is_some_and(|taken| taken > *after_this_time),
+            ExifTag::Make(tag_text) => Self::tag_text_matches(exif_tags.make.as_deref(), tag_text),
+            ExifTag::Model(tag_text) => Self::tag_text_matches(exif_tags.model.as_deref(), tag_text),
+            ExifTag::HasGps(has_gps) => exif_tags.has_gps == *has_gps,
+            ExifTag::Orientation(orientation) => exif_tags.orientation == Some(*orientation),
+        })
+    }
+
+    /// Checks the text of an audio or EXIF tag. A missing tag never matches
+    #[cfg(any(feature = "audio_tags", feature = "exif_tags"))]
+    fn tag_text_matches(text: Option<&str>, tag_text: &TagText) -> bool {
+        text.is_some_and(|text| match tag_text {
+            TagText::Exact(exact_text) => text == exact_text,
+            TagText::Contains(substring) => text.contains(substring.as_str()),
+            TagText::Regex(regex) => regex.is_match(text),
+        })
+    }
+
+    #[cfg(feature = "xattr")]
+    fn xattr_matches(entry: &impl SearchEntry, xattr_options: &Xattr) -> Result<bool, FilyError<io::Error>> {
+        let path = entry.path();
+
+        // A symlink that wasn't followed has its own attributes
+        let follow_symlinks = !entry.is_symlink();
+
+        Ok(match xattr_options {
+            Xattr::Any => !list_xattrs(path, follow_symlinks)?.is_empty(),
+            Xattr::Exists(name) => get_xattr(path, name, follow_symlinks)?.is_some(),
+            Xattr::Equals(name, value) => get_xattr(path, name, follow_symlinks)?.is_some_and(|xattr_value| xattr_value == value.as_bytes()),
+            Xattr::Regex(name, regex) => get_xattr(path, name, follow_symlinks)?.is_some_and(|xattr_value| regex.is_match(&String::from_utf8_lossy(&xattr_value))),
+        })
+    }
+
+    /// Checks if something exists at `path` without following symlinks
+    ///
+    /// Unlike `Path::exists` this only returns `false` if the file really doesn't exist and
+    /// returns all other errors
+    fn path_exists(path: &Path) -> Result<bool, FilyError<io::Error>> {
+        match symlink_metadata(path) {
+            Ok(_) => Ok(true),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(FilyError::new_with_context(e, || format!("Failed to get metadata of {:?}", path.display()))),
+        }
+    }
 }
@@ -1,11 +1,25 @@
 use std::{convert::TryFrom, fmt, error::Error};
 use super::{Condition, SearchCriteria, SearchCriteriaParsingError};
 
-// TODO: All of this
-
 #[derive(Debug, Clone, PartialEq)]
 pub enum ConditionParsingError {
-    SearchCriteriaParsingError(SearchCriteriaParsingError),
+    /// A criteria couldn't be parsed. Contains the byte offset the criteria starts at
+    SearchCriteriaParsingError(SearchCriteriaParsingError, usize),
+
+    /// The expression is empty
+    Empty,
+
+    /// The expression ended where a criteria, `!` or `(` was expected
+    UnexpectedEnd,
+
+    /// A character that isn't allowed at this point was found. Contains its byte offset
+    UnexpectedCharacter(usize),
+
+    /// A `(` is never closed. Contains its byte offset
+    UnclosedParenthesis(usize),
+
+    /// A value that starts with a `"` never ends. Contains the byte offset of the `"`
+    UnclosedQuote(usize),
 }
 
 impl Error for ConditionParsingError {}
@@ -16,16 +30,186 @@ impl fmt::Display for ConditionParsingError {
     }
 }
 
-impl From<SearchCriteriaParsingError> for ConditionParsingError {
-    fn from(error: SearchCriteriaParsingError) -> Self {
-        Self::SearchCriteriaParsingError(error)
+impl TryFrom<&str> for Condition<SearchCriteria> {
+    type Error = ConditionParsingError;
+
+    /// Parses a condition from an expression like this:
+    ///
+    /// `"filename_contains"=report & !("filesize_over"=1000000 | "filenameregex"="\.tmp$")`
+    ///
+    /// * A criteria is written like it is expected by `SearchCriteria::try_from`
+    /// * `&` requires both sides to match, `|` requires at least one of them to match and `!` negates the condition after it
+    /// * `!` binds stronger than `&` and `&` binds stronger than `|`. Parentheses can be used to group conditions
+    ///
+    /// A value ends at the first whitespace, `&`, `|` or `)`. Values that contain one of these have to be put in double
+    /// quotes, in which `\"` is a double quote and `\\` is a backslash
+    fn try_from(condition_str: &str) -> Result<Self, Self::Error> {
+        let mut parser = ExpressionParser {
+            text: condition_str,
+            position: 0,
+        };
+
+        parser.skip_whitespace();
+
+        if parser.peek().is_none() {
+            return Err(ConditionParsingError::Empty);
+        }
+
+        let condition = parser.parse_or()?;
+
+        parser.skip_whitespace();
+
+        match parser.peek() {
+            None => Ok(condition),
+            Some(_) => Err(ConditionParsingError::UnexpectedCharacter(parser.position)),
+        }
     }
 }
 
-impl TryFrom<&str> for Condition<SearchCriteria> {
-    type Error = ConditionParsingError;
+struct ExpressionParser<'a> {
+    text: &'a str,
+    position: usize,
+}
+
+impl ExpressionParser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.text[self.position..].chars().next()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek() {
+            if !c.is_whitespace() {
+                break;
+            }
+
+            self.position += c.len_utf8();
+        }
+    }
+
+    /// Consumes `c` if it comes next, ignoring whitespace in front of it
+    fn consume(&mut self, c: char) -> bool {
+        self.skip_whitespace();
+
+        if self.peek() == Some(c) {
+            self.position += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Condition<SearchCriteria>, ConditionParsingError> {
+        let mut condition = self.parse_and()?;
+
+        while self.consume('|') {
+            condition = Condition::Or(Box::from(condition), Box::from(self.parse_and()?));
+        }
+
+        Ok(condition)
+    }
+
+    fn parse_and(&mut self) -> Result<Condition<SearchCriteria>, ConditionParsingError> {
+        let mut condition = self.parse_not()?;
+
+        while self.consume('&') {
+            condition = Condition::And(Box::from(condition), Box::from(self.parse_not()?));
+        }
+
+        Ok(condition)
+    }
+
+    fn parse_not(&mut self) -> Result<Condition<SearchCriteria>, ConditionParsingError> {
+        if self.consume('!') {
+            return Ok(Condition::Not(Box::from(self.parse_not()?)));
+        }
+
+        let start = self.position;
+
+        if self.consume('(') {
+            let condition = self.parse_or()?;
+
+            if !self.consume(')') {
+                return Err(match self.peek() {
+                    None => ConditionParsingError::UnclosedParenthesis(start),
+                    Some(_) => ConditionParsingError::UnexpectedCharacter(self.position),
+                });
+            }
+
+            return Ok(condition);
+        }
+
+        self.parse_criteria()
+    }
+
+    /// Parses `"<criteria_name>"=<value>`
+    fn parse_criteria(&mut self) -> Result<Condition<SearchCriteria>, ConditionParsingError> {
+        self.skip_whitespace();
+
+        let start = self.position;
+
+        match self.peek() {
+            None => return Err(ConditionParsingError::UnexpectedEnd),
+            Some('"') => (),
+            Some(_) => return Err(ConditionParsingError::UnexpectedCharacter(start)),
+        }
+
+        let name_length = self.text[start + 1..]
+            .find('"')
+            .ok_or(ConditionParsingError::UnclosedQuote(start))?;
+
+        self.position = start + 1 + name_length + 1;
+
+        if self.peek() != Some('=') {
+            return Err(ConditionParsingError::SearchCriteriaParsingError(SearchCriteriaParsingError::NoValue, start));
+        }
+
+        self.position += 1;
+
+        let value = self.parse_value()?;
+        let criteria = format!("{}={}", &self.text[start..=start + name_length + 1], value);
+
+        SearchCriteria::try_from(criteria.as_str())
+            .map(Condition::Value)
+            .map_err(|e| ConditionParsingError::SearchCriteriaParsingError(e, start))
+    }
+
+    fn parse_value(&mut self) -> Result<String, ConditionParsingError> {
+        let start = self.position;
+
+        if self.peek() != Some('"') {
+            let length = self.text[start..]
+                .find(|c: char| c.is_whitespace() || c == '&' || c == '|' || c == ')')
+                .unwrap_or(self.text.len() - start);
+
+            self.position += length;
+
+            return Ok(self.text[start..self.position].to_string());
+        }
+
+        self.position += 1;
+
+        let mut value = String::new();
+        let mut escaped = false;
+
+        for c in self.text[start + 1..].chars() {
+            self.position += c.len_utf8();
+
+            match c {
+                _ if escaped => {
+                    // Only quotes and backslashes need to be escaped, everything else is kept as is so regexes work
+                    if c != '"' && c != '\\' {
+                        value.push('\\');
+                    }
+
+                    value.push(c);
+                    escaped = false;
+                }
+                '\\' => escaped = true,
+                '"' => return Ok(value),
+                _ => value.push(c),
+            }
+        }
 
-    fn try_from(_condition_str: &str) -> Result<Self, Self::Error> {
-        todo!("no idea how to implement this");
+        Err(ConditionParsingError::UnclosedQuote(start))
     }
 }
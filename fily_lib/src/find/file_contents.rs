@@ -0,0 +1,278 @@
+use std::{path::Path, fs::File, io::{self, Read, Seek, SeekFrom}};
+use super::{TextEncoding, LineEnding, EntropySample};
+use crate::fily_err::{Context, FilyError};
+#[allow(unused_imports)]
+use log::{trace, debug, info, warn, error};
+
+/// How many bytes at the start of a file are looked at to guess if it is text or binary
+/// and which encoding it has
+pub const TEXT_DETECTION_BYTES: usize = 8 * 1024;
+
+const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
+const UTF16_LE_BOM: &[u8] = &[0xFF, 0xFE];
+const UTF16_BE_BOM: &[u8] = &[0xFE, 0xFF];
+
+/// Reads up to `TEXT_DETECTION_BYTES` bytes from the start of the file
+fn read_start(path: &Path) -> Result<Vec<u8>, FilyError<io::Error>> {
+    let file = File::open(path)
+        .with_context(|| format!("Failed to open {:?}", path.display()))?;
+
+    let mut start = Vec::with_capacity(TEXT_DETECTION_BYTES);
+
+    file.take(TEXT_DETECTION_BYTES as u64)
+        .read_to_end(&mut start)
+        .with_context(|| format!("Failed to read {:?}", path.display()))?;
+
+    Ok(start)
+}
+
+/// Guesses the encoding of the file `path` points to from its first `TEXT_DETECTION_BYTES` bytes
+///
+/// Returns `None` if the file looks like a binary file. Like git, a file counts as binary if
+/// there is a null byte in it. Files that start with a UTF-16 byte order mark are text even though they
+/// usually contain null bytes
+pub(crate) fn detect_encoding(path: &Path) -> Result<Option<TextEncoding>, FilyError<io::Error>> {
+    let start = read_start(path)?;
+
+    if start.starts_with(UTF8_BOM) {
+        return Ok(Some(TextEncoding::Utf8Bom));
+    } else if start.starts_with(UTF16_LE_BOM) {
+        return Ok(Some(TextEncoding::Utf16Le));
+    } else if start.starts_with(UTF16_BE_BOM) {
+        return Ok(Some(TextEncoding::Utf16Be));
+    }
+
+    if start.contains(&0) {
+        return Ok(None);
+    }
+
+    if start.is_ascii() {
+        return Ok(Some(TextEncoding::Ascii));
+    }
+
+    Ok(Some(match std::str::from_utf8(&start) {
+        Ok(_) => TextEncoding::Utf8,
+        // The last character could be cut off since only the start of the file was read
+        Err(e) if e.error_len().is_none() => TextEncoding::Utf8,
+        Err(_) => TextEncoding::Unknown,
+    }))
+}
+
+/// Counts the lines of the file `path` points to
+///
+/// The last line counts even if it doesn't end with a newline. An empty file has 0 lines
+pub(crate) fn count_lines(path: &Path) -> Result<u64, FilyError<io::Error>> {
+    let mut line_count = 0;
+    let mut last_byte = None;
+
+    // Not worth pulling in the bytecount crate just for this
+    #[allow(clippy::naive_bytecount)]
+    read_chunks(path, |chunk| {
+        line_count += chunk.iter().filter(|&&byte| byte == b'\n').count() as u64;
+        last_byte = chunk.last().copied();
+    })?;
+
+    if last_byte.is_some_and(|last_byte| last_byte != b'\n') {
+        line_count += 1;
+    }
+
+    Ok(line_count)
+}
+
+/// Finds out which line endings the file `path` points to uses
+pub(crate) fn detect_line_ending(path: &Path) -> Result<LineEnding, FilyError<io::Error>> {
+    let mut has_lf = false;
+    let mut has_crlf = false;
+    let mut previous_byte = None;
+
+    read_chunks(path, |chunk| {
+        for &byte in chunk {
+            if byte == b'\n' {
+                if previous_byte == Some(b'\r') {
+                    has_crlf = true;
+                } else {
+                    has_lf = true;
+                }
+            }
+
+            previous_byte = Some(byte);
+        }
+    })?;
+
+    Ok(match (has_lf, has_crlf) {
+        (false, false) => LineEnding::None,
+        (true, false) => LineEnding::Lf,
+        (false, true) => LineEnding::Crlf,
+        (true, true) => LineEnding::Mixed,
+    })
+}
+
+/// Calculates the Shannon entropy of the bytes of the file `path` points to in bits per byte
+///
+/// Only the part of the file `sample` specifies is read. Returns a value between 0 and 8. Empty files have an entropy of 0
+pub(crate) fn entropy(path: &Path, sample: EntropySample) -> Result<f64, FilyError<io::Error>> {
+    let mut byte_counts = [0_u64; 256];
+
+    let mut count_bytes = |chunk: &[u8]| {
+        for &byte in chunk {
+            byte_counts[usize::from(byte)] += 1;
+        }
+    };
+
+    match sample {
+        EntropySample::Full => read_chunks(path, count_bytes)?,
+        EntropySample::Head(max_bytes) => {
+            let file = File::open(path)
+                .with_context(|| format!("Failed to open {:?}", path.display()))?;
+
+            let mut head = Vec::new();
+
+            file.take(max_bytes)
+                .read_to_end(&mut head)
+                .with_context(|| format!("Failed to read {:?}", path.display()))?;
+
+            count_bytes(&head);
+        }
+        EntropySample::Blocks { count, block_size } => {
+            let mut file = File::open(path)
+                .with_context(|| format!("Failed to open {:?}", path.display()))?;
+
+            let file_size = file.metadata()
+                .with_context(|| format!("Failed to get metadata of {:?}", path.display()))?
+                .len();
+
+            let count = count.max(1) as u64;
+
+            if file_size <= count.saturating_mul(block_size) {
+                read_chunks(path, count_bytes)?;
+            } else {
+                // The blocks are spread out evenly from the start to the end of the file
+                let distance = if count == 1 { 0 } else { (file_size - block_size) / (count - 1) };
+                let mut block = Vec::new();
+
+                for i in 0..count {
+                    file.seek(SeekFrom::Start(i * distance))
+                        .with_context(|| format!("Failed to seek in {:?}", path.display()))?;
+
+                    block.clear();
+
+                    (&mut file).take(block_size)
+                        .read_to_end(&mut block)
+                        .with_context(|| format!("Failed to read {:?}", path.display()))?;
+
+                    count_bytes(&block);
+                }
+            }
+        }
+    }
+
+    let total: u64 = byte_counts.iter().sum();
+
+    if total == 0 {
+        return Ok(0.0);
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    let entropy = byte_counts.iter()
+        .filter(|&&byte_count| byte_count > 0)
+        .map(|&byte_count| {
+            let probability = byte_count as f64 / total as f64;
+            -probability * probability.log2()
+        })
+        .sum();
+
+    Ok(entropy)
+}
+
+/// Reads the whole file in chunks so big files don't have to fit into memory
+fn read_chunks<F: FnMut(&[u8])>(path: &Path, mut f: F) -> Result<(), FilyError<io::Error>> {
+    let mut file = File::open(path)
+        .with_context(|| format!("Failed to open {:?}", path.display()))?;
+
+    let mut buffer = vec![0_u8; 64 * 1024];
+
+    loop {
+        let bytes_read = match file.read(&mut buffer) {
+            Ok(0) => return Ok(()),
+            Ok(bytes_read) => bytes_read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(FilyError::new_with_context(e, || format!("Failed to read {:?}", path.display()))),
+        };
+
+        f(&buffer[..bytes_read]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{detect_encoding, count_lines, detect_line_ending, entropy};
+    use crate::find::{TextEncoding, LineEnding, EntropySample};
+
+    #[test]
+    fn text_detection_test() {
+        let path = std::env::temp_dir().join(format!("fily_text_detection_test_{}", std::process::id()));
+
+        let check = |content: &[u8]| {
+            std::fs::write(&path, content).unwrap();
+            (detect_encoding(&path).unwrap(), count_lines(&path).unwrap(), detect_line_ending(&path).unwrap())
+        };
+
+        let empty = check(b"");
+        let lf = check(b"a\nb\n");
+        let crlf = check(b"a\r\nb\r\n");
+        let mixed = check(b"a\r\nb\nc");
+        let no_final_newline = check("\u{e4}\nb".as_bytes());
+        let no_newline = check(b"a\rb");
+        let utf8_bom = check(b"\xEF\xBB\xBFa\n");
+        let utf16_le = check(b"\xFF\xFEa\0\n\0");
+        let latin1 = check(b"\xE4\n");
+        let binary = check(b"a\0b\n");
+
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(empty, (Some(TextEncoding::Ascii), 0, LineEnding::None));
+        assert_eq!(lf, (Some(TextEncoding::Ascii), 2, LineEnding::Lf));
+        assert_eq!(crlf, (Some(TextEncoding::Ascii), 2, LineEnding::Crlf));
+        assert_eq!(mixed, (Some(TextEncoding::Ascii), 3, LineEnding::Mixed));
+        assert_eq!(no_final_newline, (Some(TextEncoding::Utf8), 2, LineEnding::Lf));
+        assert_eq!(no_newline, (Some(TextEncoding::Ascii), 1, LineEnding::None));
+        assert_eq!(utf8_bom.0, Some(TextEncoding::Utf8Bom));
+        assert_eq!(utf16_le.0, Some(TextEncoding::Utf16Le));
+        assert_eq!(latin1.0, Some(TextEncoding::Unknown));
+        assert_eq!(binary.0, None);
+    }
+
+    #[test]
+    fn entropy_test() {
+        let path = std::env::temp_dir().join(format!("fily_entropy_test_{}", std::process::id()));
+
+        // Bigger than the default sample so only some blocks of it are read
+        let size = 1024 * 1024;
+
+        let check = |content: &[u8]| {
+            std::fs::write(&path, content).unwrap();
+            (entropy(&path, EntropySample::default()).unwrap(), entropy(&path, EntropySample::Full).unwrap())
+        };
+
+        // xorshift, good enough to look random
+        let mut state = 0x2545_F491_u32;
+        let random: Vec<u8> = (0..size)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state.to_le_bytes()[0]
+            })
+            .collect();
+
+        let zeroes = check(&vec![0_u8; size]);
+        let random = check(&random);
+        let empty = check(&[]);
+
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(zeroes.0 < 0.01 && zeroes.1 < 0.01, "{:?}", zeroes);
+        assert!(random.0 > 7.9 && random.1 > 7.9, "{:?}", random);
+        assert!(empty.0 < 0.01 && empty.1 < 0.01, "{:?}", empty);
+    }
+}
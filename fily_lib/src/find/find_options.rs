@@ -1,5 +1,6 @@
-use std::{error::Error, convert::TryInto};
-use super::{Condition, SearchCriteria, Ignore};
+use std::{path::{Path, PathBuf}, error::Error, convert::TryInto};
+use super::{Condition, SearchCriteria, Ignore, ListSeparator, ListFileError, read_paths_from_file, read_patterns_from_file};
+use crate::fily_err::FilyError;
 
 /// Stores options for `find`
 ///
@@ -11,6 +12,7 @@ use super::{Condition, SearchCriteria, Ignore};
 /// provides a config that matches anything. So you only have to change the options
 /// you care about
 #[derive(Debug, Clone)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct FindOptions {
     /// Contains a vec of `Condition<SearchCriteria>` with which you
     /// you can define what criteria a file should or should not match.
@@ -37,6 +39,44 @@ pub struct FindOptions {
     /// it will check the conditions against the symlink itself, not the file it
     /// points to
     pub follow_symlinks: bool,
+
+    /// If paths in `paths_to_search_in` that point to the same folder or to a folder
+    /// inside of another path should be merged. Without this, files in overlapping paths
+    /// are found once per path
+    pub merge_overlapping_paths: bool,
+
+    /// Report every file only once even if there are multiple hard links to it. Only the
+    /// first link that matches is returned. This is only supported on Unix platforms
+    pub unique_inodes: bool,
+
+    /// Also search inside of zip and tar archives (`.zip`, `.tar`, `.tar.gz` and `.tgz`). Files
+    /// inside of an archive are returned as the path to the archive followed by `!/` and their
+    /// path inside of the archive. Only the name, path, size and modification time of them can be checked.
+    /// Archives aren't searched at all if a condition uses a criteria that needs the file on disk, like
+    /// `Sibling`, `TextOrBinary` or `Xattr`. Look at `SearchCriteria::needs_filesystem` for that.
+    /// The search depth limits don't apply inside of archives.
+    /// This does nothing if the `archives` feature is disabled
+    pub search_in_archives: bool,
+
+    /// Limits how many archives deep it searches if archives contain other archives.
+    /// 1 means it only searches in archives that aren't inside of another archive.
+    /// 0 means it doesn't search in any archives, the same as if `search_in_archives` is `false`.
+    /// Archives inside of archives that are larger than `MAX_NESTED_ARCHIVE_SIZE` are never searched
+    pub max_archive_depth: usize,
+
+    /// Paths that are searched in addition to the ones passed to `find`, `find_in_index`
+    /// or `FindWatcher::new`. They are searched after those
+    pub additional_paths_to_search_in: Vec<PathBuf>,
+}
+
+impl FindOptions {
+    /// Returns `paths_to_search_in` followed by `additional_paths_to_search_in`
+    pub(crate) fn all_paths_to_search_in<'a, P: AsRef<Path>>(&'a self, paths_to_search_in: &'a [P]) -> Vec<&'a Path> {
+        paths_to_search_in.iter()
+            .map(AsRef::as_ref)
+            .chain(self.additional_paths_to_search_in.iter().map(PathBuf::as_path))
+            .collect()
+    }
 }
 
 impl Default for FindOptions {
@@ -49,6 +89,11 @@ impl Default for FindOptions {
             ignore: None,
             ignore_hidden_files: false,
             follow_symlinks: false,
+            merge_overlapping_paths: false,
+            unique_inodes: false,
+            search_in_archives: false,
+            max_archive_depth: 1,
+            additional_paths_to_search_in: Vec::new(),
         }
     }
 }
@@ -59,6 +104,16 @@ pub struct FindOptionsBuilder {
     find_options: FindOptions
 }
 
+impl From<FindOptions> for FindOptionsBuilder {
+    /// Creates a builder that starts with `find_options` instead of the default
+    #[inline]
+    fn from(find_options: FindOptions) -> Self {
+        FindOptionsBuilder {
+            find_options
+        }
+    }
+}
+
 impl FindOptionsBuilder {
     /// Creates and returns a new `FindOptionsBuilder`. The containing `FindOptions`
     /// is instantiated with its default implementation
@@ -122,8 +177,12 @@ impl FindOptionsBuilder {
         self
     }
 
-    /// Adds a condition from a `&str`. This can fail.
-    /// Also this isn't actually implemented right now so it'll just panic if you call this
+    /// Adds a condition from an expression like `"filename_contains"=report & !"filesize_over"=1000`.
+    /// Look at the `TryFrom<&str>` implementation of `Condition` for how it looks like
+    ///
+    /// # Errors
+    ///
+    /// Fails if the expression can't be parsed
     #[inline]
     pub fn add_condition_from_str(&mut self, condition_str: &str) -> Result<&mut Self, Box<dyn Error>> {
         self.find_options.options.push(condition_str.try_into()?);
@@ -183,4 +242,80 @@ impl FindOptionsBuilder {
         self.find_options.follow_symlinks = follow_symlinks;
         self
     }
+
+    /// Sets if paths to search in that overlap should be merged so the files in them only get
+    /// found once
+    #[inline]
+    pub fn set_merge_overlapping_paths(&mut self, merge_overlapping_paths: bool) -> &mut Self {
+        self.find_options.merge_overlapping_paths = merge_overlapping_paths;
+        self
+    }
+
+    /// Sets if files with multiple hard links should only be returned once
+    #[inline]
+    pub fn set_unique_inodes(&mut self, unique_inodes: bool) -> &mut Self {
+        self.find_options.unique_inodes = unique_inodes;
+        self
+    }
+
+    /// Sets if it should search inside of zip and tar archives
+    #[inline]
+    pub fn set_search_in_archives(&mut self, search_in_archives: bool) -> &mut Self {
+        self.find_options.search_in_archives = search_in_archives;
+        self
+    }
+
+    /// Sets how many archives deep it'll search in archives that contain other archives.
+    /// 0 disables searching in archives
+    ///
+    /// Default is 1
+    #[inline]
+    pub fn set_max_archive_depth(&mut self, max_archive_depth: usize) -> &mut Self {
+        self.find_options.max_archive_depth = max_archive_depth;
+        self
+    }
+
+    /// Adds paths that are searched in addition to the ones passed to `find`
+    #[inline]
+    pub fn add_paths_to_search_in(&mut self, mut paths_to_search_in: Vec<PathBuf>) -> &mut Self {
+        self.find_options.additional_paths_to_search_in.append(&mut paths_to_search_in);
+        self
+    }
+
+    /// Reads a list of paths from the file `list_file` points to and adds them to the paths that
+    /// are searched in addition to the ones passed to `find`
+    ///
+    /// Look at `ListSeparator` for how the file should look like
+    ///
+    /// # Errors
+    ///
+    /// Fails if the file can't be read
+    pub fn add_paths_to_search_in_from_file(&mut self, list_file: impl AsRef<Path>, separator: ListSeparator) -> Result<&mut Self, FilyError<ListFileError>> {
+        let paths_to_search_in = read_paths_from_file(list_file, separator)?;
+
+        Ok(self.add_paths_to_search_in(paths_to_search_in))
+    }
+
+    /// Reads a list of regexes from the file `list_file` points to and adds a condition that
+    /// requires the filename to match none of them
+    ///
+    /// Look at `ListSeparator` for how the file should look like. No condition is added if the file
+    /// has no patterns, i.e. only comments
+    ///
+    /// # Errors
+    ///
+    /// Fails if the file can't be read or one of the entries isn't a valid regex
+    pub fn add_exclude_patterns_from_file(&mut self, list_file: impl AsRef<Path>, separator: ListSeparator) -> Result<&mut Self, FilyError<ListFileError>> {
+        let patterns = read_patterns_from_file(list_file, separator)?;
+
+        if patterns.is_empty() {
+            return Ok(self);
+        }
+
+        let search_criterias = patterns.into_iter()
+            .map(SearchCriteria::FilenameRegex)
+            .collect();
+
+        Ok(self.add_none_of_condition(search_criterias))
+    }
 }
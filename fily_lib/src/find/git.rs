@@ -0,0 +1,339 @@
+use std::{
+    path::{Path, PathBuf},
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+    convert::TryFrom,
+};
+use git2::{Repository, Status, StatusOptions, ErrorCode, Sort};
+use super::{ConditionEvalError, SearchCriteriaParsingError};
+use crate::fily_err::{Context, FilyError};
+#[allow(unused_imports)]
+use log::{trace, debug, info, warn, error};
+
+/// Checks the state of a file in the git repository it is in
+///
+/// Files that aren't inside of a git repository never match. Repositories inside of
+/// other repositories (i.e. submodules) aren't looked at separately, their files
+/// belong to the outer repository.
+///
+/// Folders only match `Ignored`. Everything else only looks at files
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum GitState {
+    /// The file isn't tracked and isn't ignored
+    Untracked,
+
+    /// The file or a folder it is in is ignored by a `.gitignore` or `.git/info/exclude`
+    Ignored,
+
+    /// The file is modified in the working tree compared to the index
+    Modified,
+
+    /// The file has changes in the index that aren't committed yet
+    Staged,
+
+    /// The last commit that changed the file was made before this time. Time is in seconds
+    /// and relative to the unix epoch. Files that were never committed don't match
+    ///
+    /// Only the first parent of merge commits is followed
+    LastCommitBefore(i64),
+}
+
+impl TryFrom<&str> for GitState {
+    type Error = SearchCriteriaParsingError;
+
+    /// Parses `untracked`, `ignored`, `modified` or `staged`. `LastCommitBefore`
+    /// can't be parsed from a string since it needs a time
+    fn try_from(state: &str) -> Result<Self, Self::Error> {
+        Ok(match state {
+            "untracked" => GitState::Untracked,
+            "ignored" => GitState::Ignored,
+            "modified" => GitState::Modified,
+            "staged" => GitState::Staged,
+            _ => return Err(SearchCriteriaParsingError::UnknownValue),
+        })
+    }
+}
+
+/// A `GitState` the file has to be in together with the repositories that were
+/// already looked at
+#[derive(Debug, Clone)]
+pub struct Git {
+    state: GitState,
+    repositories: GitRepositories,
+}
+
+impl Git {
+    /// Creates a new `Git` criteria that looks for files in `state`
+    #[must_use]
+    pub fn new(state: GitState) -> Self {
+        Git {
+            state,
+            repositories: GitRepositories::new(),
+        }
+    }
+
+    /// Creates a new `Git` criteria that shares the repositories with other criterias
+    ///
+    /// Use this if you have multiple `Git` criterias so every repository is only read once
+    #[must_use]
+    pub fn with_repositories(state: GitState, repositories: GitRepositories) -> Self {
+        Git {
+            state,
+            repositories,
+        }
+    }
+
+    /// Returns the state the file has to be in
+    #[must_use]
+    pub fn state(&self) -> GitState {
+        self.state
+    }
+
+    /// Checks if the file `path` points to is in the state of this criteria
+    pub(crate) fn matches(&self, path: &Path, is_dir: bool) -> Result<bool, ConditionEvalError> {
+        self.repositories.repository_for(path)?
+            .map_or(Ok(false), |(repository, relative_path)| self.state_matches(&repository, &relative_path, is_dir))
+    }
+
+    fn state_matches(&self, repository: &GitRepository, relative_path: &Path, is_dir: bool) -> Result<bool, ConditionEvalError> {
+        if let GitState::Ignored = self.state {
+            return Ok(repository.is_ignored(relative_path));
+        }
+
+        if is_dir {
+            return Ok(false);
+        }
+
+        if let GitState::LastCommitBefore(before_this_time) = self.state {
+            return Ok(repository.last_commit_time(relative_path)?.is_some_and(|commit_time| commit_time < before_this_time));
+        }
+
+        let status = repository.statuses.get(relative_path).copied().unwrap_or_else(Status::empty);
+
+        Ok(match self.state {
+            GitState::Untracked => status.contains(Status::WT_NEW),
+            GitState::Modified => status.intersects(Status::WT_MODIFIED | Status::WT_TYPECHANGE | Status::WT_RENAMED),
+            GitState::Staged => status.intersects(Status::INDEX_NEW | Status::INDEX_MODIFIED | Status::INDEX_DELETED | Status::INDEX_RENAMED | Status::INDEX_TYPECHANGE),
+            GitState::Ignored | GitState::LastCommitBefore(_) => unreachable!("Handled above"),
+        })
+    }
+}
+
+/// The git repositories that were found while searching
+///
+/// The repository a folder belongs to is only looked up once. Every repository is only
+/// read once too, no matter from how many paths to search in it is reached.
+///
+/// Cloning this doesn't copy the repositories. The clones share them
+#[derive(Debug, Clone, Default)]
+pub struct GitRepositories {
+    inner: Arc<Mutex<GitRepositoriesInner>>,
+}
+
+#[derive(Debug, Default)]
+struct GitRepositoriesInner {
+    repositories: Vec<Arc<GitRepository>>,
+
+    /// Every folder that was already looked at together with the repository it is in and its path relative
+    /// to the working directory of the repository. `None` if the folder isn't in a repository
+    folders: HashMap<PathBuf, Option<(Arc<GitRepository>, PathBuf)>>,
+}
+
+impl GitRepositories {
+    #[must_use]
+    pub fn new() -> Self {
+        GitRepositories::default()
+    }
+
+    /// Returns the repository `path` is in and the path relative to the working directory of it
+    fn repository_for(&self, path: &Path) -> Result<Option<(Arc<GitRepository>, PathBuf)>, ConditionEvalError> {
+        let canonical_path;
+
+        let (folder, name) = match (path.parent(), path.file_name()) {
+            (Some(parent), Some(name)) if parent.as_os_str().is_empty() => (Path::new("."), name),
+            (Some(parent), Some(name)) => (parent, name),
+            _ => {
+                // Paths like `.` or `..` don't have a filename
+                canonical_path = path.canonicalize()
+                    .with_context(|| format!("Failed to canonicalize {:?}", path.display()))?;
+
+                match (canonical_path.parent(), canonical_path.file_name()) {
+                    (Some(parent), Some(name)) => (parent, name),
+                    _ => return Ok(None),
+                }
+            }
+        };
+
+        let mut inner = self.inner.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+
+        if let Some(found) = inner.folders.get(folder) {
+            return Ok(found.as_ref().map(|(repository, relative_folder)| (Arc::clone(repository), relative_folder.join(name))));
+        }
+
+        let found = inner.find_repository(folder)?;
+        inner.folders.insert(folder.to_path_buf(), found.clone());
+
+        Ok(found.map(|(repository, relative_folder)| (repository, relative_folder.join(name))))
+    }
+}
+
+impl GitRepositoriesInner {
+    fn find_repository(&mut self, folder: &Path) -> Result<Option<(Arc<GitRepository>, PathBuf)>, ConditionEvalError> {
+        let canonical_folder = folder.canonicalize()
+            .with_context(|| format!("Failed to canonicalize {:?}", folder.display()))?;
+
+        // The repository that contains the folder could already be known from another folder
+        let known_repository = self.repositories.iter()
+            .filter(|repository| canonical_folder.starts_with(&repository.workdir))
+            .max_by_key(|repository| repository.workdir.as_os_str().len());
+
+        if let Some(repository) = known_repository {
+            let relative_folder = canonical_folder.strip_prefix(&repository.workdir).unwrap_or_else(|_| Path::new("")).to_path_buf();
+            return Ok(Some((Arc::clone(repository), relative_folder)));
+        }
+
+        let repository = match Repository::discover(&canonical_folder) {
+            Ok(repository) => repository,
+            Err(e) if e.code() == ErrorCode::NotFound => {
+                debug!("{:?} isn't in a git repository", folder.display());
+                return Ok(None);
+            }
+            Err(e) => return Err(FilyError::new_with_context(e, || format!("Failed to open the git repository {:?} is in", folder.display())).into()),
+        };
+
+        // Bare repositories don't have any files to find
+        let workdir = match repository.workdir() {
+            Some(workdir) => workdir.canonicalize()
+                .with_context(|| format!("Failed to canonicalize {:?}", workdir.display()))?,
+            None => return Ok(None),
+        };
+
+        let relative_folder = match canonical_folder.strip_prefix(&workdir) {
+            Ok(relative_folder) => relative_folder.to_path_buf(),
+            // The folder is inside of the .git folder of the repository
+            Err(_) => return Ok(None),
+        };
+
+        let repository = Arc::new(GitRepository::read(&repository, workdir)?);
+        self.repositories.push(Arc::clone(&repository));
+
+        Ok(Some((repository, relative_folder)))
+    }
+}
+
+/// The state of every file in a repository at the time it was read
+#[derive(Debug)]
+struct GitRepository {
+    workdir: PathBuf,
+    git_dir: PathBuf,
+
+    /// Every file that isn't unmodified. Paths are relative to `workdir`. Ignored
+    /// folders are in here but not the files in them
+    statuses: HashMap<PathBuf, Status>,
+
+    /// The time of the last commit that changed a file. This is only read once it's needed
+    /// since it has to go through the history of the repository
+    last_commit_times: Mutex<Option<HashMap<PathBuf, i64>>>,
+}
+
+impl GitRepository {
+    fn read(repository: &Repository, workdir: PathBuf) -> Result<Self, FilyError<git2::Error>> {
+        trace!("GitRepository::read workdir: {:?}", workdir.display());
+
+        let mut status_options = StatusOptions::new();
+        status_options.include_untracked(true)
+            .recurse_untracked_dirs(true)
+            .include_ignored(true)
+            .recurse_ignored_dirs(false);
+
+        let statuses = repository.statuses(Some(&mut status_options))
+            .with_context(|| format!("Failed to get the status of the git repository {:?}", workdir.display()))?
+            .iter()
+            .filter_map(|status_entry| status_entry.path().map(|path| (PathBuf::from(path), status_entry.status())))
+            .collect();
+
+        Ok(GitRepository {
+            git_dir: repository.path().to_path_buf(),
+            workdir,
+            statuses,
+            last_commit_times: Mutex::new(None),
+        })
+    }
+
+    fn is_ignored(&self, relative_path: &Path) -> bool {
+        relative_path.ancestors()
+            .filter(|ancestor| !ancestor.as_os_str().is_empty())
+            .any(|ancestor| self.statuses.get(ancestor).is_some_and(|status| status.contains(Status::IGNORED)))
+    }
+
+    fn last_commit_time(&self, relative_path: &Path) -> Result<Option<i64>, FilyError<git2::Error>> {
+        let mut last_commit_times = self.last_commit_times.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+
+        if last_commit_times.is_none() {
+            *last_commit_times = Some(self.read_last_commit_times()?);
+        }
+
+        Ok(last_commit_times.as_ref().and_then(|last_commit_times| last_commit_times.get(relative_path).copied()))
+    }
+
+    /// Goes through the history from the newest commit on until the last commit for every file in the index is found
+    fn read_last_commit_times(&self) -> Result<HashMap<PathBuf, i64>, FilyError<git2::Error>> {
+        trace!("GitRepository::read_last_commit_times workdir: {:?}", self.workdir.display());
+
+        let repository = Repository::open(&self.git_dir)
+            .with_context(|| format!("Failed to open the git repository {:?}", self.workdir.display()))?;
+
+        let mut remaining: HashSet<PathBuf> = repository.index()
+            .with_context(|| format!("Failed to read the index of the git repository {:?}", self.workdir.display()))?
+            .iter()
+            .map(|index_entry| PathBuf::from(String::from_utf8_lossy(&index_entry.path).into_owned()))
+            .collect();
+
+        let mut last_commit_times = HashMap::new();
+
+        let mut revwalk = repository.revwalk()
+            .with_context(|| format!("Failed to walk the history of the git repository {:?}", self.workdir.display()))?;
+
+        match revwalk.push_head() {
+            Ok(()) => (),
+            // There are no commits yet
+            Err(e) if e.code() == ErrorCode::UnbornBranch || e.code() == ErrorCode::NotFound => return Ok(last_commit_times),
+            Err(e) => return Err(FilyError::new_with_context(e, || format!("Failed to get HEAD of the git repository {:?}", self.workdir.display()))),
+        }
+
+        revwalk.simplify_first_parent()
+            .and_then(|()| revwalk.set_sorting(Sort::TIME))
+            .with_context(|| format!("Failed to walk the history of the git repository {:?}", self.workdir.display()))?;
+
+        for commit_id in revwalk {
+            if remaining.is_empty() {
+                break;
+            }
+
+            let commit = commit_id
+                .and_then(|commit_id| repository.find_commit(commit_id))
+                .with_context(|| format!("Failed to read a commit of the git repository {:?}", self.workdir.display()))?;
+
+            let tree = commit.tree()
+                .with_context(|| format!("Failed to read the tree of commit {} in {:?}", commit.id(), self.workdir.display()))?;
+
+            let parent_tree = match commit.parent(0) {
+                Ok(parent) => Some(parent.tree().with_context(|| format!("Failed to read the tree of commit {} in {:?}", parent.id(), self.workdir.display()))?),
+                Err(_) => None,
+            };
+
+            let diff = repository.diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), None)
+                .with_context(|| format!("Failed to diff commit {} in {:?}", commit.id(), self.workdir.display()))?;
+
+            for delta in diff.deltas() {
+                if let Some(path) = delta.new_file().path() {
+                    if remaining.remove(path) {
+                        last_commit_times.insert(path.to_path_buf(), commit.time().seconds());
+                    }
+                }
+            }
+        }
+
+        Ok(last_commit_times)
+    }
+}
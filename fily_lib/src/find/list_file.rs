@@ -0,0 +1,113 @@
+use std::{path::{Path, PathBuf}, fs, io, fmt, error::Error};
+use regex::Regex;
+use crate::fily_err::{Context, FilyError};
+#[allow(unused_imports)]
+use log::{trace, debug, info, warn, error};
+
+#[derive(Debug)]
+pub enum ListFileError {
+    /// Failed to read the file
+    IOError(io::Error),
+
+    /// A line isn't a valid regex
+    InvalidPattern(regex::Error),
+
+    /// A line isn't valid UTF-8. Only happens for patterns, paths can contain anything
+    UTF8ConversionFailed,
+}
+
+impl Error for ListFileError {}
+
+impl fmt::Display for ListFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+/// How the entries in a file with a list of paths or patterns are separated
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum ListSeparator {
+    /// One entry per line. Lines that are empty or only contain whitespace and lines that start with a `#` are skipped and
+    /// a `\r` at the end of a line is removed so files written on Windows work too
+    #[default]
+    Newline,
+
+    /// Entries are separated by null bytes, like the output of `find -print0`. Nothing is skipped
+    /// except for empty entries, so this works for paths that contain newlines or start with a `#`
+    Nul,
+}
+
+/// Reads a list of paths from the file `path` points to
+///
+/// # Errors
+///
+/// Fails if the file can't be read
+pub fn read_paths_from_file(path: impl AsRef<Path>, separator: ListSeparator) -> Result<Vec<PathBuf>, FilyError<ListFileError>> {
+    let path = path.as_ref();
+
+    trace!("read_paths_from_file path: {:?} separator: {:?}", path.display(), separator);
+
+    Ok(read_entries(path, separator)?
+        .into_iter()
+        .map(|(_, entry)| bytes_to_path(entry))
+        .collect())
+}
+
+/// Reads a list of regexes from the file `path` points to
+///
+/// # Errors
+///
+/// Fails if the file can't be read or one of the entries isn't a valid regex
+pub fn read_patterns_from_file(path: impl AsRef<Path>, separator: ListSeparator) -> Result<Vec<Regex>, FilyError<ListFileError>> {
+    let path = path.as_ref();
+
+    trace!("read_patterns_from_file path: {:?} separator: {:?}", path.display(), separator);
+
+    read_entries(path, separator)?
+        .into_iter()
+        .map(|(entry_number, entry)| {
+            let pattern = std::str::from_utf8(&entry)
+                .map_err(|_| FilyError::new_with_context(ListFileError::UTF8ConversionFailed, || format!("Entry {} in {:?} isn't valid UTF-8", entry_number, path.display())))?;
+
+            Regex::new(pattern)
+                .map_err(|e| FilyError::new_with_context(ListFileError::InvalidPattern(e), || format!("Entry {} in {:?} isn't a valid regex", entry_number, path.display())))
+        })
+        .collect()
+}
+
+/// Returns the entries that aren't skipped together with their number, starting at 1. For
+/// `ListSeparator::Newline` the number is the line number
+fn read_entries(path: &Path, separator: ListSeparator) -> Result<Vec<(usize, Vec<u8>)>, FilyError<ListFileError>> {
+    let contents = fs::read(path)
+        .map_err(ListFileError::IOError)
+        .with_context(|| format!("Failed to read {:?}", path.display()))?;
+
+    let entries = match separator {
+        ListSeparator::Newline => contents.split(|&byte| byte == b'\n')
+            .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+            .enumerate()
+            .filter(|(_, line)| !line.iter().all(u8::is_ascii_whitespace) && !line.starts_with(b"#"))
+            .map(|(i, line)| (i + 1, line.to_vec()))
+            .collect(),
+        ListSeparator::Nul => contents.split(|&byte| byte == 0)
+            .enumerate()
+            .filter(|(_, entry)| !entry.is_empty())
+            .map(|(i, entry)| (i + 1, entry.to_vec()))
+            .collect(),
+    };
+
+    Ok(entries)
+}
+
+#[cfg(unix)]
+fn bytes_to_path(bytes: Vec<u8>) -> PathBuf {
+    use std::{ffi::OsString, os::unix::ffi::OsStringExt};
+
+    PathBuf::from(OsString::from_vec(bytes))
+}
+
+/// Paths on other platforms aren't just bytes so they have to be valid UTF-8
+#[cfg(not(unix))]
+fn bytes_to_path(bytes: Vec<u8>) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(&bytes).into_owned())
+}
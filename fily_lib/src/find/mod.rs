@@ -1,5 +1,6 @@
-use std::path::{Path, PathBuf};
-use walkdir::WalkDir;
+use std::{path::{Path, PathBuf}, collections::HashSet, io};
+use walkdir::{WalkDir, DirEntry};
+use crate::fily_err::{Context, FilyError};
 #[allow(unused_imports)]
 use log::{trace, debug, info, warn, error};
 
@@ -7,80 +8,134 @@ mod condition;
 pub use condition::{Condition, ConditionEvalError};
 
 mod condition_try_from;
+pub use condition_try_from::ConditionParsingError;
+
+mod search_entry;
+pub use search_entry::SearchEntry;
 
 mod search_criteria;
 pub use search_criteria::*;
 
+mod file_contents;
+pub use file_contents::TEXT_DETECTION_BYTES;
+
 mod find_options;
 pub use find_options::*;
 
+mod list_file;
+pub use list_file::{ListSeparator, ListFileError, read_paths_from_file, read_patterns_from_file};
+
+#[cfg(feature = "presets")]
+mod preset;
+#[cfg(feature = "presets")]
+pub use preset::{Config, Preset, PresetError};
+
+#[cfg(feature = "xattr")]
+mod xattr;
+#[cfg(feature = "xattr")]
+pub use xattr::{get_xattr, list_xattrs};
+
+#[cfg(feature = "git")]
+mod git;
+#[cfg(feature = "git")]
+pub use git::{Git, GitState, GitRepositories};
+
+#[cfg(feature = "archives")]
+mod archives;
+#[cfg(feature = "archives")]
+pub use archives::{ArchiveMember, ARCHIVE_PATH_SEPARATOR, MAX_NESTED_ARCHIVE_SIZE};
+
+#[cfg(all(feature = "watch", target_os = "linux"))]
+mod watch;
+#[cfg(all(feature = "watch", target_os = "linux"))]
+pub use watch::{FindWatcher, WatchError};
+
 /// Finds files or directories that fit all of the criteria
 ///
 /// This function returns a tuple of two `Vec`s. The first one contains paths to the files that
 /// matched all of the conditions. The second one contains the errors that occured during the
 /// evaluation of the conditions on a file. These files could theoretically also match the conditions
 /// but we don't know if they do since an error occured.
+///
+/// Errors that occur while walking the directory tree (i.e. missing permissions or files that vanished)
+/// are also in the second `Vec` as `ConditionEvalError::TraversalErr`. Symlink loops are reported
+/// as `ConditionEvalError::SymlinkLoop`. Files and folders with such an error are skipped.
+///
+/// Paths in `paths_to_search_in` that overlap and files that are hard links to the same file can be
+/// reported only once. Look at `FindOptions::merge_overlapping_paths` and `FindOptions::unique_inodes` for that.
+///
+/// `FindOptions::additional_paths_to_search_in` are searched after `paths_to_search_in`.
+///
+/// If `FindOptions::search_in_archives` is set files inside of archives are returned after the
+/// other files found in the same path to search in
 /// 
 /// The returned `Vec`s can be empty if nothing was found or no error occured
 pub fn find<P: AsRef<Path>>(paths_to_search_in: &[P], find_options: &FindOptions) -> (Vec<PathBuf>, Vec<(PathBuf, ConditionEvalError)>) {
-    let paths_to_search_in: Vec<&Path> = paths_to_search_in.iter().map(AsRef::as_ref).collect();
+    let mut paths_to_search_in = find_options.all_paths_to_search_in(paths_to_search_in);
 
     trace!("find paths_to_search_in: {:?} find_options: {:?}", paths_to_search_in, find_options);
 
+    if find_options.merge_overlapping_paths {
+        paths_to_search_in = merge_overlapping_paths(paths_to_search_in, find_options);
+    }
+
     let mut results = Vec::new();
     let mut errors = Vec::new();
+    let mut seen_file_ids = HashSet::new();
 
     for path in paths_to_search_in {
+        #[allow(unused_mut)]
+        let mut archive_members = Vec::new();
+
         let mut matching_files: Vec<PathBuf> = WalkDir::new(path)
             .min_depth(find_options.min_depth_from_start)
             .max_depth(find_options.max_search_depth)
             .follow_links(find_options.follow_symlinks)
             .into_iter()
             .filter_map(|entry| {
-                if let Err(e) = entry {
-                    info!("Error accessing a file {}", e);
-                    return None;
+                let entry = match entry {
+                    Ok(entry) => entry,
+                    Err(e) => {
+                        info!("Error accessing a file {}", e);
+
+                        let error_path = e.path().unwrap_or(path).to_path_buf();
+                        errors.push((error_path, ConditionEvalError::from(e)));
+
+                        return None;
+                    }
+                };
+
+                let path = entry.path();
+
+                #[cfg(feature = "archives")]
+                if find_options.search_in_archives && entry.file_type().is_file() && archives::is_archive(path) {
+                    archives::search_archive(path, entry.depth(), find_options, &mut archive_members, &mut errors);
                 }
 
-                let entry = entry.unwrap();
+                if !entry_matches(&entry, find_options, &mut errors) {
+                    return None;
+                }
 
-                if let Some(ignore) = find_options.ignore {
-                    let file_type = entry.file_type();
-                    match ignore {
-                        Ignore::Files => if file_type.is_file() {
-                            return None;
-                        }
-                        Ignore::Folders => if file_type.is_dir() {
+                // Only the first hard link to a file that matches gets returned
+                if find_options.unique_inodes {
+                    match file_id(&entry) {
+                        Ok(Some(file_id)) => if !seen_file_ids.insert(file_id) {
                             return None;
                         }
-                    };
-                }
-
-                if find_options.ignore_hidden_files {
-                    if let Some(name) = entry.file_name().to_str() {
-                        if name.starts_with('.') {
+                        Ok(None) => (),
+                        Err(e) => {
+                            errors.push((path.to_path_buf(), e.into()));
                             return None;
                         }
                     }
-                    // Not sure if this is the right way to go here. Maybe we should actually filter out the file since it errored?
                 }
 
-                let path = entry.path();
-
-                // Checks if all Conditions match the file
-                // If any do not match, the file gets filtered out
-                if find_options.options.iter().all(|option| option.evaluate(&entry).unwrap_or_else(|err| {
-                    errors.push((path.to_path_buf(), err));
-                    false
-                })) {
-                    Some(path.to_path_buf())
-                } else {
-                    None
-                }
+                Some(path.to_path_buf())
             })
             .collect();
 
         results.append(&mut matching_files);
+        results.append(&mut archive_members);
 
         if results.len() >= find_options.max_num_results {
             // Shorten the vec in case there are too many items so we don't return more
@@ -97,3 +152,114 @@ pub fn find<P: AsRef<Path>>(paths_to_search_in: &[P], find_options: &FindOptions
 
     (results, errors)
 }
+
+/// Checks if `entry` isn't ignored because of `find_options` and matches all of its conditions
+///
+/// Errors that occur while evaluating the conditions get pushed to `errors`. The entry
+/// doesn't match in that case
+pub(crate) fn entry_matches(entry: &DirEntry, find_options: &FindOptions, errors: &mut Vec<(PathBuf, ConditionEvalError)>) -> bool {
+    if let Some(ignore) = find_options.ignore {
+        let file_type = entry.file_type();
+        match ignore {
+            Ignore::Files => if file_type.is_file() {
+                return false;
+            }
+            Ignore::Folders => if file_type.is_dir() {
+                return false;
+            }
+        };
+    }
+
+    if find_options.ignore_hidden_files {
+        if let Some(name) = entry.file_name().to_str() {
+            if name.starts_with('.') {
+                return false;
+            }
+        }
+        // Not sure if this is the right way to go here. Maybe we should actually filter out the file since it errored?
+    }
+
+    // Checks if all Conditions match the file
+    // If any do not match, the file gets filtered out
+    find_options.options.iter().all(|option| option.evaluate(entry).unwrap_or_else(|err| {
+        errors.push((entry.path().to_path_buf(), err));
+        false
+    }))
+}
+
+/// Removes paths that point to the same folder as another path or to a folder inside of another path
+///
+/// The paths are canonicalized to compare them but the paths that are kept are returned
+/// unchanged. Paths that can't be canonicalized are always kept.
+///
+/// A path inside of another one is only removed if the search depth isn't limited. Otherwise
+/// searching it could reach files that are too deep to be found from the outer path
+fn merge_overlapping_paths<'a>(paths_to_search_in: Vec<&'a Path>, find_options: &FindOptions) -> Vec<&'a Path> {
+    let depth_is_limited = find_options.max_search_depth != usize::MAX;
+    let mut kept_paths: Vec<(&Path, Option<PathBuf>)> = Vec::new();
+
+    for path in paths_to_search_in {
+        let canonical_path = match path.canonicalize() {
+            Ok(canonical_path) => canonical_path,
+            Err(e) => {
+                info!("Failed to canonicalize {:?} {}", path.display(), e);
+                kept_paths.push((path, None));
+                continue;
+            }
+        };
+
+        let is_covered_by = |other: &Path| if depth_is_limited {
+            canonical_path == other
+        } else {
+            canonical_path.starts_with(other)
+        };
+
+        if kept_paths.iter().any(|(_, other)| other.as_deref().is_some_and(is_covered_by)) {
+            debug!("Skipping {:?} since it overlaps with another path", path.display());
+            continue;
+        }
+
+        // A path that was added earlier could be inside of this one
+        if !depth_is_limited {
+            kept_paths.retain(|(other_path, other)| {
+                let is_inside = other.as_ref().is_some_and(|other| other.starts_with(&canonical_path));
+
+                if is_inside {
+                    debug!("Skipping {:?} since it overlaps with another path", other_path.display());
+                }
+
+                !is_inside
+            });
+        }
+
+        kept_paths.push((path, Some(canonical_path)));
+    }
+
+    kept_paths.into_iter().map(|(path, _)| path).collect()
+}
+
+/// Returns the device and inode number of the file `dir_entry` points to
+///
+/// Returns `None` for folders and files that only have a single link since they
+/// can't be found twice through hard links
+#[cfg(unix)]
+fn file_id(dir_entry: &DirEntry) -> Result<Option<(u64, u64)>, FilyError<io::Error>> {
+    use std::os::unix::fs::MetadataExt;
+
+    let metadata = dir_entry.metadata()
+        .map_err(io::Error::from)
+        .with_context(|| format!("Failed to get metadata of {:?}", dir_entry.path().display()))?;
+
+    if metadata.is_dir() || metadata.nlink() <= 1 {
+        return Ok(None);
+    }
+
+    Ok(Some((metadata.dev(), metadata.ino())))
+}
+
+/// Always returns `None` since getting the file index is only supported on Unix platforms
+#[cfg(not(unix))]
+#[allow(clippy::unnecessary_wraps)]
+fn file_id(_dir_entry: &DirEntry) -> Result<Option<(u64, u64)>, FilyError<io::Error>> {
+    Ok(None)
+}
@@ -0,0 +1,225 @@
+use std::{path::{Path, PathBuf}, collections::HashMap, convert::TryFrom, fs, io, fmt, error::Error};
+use serde::Deserialize;
+use super::{FindOptions, FindOptionsBuilder, Condition, ConditionParsingError, Ignore};
+use crate::fily_err::{Context, FilyError};
+#[allow(unused_imports)]
+use log::{trace, debug, info, warn, error};
+
+#[derive(Debug)]
+pub enum PresetError {
+    /// The folder the config file is in by default is unknown on this platform
+    NoConfigDir,
+
+    /// Failed to read the config file
+    IOError(io::Error),
+
+    /// The config file isn't valid TOML or has a value with the wrong type
+    MalformedConfig(toml::de::Error),
+
+    /// There is no preset with this name in the config file
+    UnknownPreset,
+
+    /// The conditions of a preset couldn't be parsed
+    MalformedConditions(ConditionParsingError),
+}
+
+impl Error for PresetError {}
+
+impl fmt::Display for PresetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+/// The config file of fily
+///
+/// It is a TOML file with a table for every preset:
+///
+/// ```toml
+/// [presets.stale-builds]
+/// paths_to_search_in = ["~/projects"]
+/// conditions = '"filename_exact"=target & "modified_before"=1609459200'
+/// max_search_depth = 3
+/// ignore = "files"
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub presets: HashMap<String, Preset>,
+}
+
+impl Config {
+    /// Returns where the config file is by default
+    ///
+    /// That is `$XDG_CONFIG_HOME/fily/config.toml` or `~/.config/fily/config.toml` on Linux,
+    /// `~/Library/Application Support/fily/config.toml` on macOS and `%APPDATA%\fily\config.toml` on Windows
+    #[must_use]
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|config_dir| config_dir.join("fily").join("config.toml"))
+    }
+
+    /// Reads the config file `path` points to
+    ///
+    /// # Errors
+    ///
+    /// Fails if the file can't be read or isn't a valid config
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, FilyError<PresetError>> {
+        let path = path.as_ref();
+
+        trace!("Config::load path: {:?}", path.display());
+
+        let config = fs::read_to_string(path)
+            .map_err(PresetError::IOError)
+            .with_context(|| format!("Failed to read config file {:?}", path.display()))?;
+
+        toml::from_str(&config)
+            .map_err(PresetError::MalformedConfig)
+            .with_context(|| format!("Failed to parse config file {:?}", path.display()))
+    }
+
+    /// Reads the config file at the default location
+    ///
+    /// # Errors
+    ///
+    /// Fails if the location is unknown, the file can't be read or isn't a valid config
+    pub fn load_default() -> Result<Self, FilyError<PresetError>> {
+        let path = Config::default_path()
+            .ok_or_else(|| FilyError::new(PresetError::NoConfigDir, "Can't find the folder the config file is in"))?;
+
+        Config::load(path)
+    }
+
+    /// Returns the preset with the name `name`
+    ///
+    /// # Errors
+    ///
+    /// Fails if there is no preset with that name
+    pub fn preset(&self, name: &str) -> Result<&Preset, FilyError<PresetError>> {
+        self.presets.get(name)
+            .ok_or_else(|| FilyError::new_with_context(PresetError::UnknownPreset, || format!("There is no preset called {:?}", name)))
+    }
+}
+
+/// A saved search
+///
+/// Everything that isn't set keeps the default value of `FindOptions`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Preset {
+    /// Where the search starts. A `~` at the start is replaced with the home folder
+    pub paths_to_search_in: Vec<PathBuf>,
+
+    /// A condition in the syntax `Condition::try_from` expects
+    pub conditions: Option<String>,
+
+    pub max_num_results: Option<usize>,
+    pub max_search_depth: Option<usize>,
+    pub min_depth_from_start: Option<usize>,
+    pub ignore: Option<Ignore>,
+    pub ignore_hidden_files: Option<bool>,
+    pub follow_symlinks: Option<bool>,
+    pub merge_overlapping_paths: Option<bool>,
+    pub unique_inodes: Option<bool>,
+    pub search_in_archives: Option<bool>,
+    pub max_archive_depth: Option<usize>,
+
+    /// What the paths that were found are separated with when they are printed. Only used by the CLI
+    pub output_separator: Option<String>,
+
+    /// If errors should be printed. Only used by the CLI
+    pub print_errors: Option<bool>,
+}
+
+impl Preset {
+    /// Reads the preset with the name `name` from the config file at the default location
+    ///
+    /// # Errors
+    ///
+    /// Fails if the config file can't be read or there is no preset with that name
+    pub fn load(name: &str) -> Result<Self, FilyError<PresetError>> {
+        Config::load_default()?
+            .presets
+            .remove(name)
+            .ok_or_else(|| FilyError::new_with_context(PresetError::UnknownPreset, || format!("There is no preset called {:?}", name)))
+    }
+
+    /// Builds the `FindOptions` the preset describes. The paths to search in are put into
+    /// `FindOptions::additional_paths_to_search_in`
+    ///
+    /// # Errors
+    ///
+    /// Fails if the conditions can't be parsed
+    pub fn to_find_options(&self) -> Result<FindOptions, FilyError<PresetError>> {
+        let mut find_options_builder = FindOptionsBuilder::new();
+
+        if let Some(conditions) = &self.conditions {
+            let condition = Condition::try_from(conditions.as_str())
+                .map_err(PresetError::MalformedConditions)
+                .with_context(|| format!("Failed to parse the conditions {:?}", conditions))?;
+
+            find_options_builder.add_condition(condition);
+        }
+
+        find_options_builder.add_paths_to_search_in(self.paths_to_search_in.iter().map(|path| expand_home(path)).collect())
+            .set_ignored_files(self.ignore);
+
+        if let Some(max_num_results) = self.max_num_results {
+            find_options_builder.set_max_num_results(max_num_results);
+        }
+
+        if let Some(max_search_depth) = self.max_search_depth {
+            find_options_builder.set_max_search_depth(max_search_depth);
+        }
+
+        if let Some(min_depth_from_start) = self.min_depth_from_start {
+            find_options_builder.set_min_depth_from_start(min_depth_from_start);
+        }
+
+        if let Some(ignore_hidden_files) = self.ignore_hidden_files {
+            find_options_builder.set_ignore_hidden_files(ignore_hidden_files);
+        }
+
+        if let Some(follow_symlinks) = self.follow_symlinks {
+            find_options_builder.set_follow_symlinks(follow_symlinks);
+        }
+
+        if let Some(merge_overlapping_paths) = self.merge_overlapping_paths {
+            find_options_builder.set_merge_overlapping_paths(merge_overlapping_paths);
+        }
+
+        if let Some(unique_inodes) = self.unique_inodes {
+            find_options_builder.set_unique_inodes(unique_inodes);
+        }
+
+        if let Some(search_in_archives) = self.search_in_archives {
+            find_options_builder.set_search_in_archives(search_in_archives);
+        }
+
+        if let Some(max_archive_depth) = self.max_archive_depth {
+            find_options_builder.set_max_archive_depth(max_archive_depth);
+        }
+
+        Ok(find_options_builder.build())
+    }
+}
+
+impl FindOptions {
+    /// Builds the `FindOptions` of the preset with the name `name` in the config file at the default location
+    ///
+    /// Look at `Config` for how the config file looks like
+    ///
+    /// # Errors
+    ///
+    /// Fails if the config file can't be read, there is no preset with that name or its conditions can't be parsed
+    pub fn from_preset(name: &str) -> Result<Self, FilyError<PresetError>> {
+        Preset::load(name)?.to_find_options()
+    }
+}
+
+/// Replaces a `~` at the start of `path` with the home folder
+fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), dirs::home_dir()) {
+        (Ok(rest), Some(home_dir)) => home_dir.join(rest),
+        _ => path.to_path_buf(),
+    }
+}
@@ -1,10 +1,31 @@
-use std::{num::ParseIntError, convert::TryFrom, error::Error, fmt};
+use std::{num::{ParseIntError, ParseFloatError}, convert::TryFrom, path::{Component, Path, PathBuf}, fs::metadata, io, error::Error, fmt};
+use filetime::FileTime;
+use crate::fily_err::{Context, FilyError};
+#[cfg(feature = "git")]
+use super::{Git, GitState};
+#[cfg(feature = "audio_tags")]
+use std::time::Duration;
+#[cfg(feature = "exif_tags")]
+use std::ops::Range;
+#[cfg(feature = "exif_tags")]
+use chrono::NaiveDateTime;
+#[cfg(feature = "exif_tags")]
+use crate::exif_tags::{parse_date_time, parse_date_time_range};
 
 /// Used to specify a criteria a file has to match
 ///
 /// There are criterias for the filename, filesize, path,
 /// filename but with a regex that has to match, the last time it was modified,
 /// the last time it was accessed and the time it was created
+///
+/// There are also criterias that look at other files in relation to the file, like
+/// what a directory contains, which siblings a file has or if it is newer
+/// than a reference file
+///
+/// Criterias like `TextOrBinary`, `LineCount` or `Entropy` read the contents of the file
+///
+/// With the `audio_tags` feature the tags of audio files can be checked and with the `exif_tags` feature the EXIF tags of photos. With the `xattr` feature the extended attributes of a file can be checked and with
+/// the `git` feature files can also be checked for their state in the git repository they are in
 #[derive(Debug, Clone)]
 pub enum SearchCriteria {
     Filename(Filename),
@@ -13,7 +34,43 @@ pub enum SearchCriteria {
     FilenameRegex(regex::Regex),
     Modified(Modified),
     Accessed(Accessed),
-    Created(Created)
+    Created(Created),
+    DirectoryContains(DirectoryContains),
+    Sibling(Sibling),
+    Reference(Reference),
+    TextOrBinary(TextOrBinary),
+    LineCount(LineCount),
+    Encoding(TextEncoding),
+    LineEnding(LineEnding),
+    NameLint(NameLint),
+    Depth(Depth),
+    Entropy(Entropy),
+    #[cfg(feature = "audio_tags")]
+    AudioTag(AudioTag),
+    #[cfg(feature = "exif_tags")]
+    ExifTag(ExifTag),
+    #[cfg(feature = "xattr")]
+    Xattr(Xattr),
+    #[cfg(feature = "git")]
+    Git(Git),
+}
+
+impl SearchCriteria {
+    /// Returns `true` if the criteria needs the file on disk and can't be checked with only the
+    /// name, path, size and timestamps of a file. Files inside of archives can't be checked against those
+    #[must_use]
+    pub fn needs_filesystem(&self) -> bool {
+        !matches!(self, SearchCriteria::Filename(_)
+            | SearchCriteria::Filesize(_)
+            | SearchCriteria::FilePath(_)
+            | SearchCriteria::FilenameRegex(_)
+            | SearchCriteria::Modified(_)
+            | SearchCriteria::Accessed(_)
+            | SearchCriteria::Created(_)
+            | SearchCriteria::Reference(_)
+            | SearchCriteria::NameLint(_)
+            | SearchCriteria::Depth(_))
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -30,8 +87,20 @@ pub enum SearchCriteriaParsingError {
     /// Error parsing the value to a number
     MalformedNumber,
 
+    /// Error parsing the value to a date like `2021-04-05` or `2021-04-05T12:00`
+    MalformedDate,
+
     /// Error parsing the regex
     MalformedRegex(regex::Error),
+
+    /// Failed to get the metadata of a reference file
+    ReferenceFileError(String),
+
+    /// The value isn't one of the values the criteria knows, i.e. `git_status` or `encoding`
+    UnknownValue,
+
+    /// The value has to be the name of a single file, not a path, i.e. for `directory_contains`
+    NotAFileName,
 }
 
 impl Error for SearchCriteriaParsingError {}
@@ -48,6 +117,19 @@ impl From<ParseIntError> for SearchCriteriaParsingError {
     }
 }
 
+#[cfg(feature = "exif_tags")]
+impl From<chrono::ParseError> for SearchCriteriaParsingError {
+    fn from(_: chrono::ParseError) -> Self {
+        Self::MalformedDate
+    }
+}
+
+impl From<ParseFloatError> for SearchCriteriaParsingError {
+    fn from(_: ParseFloatError) -> Self {
+        Self::MalformedNumber
+    }
+}
+
 impl From<regex::Error> for SearchCriteriaParsingError {
     fn from(error: regex::Error) -> Self {
         Self::MalformedRegex(error)
@@ -78,6 +160,40 @@ impl TryFrom<&str> for SearchCriteria {
     /// * `created_at`
     /// * `created_before`
     /// * `created_after`
+    /// * `directory_contains`
+    /// * `directory_contains_regex`
+    /// * `sibling_exact`
+    /// * `sibling_extension`
+    /// * `newer_than`
+    /// * `older_than`
+    /// * `content`
+    /// * `linecount_exact`
+    /// * `linecount_over`
+    /// * `linecount_under`
+    /// * `encoding`
+    /// * `line_ending`
+    /// * `name_longer_than`
+    /// * `path_longer_than`
+    /// * `name_lint`
+    /// * `depth_exact`
+    /// * `depth_over`
+    /// * `depth_under`
+    /// * `entropy_over`
+    /// * `entropy_under`
+    /// * `artist_exact`, `album_exact`, `title_exact` and `genre_exact` (only with the `audio_tags` feature)
+    /// * `artist_contains`, `album_contains`, `title_contains` and `genre_contains` (only with the `audio_tags` feature)
+    /// * `year_at`, `year_before` and `year_after` (only with the `audio_tags` feature)
+    /// * `duration_over` and `duration_under` (only with the `audio_tags` feature)
+    /// * `taken_at`, `taken_before` and `taken_after` (only with the `exif_tags` feature)
+    /// * `camera_make_exact`, `camera_make_contains`, `camera_model_exact` and `camera_model_contains` (only with the `exif_tags` feature)
+    /// * `has_gps` (only with the `exif_tags` feature)
+    /// * `orientation` (only with the `exif_tags` feature)
+    /// * `has_any_xattr` (only with the `xattr` feature)
+    /// * `has_xattr` (only with the `xattr` feature)
+    /// * `xattr_equals` (only with the `xattr` feature)
+    /// * `xattr_regex` (only with the `xattr` feature)
+    /// * `git_status` (only with the `git` feature)
+    /// * `git_last_commit_before` (only with the `git` feature)
     ///
     /// `filesize_*` and `filepath_*` expect a string
     ///
@@ -87,6 +203,34 @@ impl TryFrom<&str> for SearchCriteria {
     ///
     /// `modified_*`, `accessed_*` and `created_*` expect a number that is a timestamp relative to
     /// the unix epoch in seconds. This number can be negative
+    ///
+    /// `directory_contains` and `sibling_exact` expect a filename, `directory_contains_regex` expects a regex
+    /// and `sibling_extension` expects an extension without the `.`
+    ///
+    /// `newer_than` and `older_than` expect a path to a reference file. Its metadata is read while parsing
+    ///
+    /// `content` expects `text` or `binary` and `linecount_*` expects a number that is >= 0
+    ///
+    /// `encoding` expects `ascii`, `utf8`, `utf8_bom`, `utf16_le`, `utf16_be` or `unknown` and
+    /// `line_ending` expects `lf`, `crlf`, `mixed` or `none`
+    ///
+    /// `name_longer_than`, `path_longer_than` and `depth_*` expect a number that is >= 0. `name_lint` expects
+    /// `leading_trailing_whitespace`, `control_characters`, `non_ascii`, `windows_reserved_name` or `invalid_windows_characters`
+    ///
+    /// `entropy_*` expects a number between 0 and 8 which can have a fraction, i.e. `7.5`. The default `EntropySample` is used
+    ///
+    /// `year_*` expects a year and `duration_*` expects a number of seconds
+    ///
+    /// `taken_*` expects a date like `2021-04-05` or a date with a time like `2021-04-05T12:00`, `has_gps` expects `true` or `false` and
+    /// `orientation` expects a number from 1 to 8
+    ///
+    /// `has_any_xattr` ignores its value. `has_xattr` expects the name of an extended attribute,
+    /// `xattr_equals` expects `<name>=<value>` and `xattr_regex` expects `<name>=<regex>`
+    ///
+    /// `git_status` expects `untracked`, `ignored`, `modified` or `staged` and `git_last_commit_before`
+    /// expects a timestamp like `modified_*`. Every one of them reads the repositories on its own,
+    /// use `Git::with_repositories` to share them
+    #[allow(clippy::too_many_lines)]
     fn try_from(search_criteria_str: &str) -> Result<Self, Self::Error> {
         let parts: Vec<&str> = search_criteria_str.trim().splitn(2, '=').collect();
 
@@ -173,6 +317,193 @@ impl TryFrom<&str> for SearchCriteria {
 
                 SearchCriteria::Created(Created::After(timestamp))
             }
+            "directory_contains" => {
+                if !DirectoryContains::is_file_name(&value) {
+                    return Err(SearchCriteriaParsingError::NotAFileName);
+                }
+
+                SearchCriteria::DirectoryContains(DirectoryContains::Exact(value))
+            }
+            "directory_contains_regex" => {
+                let regex = regex::Regex::new(&value)?;
+
+                SearchCriteria::DirectoryContains(DirectoryContains::Regex(regex))
+            }
+            "sibling_exact" => SearchCriteria::Sibling(Sibling::Exact(value)),
+            "sibling_extension" => SearchCriteria::Sibling(Sibling::WithExtension(value)),
+            "newer_than" | "older_than" => {
+                let reference_file = ReferenceFile::new(&value)
+                    .map_err(|e| SearchCriteriaParsingError::ReferenceFileError(e.to_string()))?;
+
+                if criteria_name == "newer_than" {
+                    SearchCriteria::Reference(Reference::NewerThan(reference_file))
+                } else {
+                    SearchCriteria::Reference(Reference::OlderThan(reference_file))
+                }
+            }
+            "content" => SearchCriteria::TextOrBinary(match value.as_str() {
+                "text" => TextOrBinary::Text,
+                "binary" => TextOrBinary::Binary,
+                _ => return Err(SearchCriteriaParsingError::UnknownValue),
+            }),
+            "linecount_exact" => {
+                let line_count = value.parse()?;
+
+                SearchCriteria::LineCount(LineCount::Exact(line_count))
+            }
+            "linecount_over" => {
+                let line_count = value.parse()?;
+
+                SearchCriteria::LineCount(LineCount::Over(line_count))
+            }
+            "linecount_under" => {
+                let line_count = value.parse()?;
+
+                SearchCriteria::LineCount(LineCount::Under(line_count))
+            }
+            "encoding" => SearchCriteria::Encoding(match value.as_str() {
+                "ascii" => TextEncoding::Ascii,
+                "utf8" => TextEncoding::Utf8,
+                "utf8_bom" => TextEncoding::Utf8Bom,
+                "utf16_le" => TextEncoding::Utf16Le,
+                "utf16_be" => TextEncoding::Utf16Be,
+                "unknown" => TextEncoding::Unknown,
+                _ => return Err(SearchCriteriaParsingError::UnknownValue),
+            }),
+            "line_ending" => SearchCriteria::LineEnding(match value.as_str() {
+                "lf" => LineEnding::Lf,
+                "crlf" => LineEnding::Crlf,
+                "mixed" => LineEnding::Mixed,
+                "none" => LineEnding::None,
+                _ => return Err(SearchCriteriaParsingError::UnknownValue),
+            }),
+            "name_longer_than" => {
+                let length = value.parse()?;
+
+                SearchCriteria::NameLint(NameLint::NameLongerThan(length))
+            }
+            "path_longer_than" => {
+                let length = value.parse()?;
+
+                SearchCriteria::NameLint(NameLint::PathLongerThan(length))
+            }
+            "name_lint" => SearchCriteria::NameLint(match value.as_str() {
+                "leading_trailing_whitespace" => NameLint::LeadingOrTrailingWhitespace,
+                "control_characters" => NameLint::ControlCharacters,
+                "non_ascii" => NameLint::NonAscii,
+                "windows_reserved_name" => NameLint::WindowsReservedName,
+                "invalid_windows_characters" => NameLint::InvalidWindowsCharacters,
+                _ => return Err(SearchCriteriaParsingError::UnknownValue),
+            }),
+            "depth_exact" => {
+                let depth = value.parse()?;
+
+                SearchCriteria::Depth(Depth::Exact(depth))
+            }
+            "depth_over" => {
+                let depth = value.parse()?;
+
+                SearchCriteria::Depth(Depth::Over(depth))
+            }
+            "depth_under" => {
+                let depth = value.parse()?;
+
+                SearchCriteria::Depth(Depth::Under(depth))
+            }
+            "entropy_over" => {
+                let entropy = value.parse()?;
+
+                SearchCriteria::Entropy(Entropy::Over(entropy, EntropySample::default()))
+            }
+            "entropy_under" => {
+                let entropy = value.parse()?;
+
+                SearchCriteria::Entropy(Entropy::Under(entropy, EntropySample::default()))
+            }
+            #[cfg(feature = "audio_tags")]
+            "artist_exact" => SearchCriteria::AudioTag(AudioTag::Artist(TagText::Exact(value))),
+            #[cfg(feature = "audio_tags")]
+            "artist_contains" => SearchCriteria::AudioTag(AudioTag::Artist(TagText::Contains(value))),
+            #[cfg(feature = "audio_tags")]
+            "album_exact" => SearchCriteria::AudioTag(AudioTag::Album(TagText::Exact(value))),
+            #[cfg(feature = "audio_tags")]
+            "album_contains" => SearchCriteria::AudioTag(AudioTag::Album(TagText::Contains(value))),
+            #[cfg(feature = "audio_tags")]
+            "title_exact" => SearchCriteria::AudioTag(AudioTag::Title(TagText::Exact(value))),
+            #[cfg(feature = "audio_tags")]
+            "title_contains" => SearchCriteria::AudioTag(AudioTag::Title(TagText::Contains(value))),
+            #[cfg(feature = "audio_tags")]
+            "genre_exact" => SearchCriteria::AudioTag(AudioTag::Genre(TagText::Exact(value))),
+            #[cfg(feature = "audio_tags")]
+            "genre_contains" => SearchCriteria::AudioTag(AudioTag::Genre(TagText::Contains(value))),
+            #[cfg(feature = "audio_tags")]
+            "year_at" => SearchCriteria::AudioTag(AudioTag::YearAt(value.parse()?)),
+            #[cfg(feature = "audio_tags")]
+            "year_before" => SearchCriteria::AudioTag(AudioTag::YearBefore(value.parse()?)),
+            #[cfg(feature = "audio_tags")]
+            "year_after" => SearchCriteria::AudioTag(AudioTag::YearAfter(value.parse()?)),
+            #[cfg(feature = "audio_tags")]
+            "duration_over" => SearchCriteria::AudioTag(AudioTag::DurationOver(Duration::from_secs(value.parse()?))),
+            #[cfg(feature = "audio_tags")]
+            "duration_under" => SearchCriteria::AudioTag(AudioTag::DurationUnder(Duration::from_secs(value.parse()?))),
+            #[cfg(feature = "exif_tags")]
+            "taken_at" => SearchCriteria::ExifTag(ExifTag::TakenAt(parse_date_time_range(&value)?)),
+            #[cfg(feature = "exif_tags")]
+            "taken_before" => SearchCriteria::ExifTag(ExifTag::TakenBefore(parse_date_time(&value)?)),
+            #[cfg(feature = "exif_tags")]
+            "taken_after" => SearchCriteria::ExifTag(ExifTag::TakenAfter(parse_date_time(&value)?)),
+            #[cfg(feature = "exif_tags")]
+            "camera_make_exact" => SearchCriteria::ExifTag(ExifTag::Make(TagText::Exact(value))),
+            #[cfg(feature = "exif_tags")]
+            "camera_make_contains" => SearchCriteria::ExifTag(ExifTag::Make(TagText::Contains(value))),
+            #[cfg(feature = "exif_tags")]
+            "camera_model_exact" => SearchCriteria::ExifTag(ExifTag::Model(TagText::Exact(value))),
+            #[cfg(feature = "exif_tags")]
+            "camera_model_contains" => SearchCriteria::ExifTag(ExifTag::Model(TagText::Contains(value))),
+            #[cfg(feature = "exif_tags")]
+            "has_gps" => SearchCriteria::ExifTag(ExifTag::HasGps(match value.as_str() {
+                "true" => true,
+                "false" => false,
+                _ => return Err(SearchCriteriaParsingError::UnknownValue),
+            })),
+            #[cfg(feature = "exif_tags")]
+            "orientation" => {
+                let orientation = value.parse()?;
+
+                if !(1..=8).contains(&orientation) {
+                    return Err(SearchCriteriaParsingError::UnknownValue);
+                }
+
+                SearchCriteria::ExifTag(ExifTag::Orientation(orientation))
+            }
+            #[cfg(feature = "xattr")]
+            "has_any_xattr" => SearchCriteria::Xattr(Xattr::Any),
+            #[cfg(feature = "xattr")]
+            "has_xattr" => SearchCriteria::Xattr(Xattr::Exists(value)),
+            #[cfg(feature = "xattr")]
+            "xattr_equals" | "xattr_regex" => {
+                let name_and_value: Vec<&str> = value.splitn(2, '=').collect();
+
+                if name_and_value.len() == 1 {
+                    return Err(SearchCriteriaParsingError::NoValue);
+                }
+
+                let name = name_and_value[0].to_string();
+
+                if criteria_name == "xattr_equals" {
+                    SearchCriteria::Xattr(Xattr::Equals(name, name_and_value[1].to_string()))
+                } else {
+                    SearchCriteria::Xattr(Xattr::Regex(name, regex::Regex::new(name_and_value[1])?))
+                }
+            }
+            #[cfg(feature = "git")]
+            "git_status" => SearchCriteria::Git(Git::new(GitState::try_from(value.as_str())?)),
+            #[cfg(feature = "git")]
+            "git_last_commit_before" => {
+                let timestamp = value.parse()?;
+
+                SearchCriteria::Git(Git::new(GitState::LastCommitBefore(timestamp)))
+            }
             _ => return Err(SearchCriteriaParsingError::UnknownCriteria),
         })
     }
@@ -231,7 +562,325 @@ pub enum Created {
     After(i64),
 }
 
+/// Checks the entries of a directory. Never matches if the file isn't a directory
+///
+/// Only the direct children of the directory are checked, not the ones in subfolders
+#[derive(Debug, Clone)]
+pub enum DirectoryContains {
+    /// The directory contains a file or folder with exactly this name
+    Exact(String),
+
+    /// The directory contains a file or folder which name matches this regex
+    Regex(regex::Regex),
+}
+
+impl DirectoryContains {
+    /// Checks if `name` can be used for `DirectoryContains::Exact`
+    ///
+    /// It has to be the name of a single entry. Absolute paths, `.`, `..` and names
+    /// containing a path separator would look outside of the directory
+    #[must_use]
+    pub fn is_file_name(name: &str) -> bool {
+        let mut components = Path::new(name).components();
+
+        matches!((components.next(), components.next()), (Some(Component::Normal(_)), None))
+    }
+}
+
+/// Checks the other entries of the folder the file is in
+///
+/// The file itself never counts as its own sibling
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Sibling {
+    /// There is a sibling with exactly this name
+    Exact(String),
+
+    /// There is a sibling with the same name as the file but with this extension
+    /// instead of the file's own one. The extension is expected without the `.`
+    ///
+    /// For `photo.jpg` and `xmp` this checks for `photo.xmp`
+    WithExtension(String),
+}
+
+/// Compares the file against a reference file
+///
+/// This works like `-newer` of `find`. The modification time of the
+/// reference file is compared to the one of the file with nanosecond precision
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Reference {
+    NewerThan(ReferenceFile),
+    OlderThan(ReferenceFile),
+}
+
+/// A file other files are compared to
+///
+/// The metadata of the reference file is read once when this is created and not
+/// every time a file is checked against it
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ReferenceFile {
+    path: PathBuf,
+    modified: FileTime,
+}
+
+impl ReferenceFile {
+    /// Reads the metadata of the file `path` points to and creates a new `ReferenceFile` from it
+    ///
+    /// Symlinks are followed
+    ///
+    /// # Errors
+    ///
+    /// Fails if the metadata of the file can't be read
+    pub fn new(path: impl AsRef<Path>) -> Result<Self, FilyError<io::Error>> {
+        let path = path.as_ref();
+        let metadata = metadata(path)
+            .with_context(|| format!("Failed to get metadata of reference file {:?}", path.display()))?;
+
+        Ok(ReferenceFile {
+            path: path.to_path_buf(),
+            modified: FileTime::from_last_modification_time(&metadata),
+        })
+    }
+
+    /// Returns the path to the reference file
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the modification time the reference file had when this was created
+    #[must_use]
+    pub fn modified(&self) -> FileTime {
+        self.modified
+    }
+}
+
+/// Guesses if the file contains text or binary data by looking at its first
+/// `TEXT_DETECTION_BYTES` bytes. A file is binary if there is a null byte in there
+/// and it doesn't start with a UTF-16 byte order mark
+///
+/// Only regular files can match, folders, FIFOs, sockets and devices never do
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum TextOrBinary {
+    Text,
+    Binary,
+}
+
+/// Counts the lines of the file. The last line counts even if it doesn't end with a newline
+///
+/// The whole file is read for this. Only regular files can match
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum LineCount {
+    Exact(u64),
+    Over(u64),
+    Under(u64),
+}
+
+/// The encoding of a text file guessed from its first `TEXT_DETECTION_BYTES` bytes
+///
+/// Binary files never match and neither does anything that isn't a regular file
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum TextEncoding {
+    /// Only contains ASCII characters
+    Ascii,
+
+    /// Valid UTF-8 without a byte order mark. Files that only contain ASCII characters match this too
+    Utf8,
+
+    /// Starts with a UTF-8 byte order mark
+    Utf8Bom,
+
+    /// Starts with a UTF-16 little endian byte order mark
+    Utf16Le,
+
+    /// Starts with a UTF-16 big endian byte order mark
+    Utf16Be,
+
+    /// Text that isn't valid UTF-8 and has no byte order mark, i.e. Latin-1
+    Unknown,
+}
+
+/// The line endings a file uses
+///
+/// The whole file is read for this. Only regular files can match
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum LineEnding {
+    /// Only `\n`
+    Lf,
+
+    /// Only `\r\n`
+    Crlf,
+
+    /// Both `\n` and `\r\n`
+    Mixed,
+
+    /// There is no line ending at all
+    None,
+}
+
+/// Problems with the name or path of a file that break it on other systems
+///
+/// Lengths are in bytes. Checks that look at the name never match if the path has no
+/// filename, i.e. `.` or `/`
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum NameLint {
+    /// The name is longer than this. Most filesystems only allow names with up to 255 bytes
+    NameLongerThan(usize),
+
+    /// The whole path is longer than this. Linux only allows paths with up to 4096 bytes
+    PathLongerThan(usize),
+
+    /// The name starts or ends with whitespace
+    LeadingOrTrailingWhitespace,
+
+    /// The name contains control characters, i.e. a newline or a tab
+    ControlCharacters,
+
+    /// The name contains characters that aren't ASCII or isn't valid unicode
+    NonAscii,
+
+    /// The name is reserved on Windows like `CON`, `NUL`, `COM1` or `LPT1`. It doesn't matter if
+    /// it is upper or lower case or has an extension so `aux.txt` matches too
+    WindowsReservedName,
+
+    /// The name contains characters that aren't allowed on FAT or NTFS (`< > : " \ | ? *` and control
+    /// characters) or ends with a `.` or a space
+    InvalidWindowsCharacters,
+}
+
+/// How many folders deep the file is below the path that is searched in. The files directly in it
+/// have a depth of 1, i.e. `a/b.txt` has a depth of 2 when searching in `.`, `/home/u` or any other path
+///
+/// Files inside of an archive are as deep as the archive plus the folders inside of it
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Depth {
+    Exact(usize),
+    Over(usize),
+    Under(usize),
+}
+
+/// The Shannon entropy of the contents of the file in bits per byte. It is between 0 and 8
+///
+/// Encrypted and compressed data has an entropy close to 8, text usually has one between 4 and 5.
+/// Only the part of the file the `EntropySample` specifies is looked at. Small samples can't reach a high entropy,
+/// i.e. one with 100 bytes has at most 6.64. Folders never match
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Entropy {
+    Over(f64, EntropySample),
+    Under(f64, EntropySample),
+}
+
+/// Which part of a file is read to calculate its entropy
+///
+/// Sizes are in bytes
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum EntropySample {
+    /// Only the first this many bytes
+    Head(u64),
+
+    /// `count` blocks of `block_size` bytes that are spread out evenly over the file. The first
+    /// block is at the start and the last one at the end of the file. Files that are smaller than
+    /// all of the blocks together are read completely
+    Blocks {
+        count: usize,
+        block_size: u64,
+    },
+
+    /// The whole file. This can be slow for big files
+    Full,
+}
+
+impl Default for EntropySample {
+    /// 16 blocks with 4KB each
+    fn default() -> Self {
+        EntropySample::Blocks {
+            count: 16,
+            block_size: 4096,
+        }
+    }
+}
+
+/// Checks the tags of an MP3, FLAC or OGG Vorbis file
+///
+/// Files that aren't audio files in one of these formats or don't have the tag never match
+/// and neither does anything that isn't a regular file.
+/// Every criteria reads the tags again so putting the other criterias first makes this faster
+#[cfg(feature = "audio_tags")]
+#[derive(Debug, Clone)]
+pub enum AudioTag {
+    Artist(TagText),
+    Album(TagText),
+    Title(TagText),
+    Genre(TagText),
+    YearAt(i32),
+    YearBefore(i32),
+    YearAfter(i32),
+    DurationOver(Duration),
+    DurationUnder(Duration),
+}
+
+/// Checks the EXIF tags of a photo
+///
+/// Files that don't contain EXIF data or don't have the tag never match and neither
+/// does anything that isn't a regular file
+#[cfg(feature = "exif_tags")]
+#[derive(Debug, Clone)]
+pub enum ExifTag {
+    /// Checks the time the photo was taken at, which is stored in `DateTimeOriginal`. Unlike the
+    /// modification time it doesn't change when the file is copied
+    ///
+    /// The time is compared to the time the camera showed, no matter in which timezone it was.
+    /// Use `parse_date_time` to get one from a string like `2021-04-05T12:00`
+    ///
+    /// `TakenAt` matches every time in the range, use `parse_date_time_range` to get the whole day of `2021-04-05`
+    /// or the whole minute of `2021-04-05T12:00`
+    TakenAt(Range<NaiveDateTime>),
+    TakenBefore(NaiveDateTime),
+    TakenAfter(NaiveDateTime),
+
+    /// The manufacturer of the camera
+    Make(TagText),
+
+    /// The model of the camera
+    Model(TagText),
+
+    /// `true` if the photo has to have a GPS position and `false` if it must not have one
+    HasGps(bool),
+
+    /// The orientation has to be exactly this. Goes from 1 to 8
+    Orientation(u16),
+}
+
+/// How the text of a tag is checked
+#[derive(Debug, Clone)]
+pub enum TagText {
+    Exact(String),
+    Contains(String),
+    Regex(regex::Regex),
+}
+
+/// Checks the extended attributes of the file. Symlinks are followed
+///
+/// Only supported on Linux, macOS and the BSDs. Evaluating this on a file on a filesystem
+/// that doesn't support extended attributes fails
+#[cfg(feature = "xattr")]
+#[derive(Debug, Clone)]
+pub enum Xattr {
+    /// The file has at least one extended attribute
+    Any,
+
+    /// The file has an extended attribute with this name, i.e. `user.tag`
+    Exists(String),
+
+    /// The extended attribute with the name has exactly this value
+    Equals(String, String),
+
+    /// The value of the extended attribute with the name matches the regex. Values that aren't
+    /// valid UTF-8 have the invalid parts replaced with U+FFFD before they are matched
+    Regex(String, regex::Regex),
+}
+
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "presets", derive(serde::Deserialize), serde(rename_all = "lowercase"))]
 pub enum Ignore {
     Files,
     Folders,
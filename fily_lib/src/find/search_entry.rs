@@ -0,0 +1,107 @@
+use std::{path::Path, io};
+use filetime::FileTime;
+use walkdir::DirEntry;
+use crate::fily_err::{Context, FilyError};
+
+/// A file a `Condition<SearchCriteria>` can be evaluated on
+///
+/// This is implemented for `walkdir::DirEntry`, which gets everything from the filesystem,
+/// but can also be implemented for anything else that knows about the metadata of a file,
+/// i.e. an index of files
+///
+/// Criterias that need more than this (i.e. the siblings of a file) still look at the
+/// filesystem through `path`
+pub trait SearchEntry {
+    /// The path to the file
+    fn path(&self) -> &Path;
+
+    /// If the file is a folder. Symlinks only count as folders if they are followed
+    fn is_dir(&self) -> bool;
+
+    /// If the file is a regular file and not a folder, FIFO, socket or device. Symlinks
+    /// only count as regular files if they are followed
+    ///
+    /// Criterias that read the contents of a file only look at regular files since
+    /// reading from a FIFO could block forever
+    fn is_file(&self) -> bool;
+
+    /// If the file is a symlink that isn't followed
+    fn is_symlink(&self) -> bool;
+
+    /// How many folders deep the file is below the path that is searched in. That path itself has a
+    /// depth of 0 and the files directly in it a depth of 1
+    fn depth(&self) -> usize;
+
+    /// The size of the file in bytes
+    ///
+    /// # Errors
+    ///
+    /// Fails if the size can't be determined
+    fn size(&self) -> Result<u64, FilyError<io::Error>>;
+
+    /// The time the file was last modified
+    ///
+    /// # Errors
+    ///
+    /// Fails if the time can't be determined
+    fn modified(&self) -> Result<FileTime, FilyError<io::Error>>;
+
+    /// The time the file was last accessed
+    ///
+    /// # Errors
+    ///
+    /// Fails if the time can't be determined
+    fn accessed(&self) -> Result<FileTime, FilyError<io::Error>>;
+
+    /// The time the file was created
+    ///
+    /// # Errors
+    ///
+    /// Fails if the time can't be determined. Not all platforms support this
+    fn created(&self) -> Result<FileTime, FilyError<io::Error>>;
+}
+
+impl SearchEntry for DirEntry {
+    fn path(&self) -> &Path {
+        self.path()
+    }
+
+    fn is_dir(&self) -> bool {
+        self.file_type().is_dir()
+    }
+
+    fn is_file(&self) -> bool {
+        self.file_type().is_file()
+    }
+
+    fn is_symlink(&self) -> bool {
+        self.file_type().is_symlink()
+    }
+
+    fn depth(&self) -> usize {
+        self.depth()
+    }
+
+    fn size(&self) -> Result<u64, FilyError<io::Error>> {
+        Ok(dir_entry_metadata(self)?.len())
+    }
+
+    fn modified(&self) -> Result<FileTime, FilyError<io::Error>> {
+        Ok(FileTime::from_last_modification_time(&dir_entry_metadata(self)?))
+    }
+
+    fn accessed(&self) -> Result<FileTime, FilyError<io::Error>> {
+        Ok(FileTime::from_last_access_time(&dir_entry_metadata(self)?))
+    }
+
+    fn created(&self) -> Result<FileTime, FilyError<io::Error>> {
+        FileTime::from_creation_time(&dir_entry_metadata(self)?)
+            .ok_or_else(|| FilyError::new_with_context(io::Error::new(io::ErrorKind::Other, "Unsupported"), || format!("Failed to get creation time of {:?}", self.path().display())))
+    }
+}
+
+fn dir_entry_metadata(dir_entry: &DirEntry) -> Result<std::fs::Metadata, FilyError<io::Error>> {
+    dir_entry.metadata()
+        .map_err(io::Error::from)
+        .with_context(|| format!("Failed to get metadata of {:?}", dir_entry.path().display()))
+}
@@ -0,0 +1,306 @@
+use std::{
+    path::{Path, PathBuf},
+    collections::HashMap,
+    time::{Duration, Instant},
+    thread::sleep,
+    io,
+    fmt,
+    error::Error,
+};
+use inotify::{Inotify, WatchDescriptor, WatchMask, EventMask};
+use walkdir::WalkDir;
+use super::{FindOptions, ConditionEvalError, entry_matches};
+use crate::fily_err::{Context, FilyError};
+#[allow(unused_imports)]
+use log::{trace, debug, info, warn, error};
+
+/// Error number the kernel returns if the limit of inotify watches (`fs.inotify.max_user_watches`) is reached
+const ENOSPC: i32 = 28;
+
+#[derive(Debug)]
+pub enum WatchError {
+    /// Evaluating the conditions on a file that changed failed
+    ConditionEvalError(ConditionEvalError),
+
+    /// A folder couldn't be watched because the limit of inotify watches is reached. Changes in
+    /// this folder won't be noticed. The limit can be raised with the `fs.inotify.max_user_watches` sysctl
+    WatchLimitReached(FilyError<io::Error>),
+
+    /// The kernel dropped events because too many happened at once. Files could've been missed
+    EventsDropped,
+
+    /// Any other io error, i.e. failing to set up inotify or to watch a folder
+    IOError(FilyError<io::Error>),
+}
+
+impl Error for WatchError {}
+
+impl fmt::Display for WatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl From<FilyError<io::Error>> for WatchError {
+    fn from(err: FilyError<io::Error>) -> Self {
+        WatchError::IOError(err)
+    }
+}
+
+impl From<ConditionEvalError> for WatchError {
+    fn from(err: ConditionEvalError) -> Self {
+        WatchError::ConditionEvalError(err)
+    }
+}
+
+/// Watches folders for files that are created, modified or moved into them and
+/// reports the ones that match the conditions of a `FindOptions`
+///
+/// Every folder in the paths to watch and every subfolder, including ones that are created later,
+/// is watched with inotify. This is only available on Linux.
+///
+/// Changes to a file are debounced. A file is only checked once nothing happened to it for the
+/// debounce duration so a file that is still being written to isn't reported multiple times.
+///
+/// `max_num_results` and `unique_inodes` of the `FindOptions` are ignored
+#[derive(Debug)]
+pub struct FindWatcher {
+    inotify: Inotify,
+    find_options: FindOptions,
+    debounce: Duration,
+
+    /// The folder every watch belongs to together with its depth from the path it was found in
+    watched_folders: HashMap<WatchDescriptor, (PathBuf, usize)>,
+
+    /// Files that changed and haven't been checked yet together with their depth and the last time they changed
+    pending: HashMap<PathBuf, (usize, Instant)>,
+
+    watch_limit_reached: bool,
+    buffer: Vec<u8>,
+}
+
+impl FindWatcher {
+    /// Starts watching every folder in `paths_to_watch` and all of their subfolders
+    ///
+    /// Subfolders deeper than `max_search_depth` of `find_options` aren't watched. Paths
+    /// that point to a file instead of a folder are ignored.
+    ///
+    /// Returns the watcher and the folders that couldn't be watched
+    ///
+    /// # Errors
+    ///
+    /// Fails if inotify can't be initialized
+    #[allow(clippy::type_complexity)]
+    pub fn new<P: AsRef<Path>>(paths_to_watch: &[P], find_options: FindOptions, debounce: Duration) -> Result<(Self, Vec<(PathBuf, WatchError)>), WatchError> {
+        let paths_to_watch: Vec<PathBuf> = find_options.all_paths_to_search_in(paths_to_watch)
+            .into_iter()
+            .map(Path::to_path_buf)
+            .collect();
+
+        trace!("FindWatcher::new paths_to_watch: {:?} find_options: {:?} debounce: {:?}", paths_to_watch, find_options, debounce);
+
+        let inotify = Inotify::init().context("Failed to initialize inotify")?;
+
+        let mut find_watcher = FindWatcher {
+            inotify,
+            find_options,
+            debounce,
+            watched_folders: HashMap::new(),
+            pending: HashMap::new(),
+            watch_limit_reached: false,
+            buffer: vec![0_u8; 64 * 1024],
+        };
+
+        let mut errors = Vec::new();
+
+        for path in &paths_to_watch {
+            find_watcher.watch_folder_tree(path, 0, false, &mut errors);
+        }
+
+        Ok((find_watcher, errors))
+    }
+
+    /// Blocks until at least one file that changed matches the conditions or an error occured
+    ///
+    /// Returns the paths to the files that matched and the errors that occured. The same file
+    /// can be returned again if it changes again later.
+    ///
+    /// # Errors
+    ///
+    /// Fails if reading events from inotify fails
+    #[allow(clippy::type_complexity)]
+    pub fn wait_for_matches(&mut self) -> Result<(Vec<PathBuf>, Vec<(PathBuf, WatchError)>), WatchError> {
+        let mut matches = Vec::new();
+        let mut errors = Vec::new();
+
+        loop {
+            self.check_settled_files(&mut matches, &mut errors);
+
+            if !matches.is_empty() || !errors.is_empty() {
+                return Ok((matches, errors));
+            }
+
+            let earliest_change = self.pending.values().map(|(_, last_change)| *last_change).min();
+
+            let events_read = if let Some(earliest_change) = earliest_change {
+                sleep(self.debounce.saturating_sub(earliest_change.elapsed()));
+
+                match self.inotify.read_events(&mut self.buffer) {
+                    Ok(events) => events.map(|event| (event.wd, event.mask, event.name.map(Path::new).map(Path::to_path_buf))).collect(),
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => Vec::new(),
+                    Err(e) => return Err(FilyError::new(e, "Failed to read inotify events").into()),
+                }
+            } else {
+                self.inotify.read_events_blocking(&mut self.buffer)
+                    .context("Failed to read inotify events")?
+                    .map(|event| (event.wd, event.mask, event.name.map(Path::new).map(Path::to_path_buf)))
+                    .collect::<Vec<_>>()
+            };
+
+            for (watch_descriptor, mask, name) in events_read {
+                self.handle_event(&watch_descriptor, mask, name, &mut errors);
+            }
+        }
+    }
+
+    /// Checks every pending file that didn't change for the debounce duration against the conditions
+    fn check_settled_files(&mut self, matches: &mut Vec<PathBuf>, errors: &mut Vec<(PathBuf, WatchError)>) {
+        let debounce = self.debounce;
+
+        let settled_files: Vec<(PathBuf, usize)> = self.pending.iter()
+            .filter(|(_, (_, last_change))| last_change.elapsed() >= debounce)
+            .map(|(path, (depth, _))| (path.clone(), *depth))
+            .collect();
+
+        for (path, depth) in settled_files {
+            self.pending.remove(&path);
+
+            if depth < self.find_options.min_depth_from_start || depth > self.find_options.max_search_depth {
+                continue;
+            }
+
+            let entry = match WalkDir::new(&path).max_depth(0).follow_links(self.find_options.follow_symlinks).into_iter().next() {
+                Some(Ok(entry)) => entry,
+                Some(Err(e)) => {
+                    // Files that are removed again before they were checked are not an error
+                    if e.io_error().is_none_or(|io_err| io_err.kind() != io::ErrorKind::NotFound) {
+                        errors.push((path, ConditionEvalError::from(e).into()));
+                    }
+
+                    continue;
+                }
+                None => continue,
+            };
+
+            let mut condition_errors = Vec::new();
+
+            if entry_matches(&entry, &self.find_options, &mut condition_errors) {
+                matches.push(path);
+            }
+
+            errors.extend(condition_errors.into_iter().map(|(path, err)| (path, err.into())));
+        }
+    }
+
+    fn handle_event(&mut self, watch_descriptor: &WatchDescriptor, mask: EventMask, name: Option<PathBuf>, errors: &mut Vec<(PathBuf, WatchError)>) {
+        if mask.contains(EventMask::Q_OVERFLOW) {
+            warn!("inotify dropped events because too many happened at once");
+            errors.push((PathBuf::new(), WatchError::EventsDropped));
+            return;
+        }
+
+        if mask.contains(EventMask::IGNORED) {
+            // The folder was removed or unmounted so the watch doesn't exist anymore
+            self.watched_folders.remove(watch_descriptor);
+            return;
+        }
+
+        let (path, depth) = match (self.watched_folders.get(watch_descriptor), name) {
+            (Some((folder, folder_depth)), Some(name)) => (folder.join(name), folder_depth + 1),
+            _ => return,
+        };
+
+        if depth > self.find_options.max_search_depth {
+            return;
+        }
+
+        if mask.intersects(EventMask::DELETE | EventMask::MOVED_FROM) {
+            self.pending.remove(&path);
+            return;
+        }
+
+        if mask.contains(EventMask::ISDIR) && mask.intersects(EventMask::CREATE | EventMask::MOVED_TO) {
+            // Files could've been created in the new folder before we started watching it
+            // so everything that's already in there gets checked as well
+            self.watch_folder_tree(&path, depth, true, errors);
+        }
+
+        self.pending.insert(path, (depth, Instant::now()));
+    }
+
+    /// Adds a watch to `folder` and every subfolder of it
+    ///
+    /// If `check_contents` is `true` every file in the folders is added to the pending files
+    fn watch_folder_tree(&mut self, folder: &Path, depth: usize, check_contents: bool, errors: &mut Vec<(PathBuf, WatchError)>) {
+        let max_relative_depth = self.find_options.max_search_depth.saturating_sub(depth);
+
+        let entries = WalkDir::new(folder)
+            .max_depth(max_relative_depth)
+            .follow_links(self.find_options.follow_symlinks)
+            .into_iter();
+
+        for entry in entries {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    let error_path = e.path().unwrap_or(folder).to_path_buf();
+                    errors.push((error_path, ConditionEvalError::from(e).into()));
+                    continue;
+                }
+            };
+
+            let entry_depth = depth + entry.depth();
+
+            if check_contents && entry.depth() > 0 {
+                self.pending.insert(entry.path().to_path_buf(), (entry_depth, Instant::now()));
+            }
+
+            // Folders at the max depth don't have to be watched since their contents are too deep anyway
+            if entry.file_type().is_dir() && entry_depth < self.find_options.max_search_depth {
+                self.add_watch(entry.path(), entry_depth, errors);
+            }
+        }
+    }
+
+    fn add_watch(&mut self, folder: &Path, depth: usize, errors: &mut Vec<(PathBuf, WatchError)>) {
+        let mut watch_mask = WatchMask::CREATE
+            | WatchMask::MODIFY
+            | WatchMask::CLOSE_WRITE
+            | WatchMask::MOVED_TO
+            | WatchMask::MOVED_FROM
+            | WatchMask::DELETE
+            | WatchMask::ONLYDIR;
+
+        if !self.find_options.follow_symlinks {
+            watch_mask |= WatchMask::DONT_FOLLOW;
+        }
+
+        match self.inotify.watches().add(folder, watch_mask) {
+            Ok(watch_descriptor) => {
+                trace!("Watching {:?}", folder.display());
+                self.watched_folders.insert(watch_descriptor, (folder.to_path_buf(), depth));
+            }
+            Err(e) if e.raw_os_error() == Some(ENOSPC) => {
+                // Only the first one gets reported so we don't spam an error for every folder
+                if self.watch_limit_reached {
+                    debug!("Can't watch {:?} since the inotify watch limit is reached", folder.display());
+                } else {
+                    warn!("The inotify watch limit is reached. Raise fs.inotify.max_user_watches to watch more folders");
+                    self.watch_limit_reached = true;
+                    errors.push((folder.to_path_buf(), WatchError::WatchLimitReached(FilyError::new_with_context(e, || format!("Failed to watch {:?}", folder.display())))));
+                }
+            }
+            Err(e) => errors.push((folder.to_path_buf(), WatchError::IOError(FilyError::new_with_context(e, || format!("Failed to watch {:?}", folder.display()))))),
+        }
+    }
+}
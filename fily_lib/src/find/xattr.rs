@@ -0,0 +1,57 @@
+use std::{path::Path, io};
+use crate::fily_err::FilyError;
+#[allow(unused_imports)]
+use log::{trace, debug, info, warn, error};
+
+/// Reads the value of the extended attribute `name` of the file `path` points to
+///
+/// If `path` is a symlink the attribute of the file it points to is read when `follow_symlinks` is `true`
+/// and the attribute of the symlink itself otherwise. Returns `None` if the file doesn't have the attribute
+///
+/// Only supported on Linux, macOS and the BSDs
+///
+/// # Errors
+///
+/// Fails if the file can't be accessed or the filesystem it is on doesn't support extended attributes
+pub fn get_xattr(path: impl AsRef<Path>, name: &str, follow_symlinks: bool) -> Result<Option<Vec<u8>>, FilyError<io::Error>> {
+    let path = path.as_ref();
+
+    let value = if follow_symlinks {
+        ::xattr::get_deref(path, name)
+    } else {
+        ::xattr::get(path, name)
+    };
+
+    value.map_err(|e| with_context(e, path, || format!("Failed to read extended attribute {:?} of {:?}", name, path.display())))
+}
+
+/// Returns the names of all extended attributes of the file `path` points to
+///
+/// Symlinks are only followed if `follow_symlinks` is `true`
+///
+/// Only supported on Linux, macOS and the BSDs
+///
+/// # Errors
+///
+/// Fails if the file can't be accessed or the filesystem it is on doesn't support extended attributes
+pub fn list_xattrs(path: impl AsRef<Path>, follow_symlinks: bool) -> Result<Vec<String>, FilyError<io::Error>> {
+    let path = path.as_ref();
+
+    let names = if follow_symlinks {
+        ::xattr::list_deref(path)
+    } else {
+        ::xattr::list(path)
+    };
+
+    let names = names.map_err(|e| with_context(e, path, || format!("Failed to list extended attributes of {:?}", path.display())))?;
+
+    Ok(names.map(|name| name.to_string_lossy().into_owned()).collect())
+}
+
+fn with_context<F: Fn() -> String>(err: io::Error, path: &Path, context: F) -> FilyError<io::Error> {
+    if err.kind() == io::ErrorKind::Unsupported {
+        FilyError::new_with_context(err, || format!("The filesystem {:?} is on doesn't support extended attributes", path.display()))
+    } else {
+        FilyError::new_with_context(err, context)
+    }
+}
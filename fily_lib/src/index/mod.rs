@@ -0,0 +1,577 @@
+use std::{
+    path::{Path, PathBuf, Component},
+    fs::{read_dir, symlink_metadata, File, Metadata},
+    io::{self, BufReader, BufWriter, Read, Write},
+    collections::HashMap,
+    fmt,
+    error::Error,
+};
+use serde::{Serialize, Deserialize};
+use filetime::FileTime;
+use crate::{
+    find::{FindOptions, Ignore, ConditionEvalError, SearchEntry},
+    duplicates::crc32_of_file,
+    fily_err::{Context, FilyError},
+};
+#[allow(unused_imports)]
+use log::{trace, debug, info, warn, error};
+
+/// Written at the start of every index file so we don't try to read something that isn't one
+const INDEX_FILE_MAGIC: &[u8; 8] = b"FILYIDX\0";
+
+/// Version of the format the index is stored in. Increase this if the layout of `Index` changes
+const INDEX_FILE_VERSION: u32 = 1;
+
+#[derive(Debug)]
+pub enum IndexError {
+    /// Reading or writing the index file failed
+    IOError(io::Error),
+
+    /// The index couldn't be turned into bytes or the bytes couldn't be turned back into an index.
+    /// This can also happen if a path isn't valid UTF-8
+    SerializationError(bincode::Error),
+
+    /// The file isn't an index
+    NotAnIndex,
+
+    /// The index was written by a version of fily that stores it in a different format
+    UnsupportedVersion(u32),
+}
+
+impl Error for IndexError {}
+
+impl fmt::Display for IndexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl From<FilyError<io::Error>> for FilyError<IndexError> {
+    fn from(err: FilyError<io::Error>) -> Self {
+        let (io_err, context) = err.destructure();
+
+        FilyError::new(IndexError::IOError(io_err), context)
+    }
+}
+
+/// A list of files with their metadata that can be searched without touching the filesystem
+///
+/// Create one with `Index::create`, store it with `save` and load it again with `Index::load`.
+/// It can be searched with `find_in_index`.
+///
+/// The paths of the folders that are indexed are canonicalized so the index can
+/// be used from any working directory. This means every path in the index is absolute.
+///
+/// The index can be brought up to date with `refresh`. It only reads the contents of folders that
+/// changed since the last time but still has to check the modification time of every folder.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Index {
+    roots: Vec<PathBuf>,
+    hash_contents: bool,
+    entries: Vec<IndexEntry>,
+}
+
+/// A single file in an `Index`
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct IndexEntry {
+    path: PathBuf,
+    file_type: IndexedFileType,
+    size: u64,
+    modified: Timestamp,
+    accessed: Timestamp,
+    created: Option<Timestamp>,
+    crc32: Option<u32>,
+}
+
+/// The type of an indexed file. Symlinks are never followed
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub enum IndexedFileType {
+    File,
+    Folder,
+    Symlink,
+
+    /// Anything that isn't a regular file, folder or symlink, like FIFOs, sockets or devices
+    Other,
+}
+
+/// `FileTime` doesn't implement `Serialize` so this stores its parts
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+struct Timestamp {
+    seconds: i64,
+    nanoseconds: u32,
+}
+
+impl From<FileTime> for Timestamp {
+    fn from(file_time: FileTime) -> Self {
+        Timestamp {
+            seconds: file_time.unix_seconds(),
+            nanoseconds: file_time.nanoseconds(),
+        }
+    }
+}
+
+impl From<Timestamp> for FileTime {
+    fn from(timestamp: Timestamp) -> Self {
+        FileTime::from_unix_time(timestamp.seconds, timestamp.nanoseconds)
+    }
+}
+
+impl IndexEntry {
+    /// Returns the type of the file
+    #[must_use]
+    pub fn file_type(&self) -> IndexedFileType {
+        self.file_type
+    }
+
+    /// Returns the crc32 of the contents of the file if the index was created with hashes
+    #[must_use]
+    pub fn crc32(&self) -> Option<u32> {
+        self.crc32
+    }
+
+    fn from_metadata(path: PathBuf, metadata: &Metadata) -> Self {
+        let file_type = if metadata.file_type().is_symlink() {
+            IndexedFileType::Symlink
+        } else if metadata.is_dir() {
+            IndexedFileType::Folder
+        } else if metadata.is_file() {
+            IndexedFileType::File
+        } else {
+            IndexedFileType::Other
+        };
+
+        IndexEntry {
+            path,
+            file_type,
+            size: metadata.len(),
+            modified: FileTime::from_last_modification_time(metadata).into(),
+            accessed: FileTime::from_last_access_time(metadata).into(),
+            created: FileTime::from_creation_time(metadata).map(Timestamp::from),
+            crc32: None,
+        }
+    }
+}
+
+/// On its own an entry doesn't know which path is searched in, so its depth is counted from the root.
+/// `find_in_index` counts it from the path that is searched in instead
+impl SearchEntry for IndexEntry {
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn is_dir(&self) -> bool {
+        self.file_type == IndexedFileType::Folder
+    }
+
+    fn is_file(&self) -> bool {
+        self.file_type == IndexedFileType::File
+    }
+
+    fn is_symlink(&self) -> bool {
+        self.file_type == IndexedFileType::Symlink
+    }
+
+    fn depth(&self) -> usize {
+        self.path.components().filter(|component| matches!(component, Component::Normal(_))).count()
+    }
+
+    fn size(&self) -> Result<u64, FilyError<io::Error>> {
+        Ok(self.size)
+    }
+
+    fn modified(&self) -> Result<FileTime, FilyError<io::Error>> {
+        Ok(self.modified.into())
+    }
+
+    fn accessed(&self) -> Result<FileTime, FilyError<io::Error>> {
+        Ok(self.accessed.into())
+    }
+
+    fn created(&self) -> Result<FileTime, FilyError<io::Error>> {
+        self.created
+            .map(FileTime::from)
+            .ok_or_else(|| FilyError::new_with_context(io::Error::new(io::ErrorKind::Other, "Unsupported"), || format!("No creation time of {:?} in the index", self.path.display())))
+    }
+}
+
+/// An entry of the index together with how deep it is below the path that is searched in
+struct SearchedIndexEntry<'a> {
+    entry: &'a IndexEntry,
+    depth: usize,
+}
+
+impl SearchEntry for SearchedIndexEntry<'_> {
+    fn path(&self) -> &Path {
+        self.entry.path()
+    }
+
+    fn is_dir(&self) -> bool {
+        self.entry.is_dir()
+    }
+
+    fn is_file(&self) -> bool {
+        self.entry.is_file()
+    }
+
+    fn is_symlink(&self) -> bool {
+        self.entry.is_symlink()
+    }
+
+    fn depth(&self) -> usize {
+        self.depth
+    }
+
+    fn size(&self) -> Result<u64, FilyError<io::Error>> {
+        self.entry.size()
+    }
+
+    fn modified(&self) -> Result<FileTime, FilyError<io::Error>> {
+        self.entry.modified()
+    }
+
+    fn accessed(&self) -> Result<FileTime, FilyError<io::Error>> {
+        self.entry.accessed()
+    }
+
+    fn created(&self) -> Result<FileTime, FilyError<io::Error>> {
+        self.entry.created()
+    }
+}
+
+impl Index {
+    /// Walks through every folder in `paths_to_index` and stores the metadata of every file in it
+    ///
+    /// If `hash_contents` is `true` the crc32 of the contents of every file gets stored as well.
+    /// This can take a long time since every file has to be read.
+    ///
+    /// Returns the index and the files that couldn't be indexed together with the error
+    /// that occured. Folders that couldn't be read are in the index but their contents aren't.
+    pub fn create<P: AsRef<Path>>(paths_to_index: &[P], hash_contents: bool) -> (Self, Vec<(PathBuf, FilyError<io::Error>)>) {
+        let paths_to_index: Vec<&Path> = paths_to_index.iter().map(AsRef::as_ref).collect();
+
+        trace!("Index::create paths_to_index: {:?} hash_contents: {}", paths_to_index, hash_contents);
+
+        let mut errors = Vec::new();
+        let mut roots = Vec::new();
+
+        for path in paths_to_index {
+            match path.canonicalize() {
+                Ok(root) => if !roots.contains(&root) {
+                    roots.push(root);
+                }
+                Err(e) => errors.push((path.to_path_buf(), FilyError::new_with_context(e, || format!("Failed to canonicalize {:?}", path.display())))),
+            }
+        }
+
+        // Folders inside of other folders that are indexed would otherwise end up in the index twice
+        let roots: Vec<PathBuf> = roots.iter()
+            .filter(|root| !roots.iter().any(|other| other != *root && root.starts_with(other)))
+            .cloned()
+            .collect();
+
+        let mut index = Index {
+            roots,
+            hash_contents,
+            entries: Vec::new(),
+        };
+
+        errors.append(&mut index.refresh());
+
+        (index, errors)
+    }
+
+    /// Brings the index up to date with the filesystem
+    ///
+    /// Only the contents of folders whose modification time changed are read again. The
+    /// modification time of a folder only changes if a file in it was added, removed or renamed,
+    /// so the metadata of files in unchanged folders is kept as is even if the files themselves changed.
+    /// Create a new index if you need to be sure everything is up to date.
+    ///
+    /// Returns the files that couldn't be indexed together with the error that occured
+    pub fn refresh(&mut self) -> Vec<(PathBuf, FilyError<io::Error>)> {
+        trace!("Index::refresh roots: {:?}", self.roots);
+
+        let old_entries = std::mem::take(&mut self.entries);
+
+        let old_entries_by_path: HashMap<&Path, &IndexEntry> = old_entries.iter()
+            .map(|entry| (entry.path.as_path(), entry))
+            .collect();
+
+        let mut old_children: HashMap<&Path, Vec<&IndexEntry>> = HashMap::new();
+
+        for entry in &old_entries {
+            if let Some(parent) = entry.path.parent() {
+                old_children.entry(parent).or_default().push(entry);
+            }
+        }
+
+        let mut errors = Vec::new();
+        let mut folders_to_scan = Vec::new();
+
+        let roots = self.roots.clone();
+
+        for root in &roots {
+            match symlink_metadata(root) {
+                Ok(metadata) => {
+                    let entry = IndexEntry::from_metadata(root.clone(), &metadata);
+
+                    if entry.file_type == IndexedFileType::Folder {
+                        folders_to_scan.push(entry.clone());
+                    }
+
+                    self.push_entry(entry, &old_entries_by_path, &mut errors);
+                }
+                Err(e) => errors.push((root.clone(), FilyError::new_with_context(e, || format!("Failed to get metadata of {:?}", root.display())))),
+            }
+        }
+
+        while let Some(folder) = folders_to_scan.pop() {
+            let folder_is_unchanged = old_entries_by_path.get(folder.path.as_path())
+                .is_some_and(|old_folder| old_folder.modified == folder.modified);
+
+            if folder_is_unchanged {
+                if let Some(children) = old_children.get(folder.path.as_path()) {
+                    for child in children {
+                        if child.file_type != IndexedFileType::Folder {
+                            self.entries.push((*child).clone());
+                            continue;
+                        }
+
+                        // The folder itself could have changed so we have to look at it again
+                        match symlink_metadata(&child.path) {
+                            Ok(metadata) => {
+                                let entry = IndexEntry::from_metadata(child.path.clone(), &metadata);
+
+                                if entry.file_type == IndexedFileType::Folder {
+                                    folders_to_scan.push(entry.clone());
+                                }
+
+                                self.push_entry(entry, &old_entries_by_path, &mut errors);
+                            }
+                            Err(e) => errors.push((child.path.clone(), FilyError::new_with_context(e, || format!("Failed to get metadata of {:?}", child.path.display())))),
+                        }
+                    }
+
+                    continue;
+                }
+            }
+
+            debug!("Reading contents of {:?}", folder.path.display());
+
+            let folder_contents = match read_dir(&folder.path) {
+                Ok(folder_contents) => folder_contents,
+                Err(e) => {
+                    errors.push((folder.path.clone(), FilyError::new_with_context(e, || format!("Failed to read contents of {:?}", folder.path.display()))));
+                    continue;
+                }
+            };
+
+            for child in folder_contents {
+                let child_path = match child {
+                    Ok(child) => child.path(),
+                    Err(e) => {
+                        errors.push((folder.path.clone(), FilyError::new_with_context(e, || format!("Failed to read an entry of {:?}", folder.path.display()))));
+                        continue;
+                    }
+                };
+
+                match symlink_metadata(&child_path) {
+                    Ok(metadata) => {
+                        let entry = IndexEntry::from_metadata(child_path, &metadata);
+
+                        if entry.file_type == IndexedFileType::Folder {
+                            folders_to_scan.push(entry.clone());
+                        }
+
+                        self.push_entry(entry, &old_entries_by_path, &mut errors);
+                    }
+                    Err(e) => errors.push((child_path.clone(), FilyError::new_with_context(e, || format!("Failed to get metadata of {:?}", child_path.display())))),
+                }
+            }
+        }
+
+        debug!("Indexed {} files", self.entries.len());
+
+        errors
+    }
+
+    /// Adds an entry to the index and hashes it if necessary
+    ///
+    /// The hash of the old entry is reused if the size and modification time of the file didn't change
+    fn push_entry(&mut self, mut entry: IndexEntry, old_entries_by_path: &HashMap<&Path, &IndexEntry>, errors: &mut Vec<(PathBuf, FilyError<io::Error>)>) {
+        if self.hash_contents && entry.file_type == IndexedFileType::File {
+            let old_hash = old_entries_by_path.get(entry.path.as_path())
+                .filter(|old_entry| old_entry.size == entry.size && old_entry.modified == entry.modified)
+                .and_then(|old_entry| old_entry.crc32);
+
+            entry.crc32 = match old_hash {
+                Some(hash) => Some(hash),
+                None => match crc32_of_file(&entry.path) {
+                    Ok(hash) => Some(hash),
+                    Err(e) => {
+                        errors.push((entry.path.clone(), e));
+                        None
+                    }
+                },
+            };
+        }
+
+        self.entries.push(entry);
+    }
+
+    /// Returns the canonicalized paths of the folders that are indexed
+    #[must_use]
+    pub fn roots(&self) -> &[PathBuf] {
+        &self.roots
+    }
+
+    /// Returns every file in the index
+    #[must_use]
+    pub fn entries(&self) -> &[IndexEntry] {
+        &self.entries
+    }
+
+    /// Loads an index that was stored with `save`
+    ///
+    /// # Errors
+    ///
+    /// Fails if the file can't be read, isn't an index or was written in a different format
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, FilyError<IndexError>> {
+        let path = path.as_ref();
+
+        trace!("Index::load path: {:?}", path.display());
+
+        let mut reader = BufReader::new(File::open(path)
+            .with_context(|| format!("Failed to open index {:?}", path.display()))?);
+
+        let mut magic = [0_u8; 8];
+        let mut version = [0_u8; 4];
+
+        reader.read_exact(&mut magic)
+            .and_then(|()| reader.read_exact(&mut version))
+            .map_err(|_| FilyError::new_with_context(IndexError::NotAnIndex, || format!("{:?} is not an index", path.display())))?;
+
+        if &magic != INDEX_FILE_MAGIC {
+            return Err(FilyError::new_with_context(IndexError::NotAnIndex, || format!("{:?} is not an index", path.display())));
+        }
+
+        let version = u32::from_le_bytes(version);
+
+        if version != INDEX_FILE_VERSION {
+            return Err(FilyError::new_with_context(IndexError::UnsupportedVersion(version), || format!("{:?} was written in an unsupported format", path.display())));
+        }
+
+        bincode::deserialize_from(reader)
+            .map_err(|e| FilyError::new_with_context(IndexError::SerializationError(e), || format!("Failed to read index {:?}", path.display())))
+    }
+
+    /// Stores the index in a file. Overwrites the file if it already exists
+    ///
+    /// The file starts with the 8 bytes `FILYIDX\0` followed by the version of the format as a
+    /// 4 byte little endian integer. The rest is the index encoded with `bincode`.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the file can't be written or a path in the index isn't valid UTF-8
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), FilyError<IndexError>> {
+        let path = path.as_ref();
+
+        trace!("Index::save path: {:?}", path.display());
+
+        let mut writer = BufWriter::new(File::create(path)
+            .with_context(|| format!("Failed to create index {:?}", path.display()))?);
+
+        writer.write_all(INDEX_FILE_MAGIC)
+            .and_then(|()| writer.write_all(&INDEX_FILE_VERSION.to_le_bytes()))
+            .with_context(|| format!("Failed to write index {:?}", path.display()))?;
+
+        bincode::serialize_into(&mut writer, self)
+            .map_err(|e| FilyError::new_with_context(IndexError::SerializationError(e), || format!("Failed to write index {:?}", path.display())))?;
+
+        writer.flush()
+            .with_context(|| format!("Failed to write index {:?}", path.display()))?;
+
+        Ok(())
+    }
+}
+
+/// Does the same as `find` but looks at the files stored in `index` instead of the filesystem
+///
+/// Only files in the index that are in one of `paths_to_search_in` are checked. Since the index
+/// only contains absolute paths, the paths in `paths_to_search_in` get canonicalized and every
+/// path that is returned is absolute. Every file is returned at most once even if the paths overlap.
+/// The depth of a file is counted from the closest path it is in.
+///
+/// The size and times of a file are taken from the index. Criterias that need anything else (i.e. siblings)
+/// still look at the filesystem.
+///
+/// `additional_paths_to_search_in` of `find_options` is treated like the paths in `paths_to_search_in`.
+///
+/// `follow_symlinks` and `unique_inodes` of `find_options` are ignored since symlinks aren't followed
+/// while indexing and inodes aren't stored
+pub fn find_in_index<P: AsRef<Path>>(index: &Index, paths_to_search_in: &[P], find_options: &FindOptions) -> (Vec<PathBuf>, Vec<(PathBuf, ConditionEvalError)>) {
+    let paths_to_search_in: Vec<PathBuf> = find_options.all_paths_to_search_in(paths_to_search_in)
+        .into_iter()
+        .map(|path| path.canonicalize().unwrap_or_else(|_| path.to_path_buf()))
+        .collect();
+
+    trace!("find_in_index paths_to_search_in: {:?} find_options: {:?}", paths_to_search_in, find_options);
+
+    let mut results = Vec::new();
+    let mut errors = Vec::new();
+
+    for entry in index.entries() {
+        // The depth below the closest path to search in the entry is in
+        let depth = paths_to_search_in.iter()
+            .filter_map(|path| entry.path.strip_prefix(path).ok())
+            .map(|relative_path| relative_path.components().count())
+            .min();
+
+        let Some(depth) = depth.filter(|depth| (find_options.min_depth_from_start..=find_options.max_search_depth).contains(depth)) else {
+            continue;
+        };
+
+        if let Some(ignore) = find_options.ignore {
+            match ignore {
+                Ignore::Files => if entry.file_type == IndexedFileType::File {
+                    continue;
+                }
+                Ignore::Folders => if entry.file_type == IndexedFileType::Folder {
+                    continue;
+                }
+            }
+        }
+
+        if find_options.ignore_hidden_files {
+            if let Some(name) = entry.path.file_name().and_then(|name| name.to_str()) {
+                if name.starts_with('.') {
+                    continue;
+                }
+            }
+        }
+
+        let searched_entry = SearchedIndexEntry {
+            entry,
+            depth,
+        };
+
+        if !find_options.options.iter().all(|option| option.evaluate(&searched_entry).unwrap_or_else(|err| {
+            errors.push((entry.path.clone(), err));
+            false
+        })) {
+            continue;
+        }
+
+        results.push(entry.path.clone());
+
+        if results.len() >= find_options.max_num_results {
+            debug!("Max amount of results ({}) reached. Exiting early", find_options.max_num_results);
+
+            break;
+        }
+    }
+
+    debug!("Found {} files", results.len());
+
+    (results, errors)
+}
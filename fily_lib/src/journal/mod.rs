@@ -0,0 +1,376 @@
+use std::{path::{Path, PathBuf}, collections::HashMap, fs::{self, rename}, time::UNIX_EPOCH, io, fmt::{self, Write}, error::Error};
+use crate::fily_err::{Context, FilyError};
+#[allow(unused_imports)]
+use log::{trace, debug, info, warn, error};
+
+#[derive(Debug)]
+pub enum JournalError {
+    /// Failed to read or write the journal or to undo an entry
+    IOError(io::Error),
+
+    /// The journal isn't in the format `Journal` describes. Contains the line number, starting at 1
+    Malformed(usize),
+
+    /// The journal was written in a newer version of the format
+    UnsupportedVersion,
+
+    /// There is nothing at the new path of an entry anymore
+    NewPathMissing,
+
+    /// The file at the new path of an entry has a different size or modification time than when it was written to the journal
+    NewPathChanged,
+
+    /// There already is something at the old path of an entry
+    OldPathTaken,
+}
+
+impl Error for JournalError {}
+
+impl fmt::Display for JournalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+/// What moved a file
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Operation {
+    Rename,
+    Move,
+}
+
+/// A file that was renamed or moved
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct JournalEntry {
+    pub operation: Operation,
+    pub old_path: PathBuf,
+    pub new_path: PathBuf,
+
+    /// The size of the file after it was moved. For folders this is whatever the OS reports for them
+    pub size: u64,
+
+    /// The modification time of the file after it was moved as seconds and nanoseconds since the unix epoch.
+    /// `None` if it isn't available or before the epoch
+    pub modified: Option<(u64, u32)>,
+}
+
+/// A list of files that were renamed or moved, which can be used to undo that
+///
+/// A journal is saved as a UTF-8 text file. The first line is `fily journal 1`, where the 1 is the version of the format.
+/// It is followed by one line for every entry in the order they were done in. Empty lines are skipped. An entry has five
+/// fields separated by tabs:
+///
+/// ```text
+/// <operation>\t<old path>\t<new path>\t<size>\t<modification time>
+/// ```
+///
+/// * `operation` is `rename` or `move`
+/// * `old path` and `new path` are absolute paths, made so by canonicalizing the folder of a path and appending its file
+///   name unchanged. `\` is written as `\\`, a tab as `\t`, a newline as `\n`, a carriage return as `\r` and every byte
+///   that isn't part of valid UTF-8 as `\x` followed by two hex digits
+/// * `size` is the size in bytes of what was moved, after it was moved
+/// * `modification time` is `<seconds>.<nanoseconds>` since the unix epoch, with the nanoseconds always having nine digits,
+///   or `-` if it isn't known
+///
+/// New versions of the format will only be introduced if the existing fields can't be kept as they are
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Journal {
+    pub entries: Vec<JournalEntry>,
+}
+
+/// The first line of every journal
+const HEADER: &str = "fily journal 1";
+
+impl Journal {
+    /// Creates a journal from the renames or moves that were done, as `(old path, new path)` in the order they were done in
+    ///
+    /// Reads the size and modification time of every file at the place it ended up in. If a file was moved multiple
+    /// times, i.e. to a temporary name first, every entry of it gets the size and modification time of the last place
+    ///
+    /// The paths are made absolute, so the journal can be undone from any working directory
+    #[must_use]
+    pub fn record(operation: Operation, moved: &[(PathBuf, PathBuf)]) -> Self {
+        trace!("Journal::record operation: {:?} moved: {:?}", operation, moved);
+
+        let moved: Vec<(PathBuf, PathBuf)> = moved.iter()
+            .map(|(old_path, new_path)| (absolute_path(old_path), absolute_path(new_path)))
+            .collect();
+
+        // Going backwards, the file that is at a path at some point ends up where the next move away from it puts it
+        let mut ends_up_at: HashMap<&Path, &Path> = HashMap::new();
+        let mut final_paths = Vec::with_capacity(moved.len());
+
+        for (old_path, new_path) in moved.iter().rev() {
+            let final_path = ends_up_at.get(new_path.as_path()).copied().unwrap_or(new_path.as_path());
+
+            ends_up_at.insert(old_path, final_path);
+            final_paths.push(final_path);
+        }
+
+        let entries = moved.iter()
+            .zip(final_paths.into_iter().rev())
+            .map(|((old_path, new_path), final_path)| {
+                let (size, modified) = size_and_modified(final_path).unwrap_or_default();
+
+                JournalEntry {
+                    operation,
+                    old_path: old_path.clone(),
+                    new_path: new_path.clone(),
+                    size,
+                    modified,
+                }
+            })
+            .collect();
+
+        Journal {
+            entries,
+        }
+    }
+
+    /// Reads the journal `path` points to
+    ///
+    /// # Errors
+    ///
+    /// Fails if the file can't be read or isn't a journal
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, FilyError<JournalError>> {
+        let path = path.as_ref();
+
+        trace!("Journal::load path: {:?}", path.display());
+
+        let text = fs::read_to_string(path)
+            .map_err(JournalError::IOError)
+            .with_context(|| format!("Failed to read journal {:?}", path.display()))?;
+
+        let mut lines = text.lines();
+
+        match lines.next() {
+            Some(HEADER) => (),
+            Some(header) if header.strip_prefix("fily journal ").and_then(|version| version.parse::<u32>().ok()).is_some_and(|version| version > 1) => return Err(FilyError::new_with_context(JournalError::UnsupportedVersion, || format!("{:?} was written in a newer version of the journal format", path.display()))),
+            _ => return Err(FilyError::new_with_context(JournalError::Malformed(1), || format!("{:?} isn't a journal", path.display()))),
+        }
+
+        let entries = lines.enumerate()
+            .filter(|(_, line)| !line.is_empty())
+            .map(|(i, line)| parse_entry(line)
+                .ok_or_else(|| FilyError::new_with_context(JournalError::Malformed(i + 2), || format!("Line {} of {:?} isn't a valid entry", i + 2, path.display()))))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Journal {
+            entries,
+        })
+    }
+
+    /// Writes the journal to `path`, replacing the file that's already there
+    ///
+    /// # Errors
+    ///
+    /// Fails if the file can't be written
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), FilyError<JournalError>> {
+        let path = path.as_ref();
+
+        trace!("Journal::save path: {:?}", path.display());
+
+        let mut text = String::from(HEADER);
+        text.push('\n');
+
+        for entry in &self.entries {
+            let operation = match entry.operation {
+                Operation::Rename => "rename",
+                Operation::Move => "move",
+            };
+
+            let modified = match entry.modified {
+                Some((seconds, nanoseconds)) => format!("{}.{:09}", seconds, nanoseconds),
+                None => String::from("-"),
+            };
+
+            // Writing to a String can't fail
+            let _ = writeln!(text, "{}\t{}\t{}\t{}\t{}", operation, escape_path(&entry.old_path), escape_path(&entry.new_path), entry.size, modified);
+        }
+
+        fs::write(path, text)
+            .map_err(JournalError::IOError)
+            .with_context(|| format!("Failed to write journal {:?}", path.display()))
+    }
+
+    /// Moves every file back to its old path, starting with the last entry
+    ///
+    /// An entry is only undone if the file at its new path still has the same size and modification time and there is nothing
+    /// at its old path. If an entry can't be undone the others are still tried
+    ///
+    /// Returns the entries that couldn't be undone in the order they are in the journal, so they can be saved and tried again
+    #[must_use]
+    pub fn undo(&self) -> Vec<(JournalEntry, FilyError<JournalError>)> {
+        trace!("Journal::undo entries: {}", self.entries.len());
+
+        let mut failed_entries: Vec<(JournalEntry, FilyError<JournalError>)> = self.entries.iter()
+            .rev()
+            .filter_map(|entry| match undo_entry(entry) {
+                Ok(()) => {
+                    info!("Moved {:?} back to {:?}", entry.new_path.display(), entry.old_path.display());
+                    None
+                }
+                Err(e) => {
+                    info!("Failed to undo {:?} -> {:?} {}", entry.old_path.display(), entry.new_path.display(), e);
+                    Some((entry.clone(), e))
+                }
+            })
+            .collect();
+
+        failed_entries.reverse();
+        failed_entries
+    }
+}
+
+fn undo_entry(entry: &JournalEntry) -> Result<(), FilyError<JournalError>> {
+    let (size, modified) = size_and_modified(&entry.new_path)
+        .map_err(|_| FilyError::new_with_context(JournalError::NewPathMissing, || format!("{:?} doesn't exist anymore", entry.new_path.display())))?;
+
+    if size != entry.size || modified != entry.modified {
+        return Err(FilyError::new_with_context(JournalError::NewPathChanged, || format!("{:?} changed since it was moved there", entry.new_path.display())));
+    }
+
+    if entry.old_path.symlink_metadata().is_ok() {
+        return Err(FilyError::new_with_context(JournalError::OldPathTaken, || format!("There already is something at {:?}", entry.old_path.display())));
+    }
+
+    rename(&entry.new_path, &entry.old_path)
+        .map_err(JournalError::IOError)
+        .with_context(|| format!("Failed to move {:?} back to {:?}", entry.new_path.display(), entry.old_path.display()))
+}
+
+/// Canonicalizes the folder of `path` and appends its file name. The file itself isn't canonicalized, because it doesn't exist
+/// at the old path anymore and a symlink should stay the symlink that was moved. Returns `path` unchanged if that fails
+fn absolute_path(path: &Path) -> PathBuf {
+    let parent = path.parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+
+    if let (Ok(parent), Some(file_name)) = (parent.canonicalize(), path.file_name()) {
+        parent.join(file_name)
+    } else {
+        debug!("Failed to make {:?} absolute", path.display());
+        path.to_path_buf()
+    }
+}
+
+fn size_and_modified(path: &Path) -> io::Result<(u64, Option<(u64, u32)>)> {
+    let metadata = path.symlink_metadata()?;
+
+    let modified = metadata.modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| (duration.as_secs(), duration.subsec_nanos()));
+
+    Ok((metadata.len(), modified))
+}
+
+fn parse_entry(line: &str) -> Option<JournalEntry> {
+    let fields: Vec<&str> = line.split('\t').collect();
+
+    if fields.len() != 5 {
+        return None;
+    }
+
+    let operation = match fields[0] {
+        "rename" => Operation::Rename,
+        "move" => Operation::Move,
+        _ => return None,
+    };
+
+    let modified = match fields[4] {
+        "-" => None,
+        modified => {
+            let (seconds, nanoseconds) = modified.split_once('.')?;
+
+            if nanoseconds.len() != 9 {
+                return None;
+            }
+
+            Some((seconds.parse().ok()?, nanoseconds.parse().ok()?))
+        }
+    };
+
+    Some(JournalEntry {
+        operation,
+        old_path: unescape_path(fields[1])?,
+        new_path: unescape_path(fields[2])?,
+        size: fields[3].parse().ok()?,
+        modified,
+    })
+}
+
+fn escape_path(path: &Path) -> String {
+    let bytes = path_to_bytes(path);
+    let mut escaped = String::with_capacity(bytes.len());
+
+    for chunk in bytes.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            match c {
+                '\\' => escaped.push_str("\\\\"),
+                '\t' => escaped.push_str("\\t"),
+                '\n' => escaped.push_str("\\n"),
+                '\r' => escaped.push_str("\\r"),
+                _ => escaped.push(c),
+            }
+        }
+
+        for byte in chunk.invalid() {
+            let _ = write!(escaped, "\\x{:02x}", byte);
+        }
+    }
+
+    escaped
+}
+
+fn unescape_path(escaped: &str) -> Option<PathBuf> {
+    let mut bytes = Vec::with_capacity(escaped.len());
+    let mut chars = escaped.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buffer = [0; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+            continue;
+        }
+
+        match chars.next()? {
+            '\\' => bytes.push(b'\\'),
+            't' => bytes.push(b'\t'),
+            'n' => bytes.push(b'\n'),
+            'r' => bytes.push(b'\r'),
+            'x' => {
+                let hex: String = chars.by_ref().take(2).collect();
+                bytes.push(u8::from_str_radix(&hex, 16).ok()?);
+            }
+            _ => return None,
+        }
+    }
+
+    Some(bytes_to_path(bytes))
+}
+
+#[cfg(unix)]
+fn path_to_bytes(path: &Path) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+
+    path.as_os_str().as_bytes().to_vec()
+}
+
+/// Paths on other platforms aren't just bytes so they are written as UTF-8
+#[cfg(not(unix))]
+fn path_to_bytes(path: &Path) -> Vec<u8> {
+    path.to_string_lossy().into_owned().into_bytes()
+}
+
+#[cfg(unix)]
+fn bytes_to_path(bytes: Vec<u8>) -> PathBuf {
+    use std::{ffi::OsString, os::unix::ffi::OsStringExt};
+
+    PathBuf::from(OsString::from_vec(bytes))
+}
+
+#[cfg(not(unix))]
+fn bytes_to_path(bytes: Vec<u8>) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(&bytes).into_owned())
+}
@@ -12,9 +12,21 @@ pub mod rename;
 #[cfg(feature = "find")]
 pub mod find;
 
+#[cfg(feature = "index")]
+pub mod index;
+
 #[cfg(feature = "move_files")]
 pub mod move_files;
 
+#[cfg(feature = "journal")]
+pub mod journal;
+
+#[cfg(feature = "audio_tags")]
+pub mod audio_tags;
+
+#[cfg(feature = "exif_tags")]
+pub mod exif_tags;
+
 #[cfg(feature = "similar_images")]
 pub mod similar_images;
 
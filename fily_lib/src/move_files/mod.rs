@@ -1,4 +1,4 @@
-use std::{fs::{rename, create_dir_all}, path::Path, io, fmt, error::Error};
+use std::{fs::{rename, create_dir_all}, path::{Path, PathBuf}, io, fmt, error::Error};
 use crate::fily_err::{Context, FilyError};
 #[allow(unused_imports)]
 use log::{trace, debug, info, warn, error};
@@ -33,8 +33,8 @@ impl From<FilyError<io::Error>> for FilyError<MoveFilesError> {
 
 /// Moves files
 ///
-/// This function will return, if it doesn't fail, a `Vec` containing all paths which had an error.
-/// Those files weren't moved.
+/// This function will return, if it doesn't fail, a `Vec` with the files that were moved as `(old path, new path)`, which
+/// can be put into a `Journal`, and a `Vec` containing all paths which had an error. Those files weren't moved.
 ///
 /// # Errors
 ///
@@ -45,7 +45,8 @@ impl From<FilyError<io::Error>> for FilyError<MoveFilesError> {
 /// * `move_to` doesn't exist and it failed to create it
 /// * it failed to get the metadata of the path `move_to` points to
 /// * `move_to` points to a file
-pub fn move_files<P: AsRef<Path>>(move_to: impl AsRef<Path>, files_to_move: &[P]) -> Result<Vec<(&Path, FilyError<MoveFilesError>)>, FilyError<MoveFilesError>> {
+#[allow(clippy::type_complexity)]
+pub fn move_files<P: AsRef<Path>>(move_to: impl AsRef<Path>, files_to_move: &[P]) -> Result<(Vec<(PathBuf, PathBuf)>, Vec<(&Path, FilyError<MoveFilesError>)>), FilyError<MoveFilesError>> {
     let move_to = move_to.as_ref();
     let files_to_move: Vec<&Path> = files_to_move.iter().map(AsRef::as_ref).collect();
 
@@ -67,6 +68,7 @@ pub fn move_files<P: AsRef<Path>>(move_to: impl AsRef<Path>, files_to_move: &[P]
         ));
     }
 
+    let mut moved = Vec::new();
     let mut errors = Vec::new();
 
     for old_path in files_to_move {
@@ -84,13 +86,14 @@ pub fn move_files<P: AsRef<Path>>(move_to: impl AsRef<Path>, files_to_move: &[P]
         let mut new_path = move_to.to_path_buf();
         new_path.push(filename);
 
-        if let Err(e) = rename(&old_path, &new_path) {
-            errors.push((
+        match rename(&old_path, &new_path) {
+            Ok(()) => moved.push((old_path.to_path_buf(), new_path)),
+            Err(e) => errors.push((
                 old_path,
                 FilyError::new_with_context(MoveFilesError::IOError(e), || format!("Error moving {:?}", old_path.display()))
-            ));
+            )),
         };
     }
 
-    Ok(errors)
+    Ok((moved, errors))
 }
@@ -0,0 +1,155 @@
+use std::{path::{Path, PathBuf}, collections::{HashMap, HashSet}, fs::rename, ffi::OsString};
+use super::{RenameFilesError, plan::{RenamePlan, PlannedRename}};
+use crate::fily_err::FilyError;
+#[allow(unused_imports)]
+use log::{trace, debug, info, warn, error};
+
+impl RenamePlan {
+    /// Does every rename in `renames` as if they were a single operation
+    ///
+    /// Renames that depend on each other are done in an order in which no file is replaced, i.e. `b -> c` before `a -> b`.
+    /// Cycles like `a -> b, b -> a` are broken up by renaming one of the files to a temporary name first. If a rename fails
+    /// every rename that was done before it is undone. Files that were replaced because of `ConflictPolicy::Overwrite`
+    /// can't be brought back though
+    ///
+    /// The plan should've gone through `RenamePlan::resolve_conflicts` before, otherwise files can be replaced
+    ///
+    /// Returns every rename that was done as `(old path, new path)` in the order they were done in, including the ones to
+    /// and from temporary names. Doing them in reverse order with old and new path swapped undoes them
+    ///
+    /// # Errors
+    ///
+    /// Fails if a rename fails. If undoing the renames before it fails too the error contains the renames that are still done
+    pub fn apply(self) -> Result<Vec<(PathBuf, PathBuf)>, RenameFilesError> {
+        trace!("RenamePlan::apply renames: {:?}", self.renames);
+
+        let steps = ordered_steps(&self.renames);
+        let mut done_steps = Vec::with_capacity(steps.len());
+
+        for (old_path, new_path) in steps {
+            if let Err(e) = rename(&old_path, &new_path) {
+                let err = FilyError::new_with_context(e, || format!("Failed to rename {:?} to {:?}", old_path.display(), new_path.display()));
+                return Err(roll_back(done_steps, err));
+            }
+
+            info!("Renamed {:?} to {:?}", old_path.display(), new_path.display());
+
+            done_steps.push((old_path, new_path));
+        }
+
+        Ok(done_steps)
+    }
+}
+
+/// Undoes `done_steps` in reverse order after `err` happened
+///
+/// Stops at the first rename that can't be undone because undoing the ones before it could replace files
+fn roll_back(mut done_steps: Vec<(PathBuf, PathBuf)>, err: FilyError<std::io::Error>) -> RenameFilesError {
+    warn!("Undoing {} renames because of {}", done_steps.len(), err);
+
+    while let Some((old_path, new_path)) = done_steps.pop() {
+        if let Err(e) = rename(&new_path, &old_path) {
+            error!("Failed to undo the rename of {:?} to {:?} {}", old_path.display(), new_path.display(), e);
+
+            done_steps.push((old_path, new_path));
+            return RenameFilesError::RollbackFailed(err, done_steps);
+        }
+
+        info!("Undid the rename of {:?} to {:?}", old_path.display(), new_path.display());
+    }
+
+    RenameFilesError::RenameFailed(err)
+}
+
+/// Puts the renames in an order in which a file is only renamed to a path after the file that was there got renamed
+///
+/// Every rename depends on at most one other rename, the one of the file that has its new path. So following these
+/// dependencies either ends at a rename that doesn't depend on anything or leads into a cycle
+fn ordered_steps(renames: &[PlannedRename]) -> Vec<(PathBuf, PathBuf)> {
+    let renames: Vec<&PlannedRename> = renames.iter().filter(|planned_rename| planned_rename.old_path != planned_rename.new_path).collect();
+
+    let index_of_old_path: HashMap<&Path, usize> = renames.iter()
+        .enumerate()
+        .map(|(i, planned_rename)| (planned_rename.old_path.as_path(), i))
+        .collect();
+
+    let depends_on: Vec<Option<usize>> = renames.iter()
+        .map(|planned_rename| index_of_old_path.get(planned_rename.new_path.as_path()).copied())
+        .collect();
+
+    let mut used_paths: HashSet<PathBuf> = renames.iter()
+        .flat_map(|planned_rename| [planned_rename.old_path.clone(), planned_rename.new_path.clone()])
+        .collect();
+
+    let mut steps = Vec::with_capacity(renames.len());
+    let mut is_done = vec![false; renames.len()];
+
+    for start in 0..renames.len() {
+        // Follows the dependencies until something that is done already, a rename without one or a cycle comes up
+        let mut chain = Vec::new();
+        let mut current = Some(start);
+        let mut cycle_start = None;
+
+        while let Some(i) = current {
+            if is_done[i] {
+                break;
+            }
+
+            if let Some(position) = chain.iter().position(|&j| j == i) {
+                cycle_start = Some(position);
+                break;
+            }
+
+            chain.push(i);
+            current = depends_on[i];
+        }
+
+        if let Some(cycle_start) = cycle_start {
+            // The first rename of the cycle moves its file out of the way so the one that needs its path can go
+            // first, then the others follow and the file in the temporary place comes last
+            let first = renames[chain[cycle_start]];
+            let temporary_path = temporary_path(&first.old_path, &used_paths);
+
+            used_paths.insert(temporary_path.clone());
+            steps.push((first.old_path.clone(), temporary_path.clone()));
+
+            for &i in chain[cycle_start + 1..].iter().rev() {
+                steps.push((renames[i].old_path.clone(), renames[i].new_path.clone()));
+            }
+
+            steps.push((temporary_path, first.new_path.clone()));
+
+            for &i in &chain[cycle_start..] {
+                is_done[i] = true;
+            }
+
+            chain.truncate(cycle_start);
+        }
+
+        for &i in chain.iter().rev() {
+            steps.push((renames[i].old_path.clone(), renames[i].new_path.clone()));
+            is_done[i] = true;
+        }
+    }
+
+    steps
+}
+
+/// Returns a path next to `path` that doesn't exist and isn't in `used_paths`
+fn temporary_path(path: &Path, used_paths: &HashSet<PathBuf>) -> PathBuf {
+    let filename = path.file_name().unwrap_or_default();
+    let mut number = 0;
+
+    loop {
+        let mut temporary_filename = OsString::from(format!(".fily_rename_{}_", number));
+        temporary_filename.push(filename);
+
+        let temporary_path = path.with_file_name(temporary_filename);
+
+        if !used_paths.contains(&temporary_path) && temporary_path.symlink_metadata().is_err() {
+            return temporary_path;
+        }
+
+        number += 1;
+    }
+}
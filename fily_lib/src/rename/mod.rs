@@ -1,26 +1,78 @@
-use std::{fs::rename, path::Path, fmt, error::Error};
+use std::{path::{Path, PathBuf}, ops::Range, io, fmt, error::Error};
 use crate::fily_err::FilyError;
 #[allow(unused_imports)]
 use log::{trace, debug, info, warn, error};
 
 mod tokenizer;
-use tokenizer::{FilenamePart, TokenizeError, FilenameOptions, OptionsParseError};
+pub use tokenizer::{TokenizeError, OptionsParseError};
 
 mod parser;
-use parser::{Parser, ParseError};
+pub use parser::ParseError;
+
+mod plan;
+pub use plan::{plan_renames, RenamePlan, PlannedRename, RenameProblem, ConflictPolicy};
+
+mod apply;
 
 #[derive(Debug)]
 pub enum RenameFilesError {
     TokenizeError(FilyError<TokenizeError>),
     ParsingError(FilyError<ParseError>),
     OptionsParsingError(FilyError<OptionsParseError>),
+
+    /// Renames would replace other files or have invalid names and `ConflictPolicy::Abort` was used. Contains these renames
+    Conflicts(Vec<PlannedRename>),
+
+    /// A rename failed. Every rename that was done before it was undone
+    RenameFailed(FilyError<io::Error>),
+
+    /// A rename failed and undoing the renames before it failed too. Contains the renames that are still done as `(old path, new path)`
+    RollbackFailed(FilyError<io::Error>, Vec<(PathBuf, PathBuf)>),
+}
+
+impl RenameFilesError {
+    /// Returns the byte range of the template the error is at if the template itself is wrong
+    #[must_use]
+    pub fn template_span(&self) -> Option<Range<usize>> {
+        match self {
+            RenameFilesError::TokenizeError(e) => Some(e.get_error().span()),
+            RenameFilesError::OptionsParsingError(e) => Some(e.get_error().span()),
+            RenameFilesError::ParsingError(_)
+            | RenameFilesError::Conflicts(_)
+            | RenameFilesError::RenameFailed(_)
+            | RenameFilesError::RollbackFailed(..) => None,
+        }
+    }
 }
 
 impl Error for RenameFilesError {}
 
 impl fmt::Display for RenameFilesError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{:?}", self)
+        match self {
+            RenameFilesError::TokenizeError(e) => write!(f, "{}", e),
+            RenameFilesError::ParsingError(e) => write!(f, "{}", e),
+            RenameFilesError::OptionsParsingError(e) => write!(f, "{}", e),
+            RenameFilesError::Conflicts(conflicts) => {
+                write!(f, "Nothing was renamed because {} renames would replace other files or have invalid names:", conflicts.len())?;
+
+                for conflict in conflicts {
+                    write!(f, "\n{:?} -> {:?} {:?}", conflict.old_path.display(), conflict.new_path.display(), conflict.problems)?;
+                }
+
+                Ok(())
+            }
+            RenameFilesError::RenameFailed(e) => write!(f, "{}\nEvery rename before it was undone", e),
+            RenameFilesError::RollbackFailed(e, still_renamed) => {
+                write!(f, "{}\nUndoing the renames before it failed, these are still renamed:", e)?;
+
+                for (old_path, new_path) in still_renamed {
+                    write!(f, "\n{:?} -> {:?}", old_path.display(), new_path.display())?;
+                }
+
+                Ok(())
+            }
+        }
     }
 }
 
@@ -36,50 +88,68 @@ impl fmt::Display for RenameFilesError {
 /// * `filename_extension` The extension of the filename without the `.`. If there is no extension this will be an empty string
 /// * `filename_base` The base of the filename. If there is no extension this is the same as `filename`
 /// * `filesize` The size of the file in bytes
-/// * `incrementing_number` A number that will increment by one after each file. By default it starts at 0 but you can change the starting point
+/// * `incrementing_number` A number that will increment by one after each file. By default it starts at 0 but you can change the starting point and the step. It can have a format: `{incrementing_number:04}` pads it with zeroes to 4 digits, `{incrementing_number:4}` with spaces, `x` writes it in hexadecimal and `roman` as roman numerals, like `{incrementing_number:02x}` or `{incrementing_number:roman}`
+/// * `modified`, `created` and `accessed` When the file was last modified, created or last accessed. Not every platform and filesystem stores when a file was created, files without it aren't renamed
+/// * `now` When the renaming started, which is the same for every file
+/// * `artist`, `album`, `title` and `genre` The tag of an MP3, FLAC or OGG file. Path separators in the tag are replaced with `_`. Files without the tag aren't renamed
+/// * `year` The year an audio file was recorded or released in
+/// * `duration` The length of an audio file in seconds
+/// * `exif_date` When a photo was taken, read from its EXIF tags. If the photo doesn't have the offset of that time, which cameras usually don't store, it is written as the camera shows it and `timezone` doesn't change it
+/// * `camera_make`, `camera_model` and `lens` The EXIF tag of a photo. Path separators in the tag are replaced with `_`. Files without the tag aren't renamed
+/// * `image_width` and `image_height` The size of an image in pixels
+/// * `actual_extension` The extension of the format an image actually has, guessed from its content. `{filename_base}.{actual_extension}` fixes a wrong extension
 ///
-/// There are also options for the template. Everything after the first `|` will be interpreted as such. Currently there is only one option:
+/// Dates are written as `2021-04-05` by default. They can get a strftime format instead, like `{modified:%Y%m%d_%H%M%S}`. Put the format in `"` or `'` if it contains a `:`, like `{now:'%H:%M'}`. Path separators in a date are replaced with `_`
+///
+/// The value of a variable can be changed with filters, which are written behind its name separated by `:`. Multiple filters are applied from left to right, like `{filename_base:trim:lower:replace(" ","_")}`. Arguments can be put in `"` or `'` to include a `:`, `,`, `)` or whitespace at the start or end:
+///
+/// * `lower` and `upper` Makes every letter lowercase or uppercase
+/// * `replace(from,to)` Replaces every occurrence of `from` with `to`
+/// * `slice(start,end)` Keeps the characters from `start` up to, but not including, `end`. Without `end` it goes until the end. `slice(0,10)` keeps the first 10 characters
+/// * `trim` Removes whitespace at the start and the end
+/// * `title` Makes the first letter of every word uppercase and the others lowercase
+/// * `slug` Makes every letter lowercase and replaces everything that isn't a letter or a digit with a single `-`
+///
+/// There are also options for the template. Everything after the first `|` will be interpreted as such. Multiple options are separated by a `|`:
 ///
 /// * `incrementing_number_starts_at` Sets the starting point of `incrementing_number`. The number can be negative. Should be used like this: `{incrementing_number}|incrementing_number_starts_at=42`
+/// * `incrementing_number_step` How much `incrementing_number` changes after every file. Can be negative, like `incrementing_number_step=-10`
+/// * `incrementing_number_per_folder` Doesn't take a value. Every folder gets its own `incrementing_number` that starts at the starting point
+/// * `incrementing_number_order` `input`, `name`, `modified` or `size`. The order in which files get their `incrementing_number`, the order they were passed in by default. The others go from the lowest to the highest value
+/// * `match` A regex every file has to match. The capture groups can be used as variables by their number, like `{1}`, or their name, like `{date}`. `{0}` is the whole match and names of other variables can't be used. Since a regex can contain a `|` it has to be put in `"` or `'` if other options come after it, otherwise it goes until the end of the template. For example: `{d}.{m}.{y}_{4}.jpg|match=^IMG_(?P<y>\d{4})(?P<m>\d{2})(?P<d>\d{2})_(\d+)` or `{1}|match="^IMG_(\d+)"|timezone=utc`
+/// * `match_against` `filename` or `path`. What `match` is matched against, the filename by default. Path separators in captures are replaced with `_`
+/// * `no_match` `skip` or `abort`. Files that don't match are skipped by default. With `abort` nothing is renamed if a file doesn't match
+/// * `timezone` `local` or `utc`. The timezone dates are written in, `local` by default
+///
+/// Before anything is renamed it is checked if a new path is already taken by an existing file or another file that is renamed.
+/// `conflict_policy` decides what happens then. New names that are empty or contain a path separator are never used
+///
+/// The renames are done as if they were a single operation. Files can swap their names and if one rename fails the ones before
+/// it are undone, look at `RenamePlan::apply` for the details
+///
+/// Returns every rename that was done as `(old path, new path)` in the order they were done in, which can be put into a `Journal`
+///
+/// Use `plan_renames` to see what would be renamed without renaming anything
 ///
 /// # Errors
 ///
-/// This fails if either the template or the options for renaming have an error
-pub fn rename_files<P: AsRef<Path>>(files_to_rename: &[P], new_filename_template: &str) -> Result<(), RenameFilesError> {
+/// This fails if either the template or the options for renaming have an error. `RenameFilesError::template_span` returns where in the template the error is
+///
+/// With `ConflictPolicy::Abort` it also fails if there is a conflict, without renaming anything. It also fails if a rename fails
+pub fn rename_files<P: AsRef<Path>>(files_to_rename: &[P], new_filename_template: &str, conflict_policy: ConflictPolicy) -> Result<Vec<(PathBuf, PathBuf)>, RenameFilesError> {
     let files_to_rename: Vec<&Path> = files_to_rename.iter().map(AsRef::as_ref).collect();
 
-    trace!("rename_files files_to_rename: {:?} new_filename_template: {}", files_to_rename, new_filename_template);
-
-    let text_template_and_options: Vec<&str> = new_filename_template.splitn(2, '|').collect();
-    let text_template = text_template_and_options[0];
-
-    let filename_template = FilenamePart::from_text(text_template)?;
-
-    let options = if let Some(text_options) = text_template_and_options.get(1) {
-        FilenameOptions::new(text_options)?
-    } else {
-        FilenameOptions::default()
-    };
+    trace!("rename_files files_to_rename: {:?} new_filename_template: {} conflict_policy: {:?}", files_to_rename, new_filename_template, conflict_policy);
 
-    let mut parser = Parser::builder().incrementing_number(options.incrementing_number_starts_at).build();
+    let plan = plan_renames(&files_to_rename, new_filename_template)?.resolve_conflicts(conflict_policy)?;
 
-    for path in files_to_rename {
-        let filename_new = match parser.parse_filename(&filename_template, &path) {
-            Ok(filename) => filename,
-            Err(e) => {
-                info!("parse_filename failed {}", e);
-                continue;
-            }
-        };
-
-        let old_path = path;
-        let new_path = old_path.with_file_name(&filename_new);
+    for (path, e) in &plan.skipped {
+        info!("parse_filename failed for {:?} {}", path.display(), e);
+    }
 
-        match rename(&old_path, &new_path) {
-            Ok(()) => info!("Renamed {:?} to {:?}", old_path.display(), new_path.display()),
-            Err(e) => info!("Failed to rename {:?} to {:?} {}", old_path.display(), new_path.display(), e),
-        };
+    for conflict in &plan.conflicts {
+        info!("Skipped renaming {:?} to {:?} {:?}", conflict.old_path.display(), conflict.new_path.display(), conflict.problems);
     }
 
-    Ok(())
+    plan.apply()
 }
@@ -1,6 +1,21 @@
-use std::{ffi::OsStr, path::Path, io, fmt, error::Error};
+use std::{ffi::OsStr, path::{Path, PathBuf}, collections::HashMap, fs::Metadata, io, fmt, error::Error};
+use regex::Regex;
+use filetime::FileTime;
+use chrono::{DateTime, Local, TimeZone, Utc};
 use super::RenameFilesError;
-use super::tokenizer::{FilenamePart, FilenameVariable};
+use super::tokenizer::{FilenamePart, FilenameVariable, Filter, MatchTarget, NumberFormat, NumberBase, DateVariable, Timezone};
+#[cfg(feature = "audio_tags")]
+use super::tokenizer::AudioTagVariable;
+#[cfg(feature = "audio_tags")]
+use crate::audio_tags::{AudioTags, AudioTagsError};
+#[cfg(feature = "exif_tags")]
+use super::tokenizer::ExifVariable;
+#[cfg(feature = "exif_tags")]
+use crate::exif_tags::{ExifTags, ExifTagsError};
+#[cfg(feature = "check_image_formats")]
+use super::tokenizer::ImageVariable;
+#[cfg(feature = "check_image_formats")]
+use crate::check_image_formats::image_format_guess_from_content;
 use crate::fily_err::FilyError;
 #[allow(unused_imports)]
 use log::{trace, debug, info, warn, error};
@@ -15,6 +30,35 @@ pub enum ParseError {
 
     /// Happens when the call to `metadata` fails
     IOError(io::Error),
+
+    /// The file doesn't match the `match` option
+    NoMatch,
+
+    /// `incrementing_number` is 0 or below and can't be written as a roman numeral
+    NoRomanNumeral,
+
+    /// The platform or filesystem doesn't store the date a variable needs or it is out of range
+    MissingDate,
+
+    /// The file doesn't have the audio tag a variable needs
+    #[cfg(feature = "audio_tags")]
+    MissingAudioTag,
+
+    /// Failed to read the tags of an audio file
+    #[cfg(feature = "audio_tags")]
+    AudioTagsError(AudioTagsError),
+
+    /// The file doesn't have the EXIF tag a variable needs
+    #[cfg(feature = "exif_tags")]
+    MissingExifTag,
+
+    /// Failed to read the EXIF tags of a photo
+    #[cfg(feature = "exif_tags")]
+    ExifTagsError(ExifTagsError),
+
+    /// Failed to read the width and height of an image
+    #[cfg(feature = "check_image_formats")]
+    ImageError(image::ImageError),
 }
 
 impl Error for ParseError {}
@@ -34,10 +78,26 @@ impl From<FilyError<ParseError>> for RenameFilesError {
 /// Used to parse a sequence of `FilenamePart`s to a `String`
 ///
 /// Use `Parser::builder` to build or instantiate directly with `Default` or `Parser::new` if you don't need to change
-/// the starting point of the incrementing number from 0
-#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+/// how the incrementing number counts or match files against a regex
+#[derive(Debug, Clone)]
 pub struct Parser {
     incrementing_number: isize,
+    incrementing_number_step: isize,
+
+    /// The next incrementing number of every folder. `None` if there is only one for all files
+    incrementing_numbers_per_folder: Option<HashMap<PathBuf, isize>>,
+    match_regex: Option<Regex>,
+    match_against: MatchTarget,
+    timezone: Timezone,
+
+    /// The time the parser was created at, used by `{now}`
+    now: DateTime<Utc>,
+}
+
+impl Default for Parser {
+    fn default() -> Self {
+        ParserBuilder::new().build()
+    }
 }
 
 impl Parser {
@@ -57,16 +117,67 @@ impl Parser {
     ///
     /// # Errors
     ///
-    /// Returns an error if either there was a `FilenamePart::Error` in `tokens` or
-    /// if something went wrong getting info on a file
+    /// Returns an error if either there was a `FilenamePart::Error` in `tokens`,
+    /// if something went wrong getting info on a file or if the file doesn't match the regex
     pub fn parse_filename<'a>(&mut self, tokens: &[FilenamePart<'a>], path: impl AsRef<Path>) -> Result<String, FilyError<ParseError>> {
         let mut parsed_filename = String::new();
 
+        // A file that doesn't match isn't renamed, even if none of its captures are used
+        let captures = self.captures(path.as_ref())?;
+
+        // The metadata and tags are only read once per file and only if a variable needs them
+        let mut metadata = None;
+        #[cfg(feature = "audio_tags")]
+        let mut audio_tags = None;
+        #[cfg(feature = "exif_tags")]
+        let mut exif_tags = None;
+        #[cfg(feature = "check_image_formats")]
+        let mut image_dimensions = None;
+
         for token in tokens {
-            match *token {
-                FilenamePart::Constant(string) => parsed_filename.push_str(string),
-                FilenamePart::Variable(variable) => parsed_filename.push_str(&self.parse_filename_variable(variable, &path)?),
+            let (variable, filters) = match token {
+                FilenamePart::Constant(string) => {
+                    parsed_filename.push_str(string);
+                    continue;
+                }
+                FilenamePart::Variable(variable, filters) => (*variable, filters),
             };
+
+            let value = match variable {
+                FilenameVariable::Capture(index) => {
+                    // Groups that didn't take part in the match are empty
+                    let capture = captures.as_ref()
+                        .and_then(|captures| captures.get(index))
+                        .and_then(Option::as_deref)
+                        .unwrap_or_default();
+
+                    replace_separators(capture)
+                }
+                #[cfg(feature = "audio_tags")]
+                FilenameVariable::AudioTag(variable) => Self::parse_audio_tag_variable(variable, &path, &mut audio_tags)?,
+                #[cfg(feature = "exif_tags")]
+                FilenameVariable::Date(DateVariable::ExifDate, date_format) => {
+                    let path = path.as_ref();
+
+                    let tags = cached_exif_tags(path, &mut exif_tags)?;
+
+                    let date_time = tags.date_time_original
+                        .ok_or_else(|| FilyError::new_with_context(ParseError::MissingExifTag, || format!("{:?} doesn't have the date it was taken at", path.display())))?;
+
+                    // Without an offset it isn't known which timezone the time is in, so it's written as the camera shows it
+                    match tags.offset_time_original {
+                        Some(offset) => self.format_date(Utc.from_utc_datetime(&(date_time - offset)), date_format),
+                        None => replace_separators(&date_time.format(date_format).to_string()),
+                    }
+                }
+                #[cfg(feature = "exif_tags")]
+                FilenameVariable::Exif(variable) => Self::parse_exif_variable(variable, &path, &mut exif_tags)?,
+                #[cfg(feature = "check_image_formats")]
+                FilenameVariable::Image(variable) => Self::parse_image_variable(variable, &path, &mut image_dimensions)?,
+                variable => self.parse_filename_variable(variable, &path, &mut metadata)?,
+            };
+
+            parsed_filename.push_str(&filters.iter().fold(value, |value, filter| apply_filter(&value, *filter)));
         }
 
         Ok(parsed_filename)
@@ -75,7 +186,9 @@ impl Parser {
     /// Produces a string from a single `FilenameVariable`
     ///
     /// Output may change depending on where `path` points to
-    fn parse_filename_variable(&mut self, variable: FilenameVariable, path: impl AsRef<Path>) -> Result<String, FilyError<ParseError>> {
+    ///
+    /// `metadata` is used as a cache and filled with the metadata of the file if it is `None`
+    fn parse_filename_variable(&mut self, variable: FilenameVariable<'_>, path: impl AsRef<Path>, metadata: &mut Option<Metadata>) -> Result<String, FilyError<ParseError>> {
         let path = path.as_ref();
         Ok(match variable {
             FilenameVariable::Filename => path.file_name()
@@ -93,21 +206,276 @@ impl Parser {
                 .to_str()
                 .ok_or_else(|| FilyError::new_with_context(ParseError::UTF8ConversionFailed, || format!("Can't convert {:?} to UTF-8", path.display())))?
                 .to_string(),
-            FilenameVariable::FileSize => path.metadata()
-                .map_err(|e| FilyError::new_with_context(ParseError::IOError(e), || format!("Failed to get size of {:?}", path.display())))?
+            FilenameVariable::FileSize => cached_metadata(path, metadata)?
                 .len()
                 .to_string(),
-            FilenameVariable::IncrementingNumber => {
-                let num = self.incrementing_number;
-                self.incrementing_number += 1;
-                num.to_string()
+            FilenameVariable::IncrementingNumber(format) => {
+                let next_num = match &mut self.incrementing_numbers_per_folder {
+                    Some(incrementing_numbers) => incrementing_numbers
+                        .entry(path.parent().unwrap_or_else(|| Path::new("")).to_path_buf())
+                        .or_insert(self.incrementing_number),
+                    None => &mut self.incrementing_number,
+                };
+
+                let num = *next_num;
+                *next_num += self.incrementing_number_step;
+
+                format_number(num, format)
+                    .ok_or_else(|| FilyError::new_with_context(ParseError::NoRomanNumeral, || format!("{} can't be written as a roman numeral", num)))?
             },
+            FilenameVariable::Date(date_variable, date_format) => {
+                let date = match date_variable {
+                    DateVariable::Modified => date_time(FileTime::from_last_modification_time(cached_metadata(path, metadata)?)),
+                    DateVariable::Accessed => date_time(FileTime::from_last_access_time(cached_metadata(path, metadata)?)),
+                    DateVariable::Created => FileTime::from_creation_time(cached_metadata(path, metadata)?).and_then(date_time),
+                    DateVariable::Now => Some(self.now),
+                    #[cfg(feature = "exif_tags")]
+                    DateVariable::ExifDate => unreachable!("The EXIF date is handled in parse_filename"),
+                };
+
+                let date = date
+                    .ok_or_else(|| FilyError::new_with_context(ParseError::MissingDate, || format!("Can't get the {:?} date of {:?}", date_variable, path.display())))?;
+
+                self.format_date(date, date_format)
+            }
+            FilenameVariable::Capture(_) => unreachable!("Captures are handled in parse_filename"),
+            #[cfg(feature = "audio_tags")]
+            FilenameVariable::AudioTag(_) => unreachable!("Audio tag variables are handled in parse_filename"),
+            #[cfg(feature = "exif_tags")]
+            FilenameVariable::Exif(_) => unreachable!("EXIF variables are handled in parse_filename"),
+            #[cfg(feature = "check_image_formats")]
+            FilenameVariable::Image(_) => unreachable!("Image variables are handled in parse_filename"),
         })
     }
+
+    /// Writes `date` with the strftime format `date_format` in the timezone of the parser
+    fn format_date(&self, date: DateTime<Utc>, date_format: &str) -> String {
+        let formatted_date = match self.timezone {
+            Timezone::Local => date.with_timezone(&Local).format(date_format).to_string(),
+            Timezone::Utc => date.format(date_format).to_string(),
+        };
+
+        // Formats like `%D` contain a `/`
+        replace_separators(&formatted_date)
+    }
+
+    /// Matches the filename or the path of `path` against the regex
+    ///
+    /// Returns the text of every capture group, or `None` if there is no regex
+    fn captures(&self, path: &Path) -> Result<Option<Vec<Option<String>>>, FilyError<ParseError>> {
+        let Some(match_regex) = &self.match_regex else {
+            return Ok(None);
+        };
+
+        let haystack = match self.match_against {
+            MatchTarget::Filename => path.file_name()
+                .ok_or_else(|| FilyError::new_with_context(ParseError::NoFilename, || format!("Can't get filename of {:?}", path.display())))?,
+            MatchTarget::Path => path.as_os_str(),
+        };
+
+        let haystack = haystack.to_str()
+            .ok_or_else(|| FilyError::new_with_context(ParseError::UTF8ConversionFailed, || format!("Can't convert {:?} to UTF-8", path.display())))?;
+
+        let captures = match_regex.captures(haystack)
+            .ok_or_else(|| FilyError::new_with_context(ParseError::NoMatch, || format!("{:?} doesn't match {:?}", haystack, match_regex.as_str())))?;
+
+        Ok(Some(captures.iter()
+            .map(|capture| capture.map(|capture| capture.as_str().to_string()))
+            .collect()))
+    }
+
+    /// Produces a string from a variable that is read from the tags of an audio file
+    ///
+    /// `audio_tags` is used as a cache and filled with the tags of the file if it is `None`
+    #[cfg(feature = "audio_tags")]
+    fn parse_audio_tag_variable(variable: AudioTagVariable, path: impl AsRef<Path>, audio_tags: &mut Option<AudioTags>) -> Result<String, FilyError<ParseError>> {
+        let path = path.as_ref();
+
+        let audio_tags = match audio_tags {
+            Some(audio_tags) => audio_tags,
+            None => audio_tags.insert(AudioTags::read(path)
+                .map_err(|e| {
+                    let (err, context) = e.destructure();
+                    FilyError::new(ParseError::AudioTagsError(err), context)
+                })?),
+        };
+
+        let value = match variable {
+            AudioTagVariable::Artist => audio_tags.artist.clone(),
+            AudioTagVariable::Album => audio_tags.album.clone(),
+            AudioTagVariable::Title => audio_tags.title.clone(),
+            AudioTagVariable::Year => audio_tags.year.map(|year| year.to_string()),
+            AudioTagVariable::Genre => audio_tags.genre.clone(),
+            AudioTagVariable::Duration => audio_tags.duration.map(|duration| duration.as_secs().to_string()),
+        };
+
+        let value = value
+            .ok_or_else(|| FilyError::new_with_context(ParseError::MissingAudioTag, || format!("{:?} doesn't have a {:?} tag", path.display(), variable)))?;
+
+        Ok(replace_separators(&value))
+    }
+
+    /// Produces a string from a variable that is read from the EXIF tags of a photo
+    ///
+    /// `exif_tags` is used as a cache and filled with the tags of the file if it is `None`
+    #[cfg(feature = "exif_tags")]
+    fn parse_exif_variable(variable: ExifVariable, path: impl AsRef<Path>, exif_tags: &mut Option<ExifTags>) -> Result<String, FilyError<ParseError>> {
+        let path = path.as_ref();
+        let exif_tags = cached_exif_tags(path, exif_tags)?;
+
+        let value = match variable {
+            ExifVariable::CameraMake => exif_tags.make.as_deref(),
+            ExifVariable::CameraModel => exif_tags.model.as_deref(),
+            ExifVariable::Lens => exif_tags.lens_model.as_deref(),
+        };
+
+        let value = value
+            .ok_or_else(|| FilyError::new_with_context(ParseError::MissingExifTag, || format!("{:?} doesn't have a {:?} tag", path.display(), variable)))?;
+
+        Ok(replace_separators(value))
+    }
+
+    /// Produces a string from a variable that is read from the content of an image
+    ///
+    /// `image_dimensions` is used as a cache and filled with the width and height of the image if it is `None`
+    #[cfg(feature = "check_image_formats")]
+    fn parse_image_variable(variable: ImageVariable, path: impl AsRef<Path>, image_dimensions: &mut Option<(u32, u32)>) -> Result<String, FilyError<ParseError>> {
+        let path = path.as_ref();
+
+        if variable == ImageVariable::ActualExtension {
+            let format = image_format_guess_from_content(path)
+                .map_err(|e| {
+                    let (err, context) = e.destructure();
+                    FilyError::new(ParseError::IOError(err), context)
+                })?;
+
+            return Ok(format.extensions_str()[0].to_string());
+        }
+
+        let (width, height) = match image_dimensions {
+            Some(image_dimensions) => *image_dimensions,
+            // The format is guessed from the content because the extension can be wrong
+            None => *image_dimensions.insert(image::io::Reader::open(path)
+                .and_then(image::io::Reader::with_guessed_format)
+                .map_err(image::ImageError::IoError)
+                .and_then(image::io::Reader::into_dimensions)
+                .map_err(|e| FilyError::new_with_context(ParseError::ImageError(e), || format!("Failed to get the width and height of {:?}", path.display())))?),
+        };
+
+        Ok(if variable == ImageVariable::Width { width } else { height }.to_string())
+    }
+}
+
+/// Returns the EXIF tags of `path`, which are only read if `exif_tags` is `None`
+#[cfg(feature = "exif_tags")]
+fn cached_exif_tags<'e>(path: &Path, exif_tags: &'e mut Option<ExifTags>) -> Result<&'e ExifTags, FilyError<ParseError>> {
+    match exif_tags {
+        Some(exif_tags) => Ok(exif_tags),
+        None => Ok(exif_tags.insert(ExifTags::read(path)
+            .map_err(|e| {
+                let (err, context) = e.destructure();
+                FilyError::new(ParseError::ExifTagsError(err), context)
+            })?)),
+    }
+}
+
+/// Returns the metadata of `path`, which is only read if `metadata` is `None`
+fn cached_metadata<'m>(path: &Path, metadata: &'m mut Option<Metadata>) -> Result<&'m Metadata, FilyError<ParseError>> {
+    match metadata {
+        Some(metadata) => Ok(metadata),
+        None => Ok(metadata.insert(path.metadata()
+            .map_err(|e| FilyError::new_with_context(ParseError::IOError(e), || format!("Failed to get metadata of {:?}", path.display())))?)),
+    }
+}
+
+/// Converts `file_time` to a date. Returns `None` if it is out of range
+fn date_time(file_time: FileTime) -> Option<DateTime<Utc>> {
+    Utc.timestamp_opt(file_time.unix_seconds(), file_time.nanoseconds()).single()
+}
+
+/// Writes `num` in `format`
+///
+/// Returns `None` if `num` can't be written as a roman numeral
+fn format_number(num: isize, format: NumberFormat) -> Option<String> {
+    let digits = match format.base {
+        NumberBase::Decimal => num.unsigned_abs().to_string(),
+        NumberBase::Hex => format!("{:x}", num.unsigned_abs()),
+        NumberBase::Roman if num > 0 => roman_numeral(num.unsigned_abs()),
+        NumberBase::Roman => return None,
+    };
+
+    let sign = if num < 0 { "-" } else { "" };
+    let padding_length = format.width.saturating_sub(sign.len() + digits.len());
+
+    Some(if format.zero_padded {
+        format!("{}{}{}", sign, "0".repeat(padding_length), digits)
+    } else {
+        format!("{}{}{}", " ".repeat(padding_length), sign, digits)
+    })
+}
+
+/// Writes `num` as uppercase roman numerals. Numbers above 3999 start with more than three `M`
+fn roman_numeral(mut num: usize) -> String {
+    const NUMERALS: &[(usize, &str)] = &[(1000, "M"), (900, "CM"), (500, "D"), (400, "CD"), (100, "C"), (90, "XC"), (50, "L"), (40, "XL"), (10, "X"), (9, "IX"), (5, "V"), (4, "IV"), (1, "I")];
+
+    let mut roman = String::new();
+
+    for &(value, numeral) in NUMERALS {
+        while num >= value {
+            roman.push_str(numeral);
+            num -= value;
+        }
+    }
+
+    roman
+}
+
+/// Returns `value` changed by `filter`
+fn apply_filter(value: &str, filter: Filter<'_>) -> String {
+    match filter {
+        Filter::Lower => value.to_lowercase(),
+        Filter::Upper => value.to_uppercase(),
+        Filter::Replace(from, to) => value.replace(from, to),
+        Filter::Slice(start, end) => value.chars()
+            .take(end.unwrap_or(usize::MAX))
+            .skip(start)
+            .collect(),
+        Filter::Trim => value.trim().to_string(),
+        Filter::Title => {
+            let mut title = String::with_capacity(value.len());
+            let mut is_word_start = true;
+
+            for c in value.chars() {
+                if is_word_start {
+                    title.extend(c.to_uppercase());
+                } else {
+                    title.extend(c.to_lowercase());
+                }
+
+                is_word_start = c.is_whitespace() || c == '_' || c == '-' || c == '.';
+            }
+
+            title
+        }
+        Filter::Slug => value.to_lowercase()
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .collect::<Vec<&str>>()
+            .join("-"),
+    }
+}
+
+/// Replaces path separators and null bytes with `_`
+///
+/// Tags and captures of a path can contain anything, including characters that would turn the filename into a path
+fn replace_separators(value: &str) -> String {
+    value.chars()
+        .map(|c| if std::path::is_separator(c) || c == '\0' { '_' } else { c })
+        .collect()
 }
 
 /// Used to build a `Parser`
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone)]
 pub struct ParserBuilder {
     parser: Parser,
 }
@@ -119,6 +487,12 @@ impl ParserBuilder {
         ParserBuilder {
             parser: Parser {
                 incrementing_number: 0,
+                incrementing_number_step: 1,
+                incrementing_numbers_per_folder: None,
+                match_regex: None,
+                match_against: MatchTarget::Filename,
+                timezone: Timezone::Local,
+                now: Utc::now(),
             }
         }
     }
@@ -132,9 +506,54 @@ impl ParserBuilder {
         self
     }
 
+    /// Sets how much the incrementing number changes after every use. Can be negative
+    ///
+    /// Default is 1
+    #[inline]
+    pub fn incrementing_number_step(&mut self, step: isize) -> &mut Self {
+        self.parser.incrementing_number_step = step;
+        self
+    }
+
+    /// Gives every folder its own incrementing number, which starts at the number that was set with `incrementing_number`
+    ///
+    /// Default is `false`, which means all files share one
+    #[inline]
+    pub fn incrementing_number_per_folder(&mut self, per_folder: bool) -> &mut Self {
+        self.parser.incrementing_numbers_per_folder = if per_folder { Some(HashMap::new()) } else { None };
+        self
+    }
+
+    /// Sets the regex every file has to match. Its captures can be used as variables
+    ///
+    /// Default is `None`, which means every file is renamed
+    #[inline]
+    pub fn match_regex(&mut self, match_regex: Option<Regex>) -> &mut Self {
+        self.parser.match_regex = match_regex;
+        self
+    }
+
+    /// Sets whether the regex is matched against the filename or the whole path
+    ///
+    /// Default is `MatchTarget::Filename`
+    #[inline]
+    pub fn match_against(&mut self, match_against: MatchTarget) -> &mut Self {
+        self.parser.match_against = match_against;
+        self
+    }
+
+    /// Sets the timezone date variables are written in
+    ///
+    /// Default is `Timezone::Local`
+    #[inline]
+    pub fn timezone(&mut self, timezone: Timezone) -> &mut Self {
+        self.parser.timezone = timezone;
+        self
+    }
+
     /// Builds and returns the resulting `Parser`
     #[inline]
-    pub fn build(self) -> Parser {
-        self.parser
+    pub fn build(&self) -> Parser {
+        self.parser.clone()
     }
 }
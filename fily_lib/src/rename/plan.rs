@@ -0,0 +1,349 @@
+use std::{path::{Path, PathBuf}, collections::{HashMap, HashSet}};
+use super::{RenameFilesError, tokenizer::{FilenamePart, FilenameOptions, NoMatchPolicy, NumberingOrder}, parser::{Parser, ParseError}};
+use crate::fily_err::FilyError;
+#[allow(unused_imports)]
+use log::{trace, debug, info, warn, error};
+
+/// Something that is wrong with a planned rename
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum RenameProblem {
+    /// The new name is the same as the old one
+    Unchanged,
+
+    /// Another file of the plan gets the same new path
+    Collision,
+
+    /// There already is a file at the new path that isn't renamed itself
+    TargetExists,
+
+    /// The new name is empty, `.` or `..` or contains a path separator or a null byte
+    InvalidCharacters,
+}
+
+/// What happens to a rename whose new path is already taken by an existing file or another rename
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum ConflictPolicy {
+    /// Nothing is renamed if at least one rename has a conflict
+    #[default]
+    Abort,
+
+    /// Renames with a conflict are left out
+    Skip,
+
+    /// `_1`, `_2` and so on is added to the new name in front of the extension until it isn't taken
+    Suffix,
+
+    /// An existing file at the new path is replaced. Renames whose new path another rename already gets are left out,
+    /// since that would replace a file that is renamed itself
+    Overwrite,
+}
+
+/// The new path of a single file
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct PlannedRename {
+    pub old_path: PathBuf,
+    pub new_path: PathBuf,
+
+    /// Everything that is wrong with this rename. Empty if it can be done safely
+    pub problems: Vec<RenameProblem>,
+}
+
+impl PlannedRename {
+    /// Returns `false` if the file stays where it is because the name doesn't change or the new one is invalid
+    fn moves(&self) -> bool {
+        !self.problems.iter().any(|&problem| problem == RenameProblem::Unchanged || problem == RenameProblem::InvalidCharacters)
+    }
+}
+
+/// Every rename a template would do, computed without touching the filesystem
+#[derive(Debug, Default)]
+pub struct RenamePlan {
+    /// The renames in the same order as the files that were passed in
+    pub renames: Vec<PlannedRename>,
+
+    /// Files for which no new name could be produced, i.e. because they don't have a tag a variable needs
+    pub skipped: Vec<(PathBuf, FilyError<ParseError>)>,
+
+    /// Renames that are left out because of their problems. Filled by `RenamePlan::resolve_conflicts`
+    pub conflicts: Vec<PlannedRename>,
+}
+
+impl RenamePlan {
+    /// Returns `true` if at least one rename has a problem other than `RenameProblem::Unchanged`
+    #[must_use]
+    pub fn has_problems(&self) -> bool {
+        self.renames.iter()
+            .flat_map(|planned_rename| &planned_rename.problems)
+            .any(|&problem| problem != RenameProblem::Unchanged)
+    }
+
+    /// Changes the plan so that it can be done without a rename replacing a file it shouldn't
+    ///
+    /// The renames are gone through in order and the first one that wants a new path gets it. A path that belongs to a file
+    /// which is renamed itself is free. After that every rename in `renames` can be done and only has the problems that are
+    /// left, which is `RenameProblem::Unchanged` or `RenameProblem::TargetExists` with `ConflictPolicy::Overwrite`. Renames
+    /// with invalid characters and renames that were skipped because of a conflict are moved to `conflicts`
+    ///
+    /// # Errors
+    ///
+    /// With `ConflictPolicy::Abort` this fails if there is a conflict or a new name has invalid characters. The error
+    /// contains every rename that has one of these problems
+    pub fn resolve_conflicts(mut self, conflict_policy: ConflictPolicy) -> Result<Self, RenameFilesError> {
+        trace!("RenamePlan::resolve_conflicts conflict_policy: {:?}", conflict_policy);
+
+        let planned_renames = std::mem::take(&mut self.renames);
+
+        // A rename that is left out keeps its file where it is, which can take away the path another rename needs.
+        // So this is repeated until no more renames are left out
+        let mut staying_paths = HashSet::new();
+
+        let (renames, conflicts) = loop {
+            let (renames, conflicts) = resolve_conflicts_once(planned_renames.clone(), conflict_policy, &staying_paths);
+
+            let staying_paths_before = staying_paths.len();
+            staying_paths.extend(conflicts.iter().map(|conflict| conflict.old_path.clone()));
+
+            if staying_paths.len() == staying_paths_before {
+                break (renames, conflicts);
+            }
+        };
+
+        self.conflicts.extend(conflicts);
+
+        if conflict_policy == ConflictPolicy::Abort && !self.conflicts.is_empty() {
+            return Err(RenameFilesError::Conflicts(self.conflicts));
+        }
+
+        self.renames = renames;
+
+        Ok(self)
+    }
+}
+
+/// Returns the renames that can be done and the ones that are left out
+///
+/// `staying_paths` are the old paths of renames that are left out. Every other file that is renamed frees its path
+fn resolve_conflicts_once(planned_renames: Vec<PlannedRename>, conflict_policy: ConflictPolicy, staying_paths: &HashSet<PathBuf>) -> (Vec<PlannedRename>, Vec<PlannedRename>) {
+    let moving_paths: HashSet<PathBuf> = planned_renames.iter()
+        .filter(|planned_rename| planned_rename.moves())
+        .map(|planned_rename| planned_rename.old_path.clone())
+        .filter(|old_path| !staying_paths.contains(old_path))
+        .collect();
+
+    let mut taken_paths = HashSet::new();
+    let mut renames = Vec::new();
+    let mut conflicts = Vec::new();
+
+    for mut planned_rename in planned_renames {
+        if planned_rename.problems.contains(&RenameProblem::InvalidCharacters) {
+            conflicts.push(planned_rename);
+            continue;
+        }
+
+        if planned_rename.problems.contains(&RenameProblem::Unchanged) {
+            taken_paths.insert(planned_rename.new_path.clone());
+            renames.push(planned_rename);
+            continue;
+        }
+
+        let is_taken = |path: &Path| taken_paths.contains(path) || (path.symlink_metadata().is_ok() && !moving_paths.contains(path));
+
+        if is_taken(&planned_rename.new_path) {
+            match conflict_policy {
+                ConflictPolicy::Abort | ConflictPolicy::Skip => {
+                    conflicts.push(planned_rename);
+                    continue;
+                }
+                ConflictPolicy::Suffix => {
+                    let mut number = 1;
+                    let mut new_path = path_with_suffix(&planned_rename.new_path, number);
+
+                    while is_taken(&new_path) {
+                        number += 1;
+                        new_path = path_with_suffix(&planned_rename.new_path, number);
+                    }
+
+                    planned_rename.new_path = new_path;
+                    planned_rename.problems.clear();
+                }
+                ConflictPolicy::Overwrite => {
+                    // Only files that aren't renamed are replaced, the renames after the first one that gets a path are left out
+                    if taken_paths.contains(&planned_rename.new_path) {
+                        conflicts.push(planned_rename);
+                        continue;
+                    }
+
+                    planned_rename.problems.retain(|&problem| problem != RenameProblem::Collision);
+                }
+            }
+        } else {
+            planned_rename.problems.clear();
+        }
+
+        taken_paths.insert(planned_rename.new_path.clone());
+        renames.push(planned_rename);
+    }
+
+    (renames, conflicts)
+}
+
+/// Adds `_<number>` to the filename of `path` in front of the extension
+fn path_with_suffix(path: &Path, number: usize) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+
+    match path.extension() {
+        Some(extension) => path.with_file_name(format!("{}_{}.{}", stem, number, extension.to_string_lossy())),
+        None => path.with_file_name(format!("{}_{}", stem, number)),
+    }
+}
+
+/// Returns the indices of `files` in the order they get their incrementing number in
+///
+/// Files whose modification time or size can't be read come first
+fn numbering_order(files: &[&Path], numbering_order: NumberingOrder) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..files.len()).collect();
+
+    match numbering_order {
+        NumberingOrder::Input => (),
+        NumberingOrder::Name => order.sort_by_key(|&i| (files[i].file_name(), files[i])),
+        NumberingOrder::Modified => {
+            let modified: Vec<_> = files.iter().map(|path| path.metadata().and_then(|metadata| metadata.modified()).ok()).collect();
+            order.sort_by_key(|&i| modified[i]);
+        }
+        NumberingOrder::Size => {
+            let sizes: Vec<_> = files.iter().map(|path| path.metadata().map(|metadata| metadata.len()).ok()).collect();
+            order.sort_by_key(|&i| sizes[i]);
+        }
+    }
+
+    order
+}
+
+/// Returns the position of the `|` that separates the template from its options
+///
+/// A `|` inside of a variable is part of it, like in `{filename:replace("|","_")}`, as is one in quotes there
+fn options_start(template: &str) -> Option<usize> {
+    let mut in_variable = false;
+    let mut quote = None;
+
+    for (i, c) in template.char_indices() {
+        match (in_variable, quote, c) {
+            (true, Some(quote_char), _) if c == quote_char => quote = None,
+            (true, None, '"' | '\'') => quote = Some(c),
+            (true, None, '}') => in_variable = false,
+            (false, _, '{') => in_variable = true,
+            (false, _, '|') => return Some(i),
+            _ => (),
+        }
+    }
+
+    None
+}
+
+/// Computes the new path of every file without renaming anything
+///
+/// The template is the same as for `rename_files`. The only thing that is read from the filesystem is
+/// the information variables need and whether a file already exists at a new path
+///
+/// # Errors
+///
+/// This fails if either the template or the options for renaming have an error
+pub fn plan_renames<P: AsRef<Path>>(files_to_rename: &[P], new_filename_template: &str) -> Result<RenamePlan, RenameFilesError> {
+    let files_to_rename: Vec<&Path> = files_to_rename.iter().map(AsRef::as_ref).collect();
+
+    trace!("plan_renames files_to_rename: {:?} new_filename_template: {}", files_to_rename, new_filename_template);
+
+    let (text_template, text_options) = match options_start(new_filename_template) {
+        Some(i) => (&new_filename_template[..i], Some(&new_filename_template[i + 1..])),
+        None => (new_filename_template, None),
+    };
+
+    // The options come first because the captures of `match` are variables
+    let options = if let Some(text_options) = text_options {
+        FilenameOptions::new(text_options, text_template.len() + 1)?
+    } else {
+        FilenameOptions::default()
+    };
+
+    let filename_template = FilenamePart::from_text(text_template, options.match_regex.as_ref())?;
+
+    let mut parser = Parser::builder()
+        .incrementing_number(options.incrementing_number_starts_at)
+        .incrementing_number_step(options.incrementing_number_step)
+        .incrementing_number_per_folder(options.incrementing_number_per_folder)
+        .match_regex(options.match_regex.clone())
+        .match_against(options.match_against)
+        .timezone(options.timezone)
+        .build();
+
+    // The files are parsed in the order they get their incrementing number in but the plan keeps the input order
+    let mut parsed_filenames: Vec<_> = numbering_order(&files_to_rename, options.incrementing_number_order)
+        .into_iter()
+        .map(|i| (i, files_to_rename[i], parser.parse_filename(&filename_template, files_to_rename[i])))
+        .collect();
+
+    parsed_filenames.sort_by_key(|&(i, _, _)| i);
+
+    let mut plan = RenamePlan::default();
+
+    for (_, path, filename_new) in parsed_filenames {
+        let filename_new = match filename_new {
+            Ok(filename) => filename,
+            Err(e) if options.no_match_policy == NoMatchPolicy::Abort && matches!(e.get_error(), ParseError::NoMatch) => return Err(e.into()),
+            Err(e) => {
+                plan.skipped.push((path.to_path_buf(), e));
+                continue;
+            }
+        };
+
+        let mut problems = Vec::new();
+
+        if filename_new.is_empty() || filename_new == "." || filename_new == ".." || filename_new.chars().any(|c| std::path::is_separator(c) || c == '\0') {
+            problems.push(RenameProblem::InvalidCharacters);
+        }
+
+        let new_path = path.with_file_name(filename_new.as_str());
+
+        if new_path == path {
+            problems.push(RenameProblem::Unchanged);
+        } else if new_path.symlink_metadata().is_ok() {
+            problems.push(RenameProblem::TargetExists);
+        }
+
+        plan.renames.push(PlannedRename {
+            old_path: path.to_path_buf(),
+            new_path,
+            problems,
+        });
+    }
+
+    // A file that already exists at a new path isn't in the way if it is renamed too
+    let moving_paths: HashSet<PathBuf> = plan.renames.iter()
+        .filter(|planned_rename| planned_rename.moves())
+        .map(|planned_rename| planned_rename.old_path.clone())
+        .collect();
+
+    for planned_rename in &mut plan.renames {
+        if moving_paths.contains(&planned_rename.new_path) {
+            planned_rename.problems.retain(|&problem| problem != RenameProblem::TargetExists);
+        }
+    }
+
+    let mut number_of_renames_to: HashMap<&Path, usize> = HashMap::new();
+
+    for planned_rename in &plan.renames {
+        *number_of_renames_to.entry(&planned_rename.new_path).or_default() += 1;
+    }
+
+    let colliding: Vec<bool> = plan.renames.iter()
+        .map(|planned_rename| number_of_renames_to[planned_rename.new_path.as_path()] > 1)
+        .collect();
+
+    for (planned_rename, colliding) in plan.renames.iter_mut().zip(colliding) {
+        if colliding {
+            planned_rename.problems.push(RenameProblem::Collision);
+        }
+    }
+
+    Ok(plan)
+}
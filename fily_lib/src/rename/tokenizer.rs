@@ -1,26 +1,74 @@
-use std::{fmt, error::Error};
-use logos::Logos;
+use std::{ops::Range, fmt, error::Error};
+use regex::Regex;
+use chrono::format::{StrftimeItems, Item};
 use super::RenameFilesError;
 use crate::fily_err::FilyError;
 #[allow(unused_imports)]
 use log::{trace, debug, info, warn, error};
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+/// An error in the text part of a template
+///
+/// Every variant contains the byte range of the template the error is at
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub enum TokenizeError {
-    // TODO: Remove the UnknownError variant once we can return the actual error
+    /// A `{` is never closed. Goes from the `{` to the end of the template
+    UnclosedBrace(Range<usize>),
+
+    /// A `}` that doesn't close a variable
+    StrayClosingBrace(Range<usize>),
+
+    /// A `{` inside of a variable. Variables can't be nested
+    NestedBrace(Range<usize>),
+
+    /// A variable without a name, `{}`
+    EmptyVariable(Range<usize>),
+
+    /// An unknown variable was found. Contains the known variable with the most similar name if there is one
+    UnknownVariable(Range<usize>, Option<&'static str>),
+
+    /// An unknown filter was found. Contains the known filter with the most similar name if there is one
+    UnknownFilter(Range<usize>, Option<&'static str>),
+
+    /// A filter has the wrong number of arguments, an argument that can't be parsed or a quote that is never closed
+    MalformedFilter(Range<usize>),
 
-    /// Something went wrong during tokenizing but we don't know what because that one library I use doesn't support returning errors containing enums and it's really annoying so this is a placeholder until it gets implemented. Good luck figuring out whats wrong with you input
-    UnknownError,
+    /// The format of a variable, like the `04` in `{incrementing_number:04}`, can't be parsed
+    MalformedFormat(Range<usize>),
+}
 
-    /// An unknown variable was found
-    UnknownVariable,
+impl TokenizeError {
+    /// Returns the byte range of the template the error is at
+    #[must_use]
+    pub fn span(&self) -> Range<usize> {
+        match self {
+            TokenizeError::UnclosedBrace(span)
+            | TokenizeError::StrayClosingBrace(span)
+            | TokenizeError::NestedBrace(span)
+            | TokenizeError::EmptyVariable(span)
+            | TokenizeError::UnknownVariable(span, _)
+            | TokenizeError::UnknownFilter(span, _)
+            | TokenizeError::MalformedFilter(span)
+            | TokenizeError::MalformedFormat(span) => span.clone(),
+        }
+    }
 }
 
 impl Error for TokenizeError {}
 
 impl fmt::Display for TokenizeError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{:?}", self)
+        match self {
+            TokenizeError::UnclosedBrace(_) => write!(f, "this `{{` is never closed"),
+            TokenizeError::StrayClosingBrace(_) => write!(f, "this `}}` doesn't close a variable"),
+            TokenizeError::NestedBrace(_) => write!(f, "variables can't contain a `{{`"),
+            TokenizeError::EmptyVariable(_) => write!(f, "a variable needs a name"),
+            TokenizeError::UnknownVariable(_, Some(suggestion)) => write!(f, "unknown variable, did you mean {:?}?", suggestion),
+            TokenizeError::UnknownVariable(_, None) => write!(f, "unknown variable"),
+            TokenizeError::UnknownFilter(_, Some(suggestion)) => write!(f, "unknown filter, did you mean {:?}?", suggestion),
+            TokenizeError::UnknownFilter(_, None) => write!(f, "unknown filter"),
+            TokenizeError::MalformedFilter(_) => write!(f, "this filter has the wrong arguments"),
+            TokenizeError::MalformedFormat(_) => write!(f, "this isn't a valid format for the variable"),
+        }
     }
 }
 
@@ -30,83 +78,489 @@ impl From<FilyError<TokenizeError>> for RenameFilesError {
     }
 }
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq, Logos)]
-pub enum FilenameToken<'a> {
-    #[regex("[^{}]+")]
-    Constant(&'a str),
-    #[regex(r"\{[^{}]*\}", |var| { let var = var.slice(); &var[1..var.len() - 1] })]
-    Variable(&'a str),
-    #[error]
-    Error,
-}
-
-impl<'a> FilenameToken<'a> {
-    pub fn tokenize(text: &'a str) -> Vec<Self> {
-        FilenameToken::lexer(text).spanned().map(|(part, _)| part).collect()
-    }
-}
-
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub enum FilenamePart<'a> {
     Constant(&'a str),
-    Variable(FilenameVariable),
+
+    /// A variable and the filters that are applied to its value in order
+    Variable(FilenameVariable<'a>, Vec<Filter<'a>>),
 }
 
 impl<'a> FilenamePart<'a> {
-    pub fn from_text(text: &'a str) -> Result<Vec<Self>, FilyError<TokenizeError>> {
-        let tokens = FilenameToken::tokenize(text);
+    /// Splits the text part of a template into constants and variables
+    ///
+    /// The captures of `match_regex` can be used as variables, by their number or their name
+    ///
+    /// # Errors
+    ///
+    /// Fails if a brace doesn't belong to a variable or a variable or filter is unknown
+    pub fn from_text(text: &'a str, match_regex: Option<&Regex>) -> Result<Vec<Self>, FilyError<TokenizeError>> {
+        trace!("FilenamePart::from_text text: {} match_regex: {:?}", text, match_regex);
 
-        if tokens.contains(&FilenameToken::Error) {
-            return Err(FilyError::new(TokenizeError::UnknownError, "Something went wrong during tokenizing"));
-        }
+        let mut parts = Vec::new();
+        let mut position = 0;
+
+        while position < text.len() {
+            let rest = &text[position..];
+
+            match rest.find(['{', '}']) {
+                None => {
+                    parts.push(FilenamePart::Constant(rest));
+                    break;
+                }
+                Some(0) => (),
+                Some(length) => {
+                    parts.push(FilenamePart::Constant(&rest[..length]));
+                    position += length;
+                    continue;
+                }
+            }
+
+            if rest.starts_with('}') {
+                return Err(FilyError::new_with_context(TokenizeError::StrayClosingBrace(position..position + 1), || format!("Found a `}}` without a `{{` at {} in {:?}", position, text)));
+            }
+
+            let name_start = position + 1;
+
+            let name_length = text[name_start..]
+                .find(['{', '}'])
+                .ok_or_else(|| FilyError::new_with_context(TokenizeError::UnclosedBrace(position..text.len()), || format!("The `{{` at {} in {:?} is never closed", position, text)))?;
+
+            let name_end = name_start + name_length;
+
+            if text[name_end..].starts_with('{') {
+                return Err(FilyError::new_with_context(TokenizeError::NestedBrace(name_end..name_end + 1), || format!("Found a `{{` inside of a variable at {} in {:?}", name_end, text)));
+            }
+
+            if name_length == 0 {
+                return Err(FilyError::new_with_context(TokenizeError::EmptyVariable(position..name_end + 1), || format!("Found a variable without a name at {} in {:?}", position, text)));
+            }
 
-        let result: Result<Vec<FilenamePart<'a>>, FilyError<TokenizeError>> = tokens.into_iter()
-            .map(|token| Ok(match token {
-                FilenameToken::Constant(string) => FilenamePart::Constant(string),
-                FilenameToken::Variable(var) => FilenamePart::Variable(FilenameVariable::from_text(var)?),
-                FilenameToken::Error => unreachable!("Error variant in vec after checking for it"),
-            })).collect();
+            // Everything after the first `:` are filters
+            let variable_text = &text[name_start..name_end];
+
+            let (name, mut filter_texts) = match variable_text.find(':') {
+                Some(i) => (&variable_text[..i], split_outside_quotes(&variable_text[i + 1..], ':', name_start + i + 1)?),
+                None => (variable_text, Vec::new()),
+            };
+
+            let mut variable = FilenameVariable::from_text(name, name_start, match_regex)?;
+
+            // Unless it is a filter, the first part is the format of a variable that has one
+            if let Some(&(format_start, format)) = filter_texts.first().filter(|&&(_, format)| variable.has_format() && !is_filter(format)) {
+                variable = variable.with_format(format, format_start)?;
+                filter_texts.remove(0);
+            }
+
+            let filters = filter_texts.into_iter()
+                .map(|(filter_start, filter)| Filter::from_text(filter, filter_start))
+                .collect::<Result<_, _>>()?;
+
+            parts.push(FilenamePart::Variable(variable, filters));
+            position = name_end + 1;
+        }
 
-        result
+        Ok(parts)
     }
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
-pub enum FilenameVariable {
+pub enum FilenameVariable<'a> {
     Filename,
     FilenameExtension,
     FilenameBase,
     FileSize,
-    IncrementingNumber,
-}
-
-impl FilenameVariable {
-    pub fn from_text(var: &str) -> Result<Self, FilyError<TokenizeError>> {
-        Ok(match var {
-            "filename" => FilenameVariable::Filename,
-            "filename_extension" => FilenameVariable::FilenameExtension,
-            "filename_base" => FilenameVariable::FilenameBase,
-            "filesize" => FilenameVariable::FileSize,
-            "incrementing_number" => FilenameVariable::IncrementingNumber,
-            _ => return Err(FilyError::new_with_context(TokenizeError::UnknownVariable, || format!("Unknown variable {:?}", var))),
+    IncrementingNumber(NumberFormat),
+
+    /// A point in time written with a strftime format, like `%Y-%m-%d`
+    Date(DateVariable, &'a str),
+
+    /// A capture of the `match` option. Contains the number of the capture group, 0 is the whole match
+    Capture(usize),
+    #[cfg(feature = "audio_tags")]
+    AudioTag(AudioTagVariable),
+    #[cfg(feature = "exif_tags")]
+    Exif(ExifVariable),
+    #[cfg(feature = "check_image_formats")]
+    Image(ImageVariable),
+}
+
+/// How `incrementing_number` is written
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct NumberFormat {
+    /// The minimum number of characters
+    pub width: usize,
+
+    /// Fills up the width with `0` after the sign instead of spaces in front of the number
+    pub zero_padded: bool,
+    pub base: NumberBase,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum NumberBase {
+    Decimal,
+
+    /// Lowercase hexadecimal
+    Hex,
+
+    /// Uppercase roman numerals. Only works for numbers above 0
+    Roman,
+}
+
+impl NumberFormat {
+    /// Parses a format like `04`, `x`, `3roman` or `08x`
+    ///
+    /// The optional width comes first, with a leading `0` for zero padding. It is followed by the optional base, `x` for
+    /// hexadecimal or `roman` for roman numerals. Without a base the number is decimal
+    fn from_text(text: &str) -> Option<Self> {
+        let width_length = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+        let (width, base) = text.split_at(width_length);
+
+        let base = match base {
+            "" => NumberBase::Decimal,
+            "x" => NumberBase::Hex,
+            "roman" => NumberBase::Roman,
+            _ => return None,
+        };
+
+        Some(NumberFormat {
+            width: if width.is_empty() { 0 } else { width.parse().ok()? },
+            zero_padded: width.starts_with('0'),
+            base,
         })
     }
 }
 
+/// Points in time a date variable can be
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum DateVariable {
+    /// When the file was last modified
+    Modified,
+
+    /// When the file was created. Not every platform and filesystem has this
+    Created,
+
+    /// When the file was last accessed
+    Accessed,
+
+    /// When the renaming started. The same for every file
+    Now,
+
+    /// When the photo was taken, read from its EXIF tags. The timezone only applies if the photo has the offset of the time
+    #[cfg(feature = "exif_tags")]
+    ExifDate,
+}
+
+/// The format date variables have if they don't get one
+const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// Variables that are read from the tags of audio files
+#[cfg(feature = "audio_tags")]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum AudioTagVariable {
+    Artist,
+    Album,
+    Title,
+    Year,
+    Genre,
+    Duration,
+}
+
+/// Variables that are read from the EXIF tags of photos
+#[cfg(feature = "exif_tags")]
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ExifVariable {
+    CameraMake,
+    CameraModel,
+    Lens,
+}
+
+/// Variables that are read from the content of images
+#[cfg(feature = "check_image_formats")]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ImageVariable {
+    Width,
+    Height,
+
+    /// The extension of the format the image actually has, no matter what its extension is
+    ActualExtension,
+}
+
+/// Every variable together with its name in a template
+const VARIABLES: &[(&str, FilenameVariable<'static>)] = &[
+    ("filename", FilenameVariable::Filename),
+    ("filename_extension", FilenameVariable::FilenameExtension),
+    ("filename_base", FilenameVariable::FilenameBase),
+    ("filesize", FilenameVariable::FileSize),
+    ("incrementing_number", FilenameVariable::IncrementingNumber(NumberFormat { width: 0, zero_padded: false, base: NumberBase::Decimal })),
+    ("modified", FilenameVariable::Date(DateVariable::Modified, DEFAULT_DATE_FORMAT)),
+    ("created", FilenameVariable::Date(DateVariable::Created, DEFAULT_DATE_FORMAT)),
+    ("accessed", FilenameVariable::Date(DateVariable::Accessed, DEFAULT_DATE_FORMAT)),
+    ("now", FilenameVariable::Date(DateVariable::Now, DEFAULT_DATE_FORMAT)),
+    #[cfg(feature = "audio_tags")]
+    ("artist", FilenameVariable::AudioTag(AudioTagVariable::Artist)),
+    #[cfg(feature = "audio_tags")]
+    ("album", FilenameVariable::AudioTag(AudioTagVariable::Album)),
+    #[cfg(feature = "audio_tags")]
+    ("title", FilenameVariable::AudioTag(AudioTagVariable::Title)),
+    #[cfg(feature = "audio_tags")]
+    ("year", FilenameVariable::AudioTag(AudioTagVariable::Year)),
+    #[cfg(feature = "audio_tags")]
+    ("genre", FilenameVariable::AudioTag(AudioTagVariable::Genre)),
+    #[cfg(feature = "audio_tags")]
+    ("duration", FilenameVariable::AudioTag(AudioTagVariable::Duration)),
+    #[cfg(feature = "exif_tags")]
+    ("exif_date", FilenameVariable::Date(DateVariable::ExifDate, DEFAULT_DATE_FORMAT)),
+    #[cfg(feature = "exif_tags")]
+    ("camera_make", FilenameVariable::Exif(ExifVariable::CameraMake)),
+    #[cfg(feature = "exif_tags")]
+    ("camera_model", FilenameVariable::Exif(ExifVariable::CameraModel)),
+    #[cfg(feature = "exif_tags")]
+    ("lens", FilenameVariable::Exif(ExifVariable::Lens)),
+    #[cfg(feature = "check_image_formats")]
+    ("image_width", FilenameVariable::Image(ImageVariable::Width)),
+    #[cfg(feature = "check_image_formats")]
+    ("image_height", FilenameVariable::Image(ImageVariable::Height)),
+    #[cfg(feature = "check_image_formats")]
+    ("actual_extension", FilenameVariable::Image(ImageVariable::ActualExtension)),
+];
+
+impl<'a> FilenameVariable<'a> {
+    /// Returns the variable with the name `var`
+    ///
+    /// If there is no variable with that name but `match_regex` has a capture group with it as its number or name, a
+    /// `FilenameVariable::Capture` of that group is returned
+    ///
+    /// `offset` is the byte position of `var` in the template and is used for the span of the error
+    ///
+    /// # Errors
+    ///
+    /// Fails if there is no variable or capture group with that name
+    pub fn from_text(var: &str, offset: usize, match_regex: Option<&Regex>) -> Result<Self, FilyError<TokenizeError>> {
+        if let Some(&(_, variable)) = VARIABLES.iter().find(|(name, _)| *name == var) {
+            return Ok(variable);
+        }
+
+        let capture_index = match_regex.and_then(|match_regex| match var.parse::<usize>() {
+            Ok(index) => Some(index).filter(|&index| index < match_regex.captures_len()),
+            Err(_) => match_regex.capture_names().position(|name| name == Some(var)),
+        });
+
+        capture_index
+            .map(FilenameVariable::Capture)
+            .ok_or_else(|| {
+                let suggestion = closest_name(var, VARIABLES.iter().map(|&(name, _)| name));
+                FilyError::new_with_context(TokenizeError::UnknownVariable(offset..offset + var.len(), suggestion), || format!("Unknown variable {:?}", var))
+            })
+    }
+
+    /// Returns `true` if the variable can have a format, like `{incrementing_number:04}` or `{modified:%Y}`
+    #[must_use]
+    pub fn has_format(self) -> bool {
+        matches!(self, FilenameVariable::IncrementingNumber(_) | FilenameVariable::Date(..))
+    }
+
+    /// Returns the variable with the format `format`
+    ///
+    /// The format of a date variable can be put in `"` or `'` to include a `:`
+    ///
+    /// `offset` is the byte position of `format` in the template and is used for the span of the error
+    ///
+    /// # Errors
+    ///
+    /// Fails if `format` isn't a valid format for the variable
+    pub fn with_format(self, format: &'a str, offset: usize) -> Result<Self, FilyError<TokenizeError>> {
+        let malformed_format = || FilyError::new_with_context(TokenizeError::MalformedFormat(offset..offset + format.len()), || format!("{:?} isn't a valid format for {:?}", format, self));
+
+        match self {
+            FilenameVariable::IncrementingNumber(_) => NumberFormat::from_text(format.trim())
+                .map(FilenameVariable::IncrementingNumber)
+                .ok_or_else(malformed_format),
+            FilenameVariable::Date(date_variable, _) => {
+                let date_format = unquote(format.trim());
+
+                if StrftimeItems::new(date_format).any(|item| item == Item::Error) {
+                    return Err(malformed_format());
+                }
+
+                Ok(FilenameVariable::Date(date_variable, date_format))
+            }
+            _ => Err(malformed_format()),
+        }
+    }
+}
+
+/// Changes the value of a variable. Written behind the name of a variable separated by `:`, like `{filename:lower:trim}`
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Filter<'a> {
+    /// Makes every letter lowercase
+    Lower,
+
+    /// Makes every letter uppercase
+    Upper,
+
+    /// Replaces every occurrence of the first argument with the second one
+    Replace(&'a str, &'a str),
+
+    /// Keeps the characters from the first argument up to, but not including, the second one. Without a second
+    /// argument it goes until the end
+    Slice(usize, Option<usize>),
+
+    /// Removes whitespace at the start and the end
+    Trim,
+
+    /// Makes the first letter of every word uppercase and the others lowercase
+    Title,
+
+    /// Makes every letter lowercase and replaces everything that isn't a letter or a digit with a single `-`
+    Slug,
+}
+
+/// The names of every filter
+const FILTERS: &[&str] = &["lower", "upper", "replace", "slice", "trim", "title", "slug"];
+
+impl<'a> Filter<'a> {
+    /// Parses a single filter of a variable
+    ///
+    /// Arguments are put in parentheses and separated by `,`. An argument can be put in `"` or `'` to include a `:`,
+    /// `,`, `)` or whitespace at the start or end
+    ///
+    /// `offset` is the byte position of `text` in the template and is used for the span of the error
+    ///
+    /// # Errors
+    ///
+    /// Fails if the filter is unknown or has the wrong arguments
+    pub fn from_text(text: &'a str, offset: usize) -> Result<Self, FilyError<TokenizeError>> {
+        trace!("Filter::from_text text: {} offset: {}", text, offset);
+
+        let filter = text.trim();
+        let start = offset + (text.len() - text.trim_start().len());
+        let span = start..start + filter.len();
+
+        let malformed_filter = || FilyError::new_with_context(TokenizeError::MalformedFilter(span.clone()), || format!("The filter {:?} has the wrong arguments", filter));
+
+        let (name, arguments) = match filter.find('(') {
+            Some(i) if filter.ends_with(')') => {
+                let arguments_text = &filter[i + 1..filter.len() - 1];
+
+                let arguments = if arguments_text.trim().is_empty() {
+                    Vec::new()
+                } else {
+                    split_outside_quotes(arguments_text, ',', start + i + 1)?
+                        .into_iter()
+                        .map(|(_, argument)| unquote(argument.trim()))
+                        .collect()
+                };
+
+                (filter[..i].trim_end(), arguments)
+            }
+            Some(_) => return Err(malformed_filter()),
+            None => (filter, Vec::new()),
+        };
+
+        let parse_index = |argument: &str| argument.parse::<usize>().map_err(|_| malformed_filter());
+
+        Ok(match (name, arguments.as_slice()) {
+            ("lower", []) => Filter::Lower,
+            ("upper", []) => Filter::Upper,
+            ("replace", &[from, to]) if !from.is_empty() => Filter::Replace(from, to),
+            ("slice", &[start]) => Filter::Slice(parse_index(start)?, None),
+            ("slice", &[start, end]) => Filter::Slice(parse_index(start)?, Some(parse_index(end)?)),
+            ("trim", []) => Filter::Trim,
+            ("title", []) => Filter::Title,
+            ("slug", []) => Filter::Slug,
+            _ if FILTERS.contains(&name) => return Err(malformed_filter()),
+            _ => {
+                let suggestion = closest_name(name, FILTERS.iter().copied());
+                return Err(FilyError::new_with_context(TokenizeError::UnknownFilter(start..start + name.len(), suggestion), || format!("Unknown filter {:?}", name)));
+            }
+        })
+    }
+}
+
+/// Returns `true` if `text` is the name of a filter, with or without arguments
+fn is_filter(text: &str) -> bool {
+    let name = text.split('(').next().unwrap_or_default().trim();
+    FILTERS.contains(&name)
+}
+
+/// Splits `text` at every `separator` that isn't inside of `"` or `'`
+///
+/// Returns every part together with its byte position in the template
+fn split_outside_quotes(text: &str, separator: char, offset: usize) -> Result<Vec<(usize, &str)>, FilyError<TokenizeError>> {
+    let mut parts = Vec::new();
+    let mut part_start = 0;
+    let mut quote = None;
+
+    for (i, c) in text.char_indices() {
+        match quote {
+            Some(quote_char) if c == quote_char => quote = None,
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == separator => {
+                parts.push((offset + part_start, &text[part_start..i]));
+                part_start = i + 1;
+            }
+            _ => (),
+        }
+    }
+
+    if quote.is_some() {
+        return Err(FilyError::new_with_context(TokenizeError::MalformedFilter(offset..offset + text.len()), || format!("A quote in {:?} is never closed", text)));
+    }
+
+    parts.push((offset + part_start, &text[part_start..]));
+
+    Ok(parts)
+}
+
+/// Removes the quotes around `argument` if there are any
+fn unquote(argument: &str) -> &str {
+    let is_quoted = argument.len() >= 2 && (argument.starts_with('"') && argument.ends_with('"') || argument.starts_with('\'') && argument.ends_with('\''));
+
+    if is_quoted {
+        &argument[1..argument.len() - 1]
+    } else {
+        argument
+    }
+}
+
+/// An error in the options part of a template
+///
+/// Every variant contains the byte range of the template the error is at
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub enum OptionsParseError {
-    /// Failed to parse the value of an option or there was no value even though there should've been one
-    MalformedOption,
+    /// An option that needs a value was passed without one
+    MissingValue(Range<usize>),
 
-    /// An unknown option was passed
-    UnknownOption,
+    /// Failed to parse the value of an option
+    MalformedValue(Range<usize>),
+
+    /// An unknown option was passed. Contains the known option with the most similar name if there is one
+    UnknownOption(Range<usize>, Option<&'static str>),
+}
+
+impl OptionsParseError {
+    /// Returns the byte range of the template the error is at
+    #[must_use]
+    pub fn span(&self) -> Range<usize> {
+        match self {
+            OptionsParseError::MissingValue(span)
+            | OptionsParseError::MalformedValue(span)
+            | OptionsParseError::UnknownOption(span, _) => span.clone(),
+        }
+    }
 }
 
 impl Error for OptionsParseError {}
 
 impl fmt::Display for OptionsParseError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{:?}", self)
+        match self {
+            OptionsParseError::MissingValue(_) => write!(f, "this option needs a value, like `option=value`"),
+            OptionsParseError::MalformedValue(_) => write!(f, "this isn't a valid value for the option"),
+            OptionsParseError::UnknownOption(_, Some(suggestion)) => write!(f, "unknown option, did you mean {:?}?", suggestion),
+            OptionsParseError::UnknownOption(_, None) => write!(f, "unknown option"),
+        }
     }
 }
 
@@ -116,9 +570,82 @@ impl From<FilyError<OptionsParseError>> for RenameFilesError {
     }
 }
 
+/// The names of every option
+const OPTIONS: &[&str] = &["incrementing_number_starts_at", "incrementing_number_step", "incrementing_number_per_folder", "incrementing_number_order", "match", "match_against", "no_match", "timezone"];
+
+/// The order in which files get their `incrementing_number`
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum NumberingOrder {
+    /// The order the files were passed in
+    #[default]
+    Input,
+
+    /// Sorted by filename
+    Name,
+
+    /// Sorted by modification time, oldest first
+    Modified,
+
+    /// Sorted by size, smallest first
+    Size,
+}
+
+/// The timezone date variables are written in
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum Timezone {
+    #[default]
+    Local,
+    Utc,
+}
+
+/// What the `match` option is matched against
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum MatchTarget {
+    #[default]
+    Filename,
+    Path,
+}
+
+/// What happens to a file that doesn't match the `match` option
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum NoMatchPolicy {
+    /// The file isn't renamed
+    #[default]
+    Skip,
+
+    /// Nothing is renamed
+    Abort,
+}
+
+#[derive(Debug, Clone)]
 pub struct FilenameOptions {
     pub incrementing_number_starts_at: isize,
+    pub incrementing_number_step: isize,
+
+    /// Every folder has its own `incrementing_number` which starts at `incrementing_number_starts_at`
+    pub incrementing_number_per_folder: bool,
+    pub incrementing_number_order: NumberingOrder,
+
+    /// Only files that match this are renamed. Its captures can be used as variables
+    pub match_regex: Option<Regex>,
+    pub match_against: MatchTarget,
+    pub no_match_policy: NoMatchPolicy,
+    pub timezone: Timezone,
+}
+
+impl Default for FilenameOptions {
+    fn default() -> Self {
+        FilenameOptions {
+            incrementing_number_starts_at: 0,
+            incrementing_number_step: 1,
+            incrementing_number_per_folder: false,
+            incrementing_number_order: NumberingOrder::Input,
+            match_regex: None,
+            match_against: MatchTarget::Filename,
+            no_match_policy: NoMatchPolicy::Skip,
+            timezone: Timezone::Local,
+        }
+    }
 }
 
 impl FilenameOptions {
@@ -126,7 +653,7 @@ impl FilenameOptions {
     ///
     /// Read the docs of the `rename` function for an explanation on how the options format looks like
     ///
-    /// If you have options which are already separated use the `FilenameOptions::parse_options` function
+    /// `offset` is the byte position of `text` in the template and is used for the spans of errors
     ///
     /// # Errors
     ///
@@ -135,31 +662,168 @@ impl FilenameOptions {
     /// * the options aren't seperated by a `|`
     /// * the input includes an unknown option
     /// * if an option which requires a value does not have one or it failed to parse it
-    pub fn new(text: &str) -> Result<Self, FilyError<OptionsParseError>> {
-        let options: Vec<&str> = text.split('|').collect();
-
-        FilenameOptions::parse_options(&options)
-    }
+    pub fn new(text: &str, offset: usize) -> Result<Self, FilyError<OptionsParseError>> {
+        trace!("FilenameOptions::new text: {} offset: {}", text, offset);
 
-    /// Parses options that are not separated with a `|`
-    pub fn parse_options(options: &[&str]) -> Result<Self, FilyError<OptionsParseError>> {
         let mut filename_options = FilenameOptions::default();
+        let mut position = 0;
+
+        while position <= text.len() {
+            let rest = &text[position..];
+
+            let untrimmed_option = if rest.split('=').next().unwrap_or_default().trim() == "match" {
+                &rest[..match_option_length(rest)]
+            } else {
+                rest.find('|').map_or(rest, |i| &rest[..i])
+            };
 
-        for option in options {
-            match option.trim() {
-                _ if option.starts_with("incrementing_number_starts_at") => {
-                    let start_num = option.splitn(2, '=')
-                        .nth(1)
-                        .ok_or_else(|| FilyError::new(OptionsParseError::MalformedOption, "incrementing_number_starts_at was passed without a value"))?
+            let start = offset + position + (untrimmed_option.len() - untrimmed_option.trim_start().len());
+            let option = untrimmed_option.trim();
+            position += untrimmed_option.len() + 1;
+
+            let (name, value) = match option.find('=') {
+                Some(i) => (option[..i].trim_end(), Some((start + i + 1, &option[i + 1..]))),
+                None => (option, None),
+            };
+
+            let required_value = || value.ok_or_else(|| FilyError::new_with_context(OptionsParseError::MissingValue(start..start + option.len()), || format!("{} was passed without a value", name)));
+            let malformed_value = |value_start: usize, value: &str| FilyError::new_with_context(OptionsParseError::MalformedValue(value_start..value_start + value.len()), || format!("Couldn't parse value of {}", name));
+
+            match name {
+                "incrementing_number_starts_at" => {
+                    let (value_start, value) = required_value()?;
+
+                    filename_options.incrementing_number_starts_at = value.trim()
                         .parse()
-                        .map_err(|_| FilyError::new(OptionsParseError::MalformedOption, "Couldn't parse value of incrementing_number_starts_at"))?;
+                        .map_err(|_| malformed_value(value_start, value))?;
+                }
+                "incrementing_number_step" => {
+                    let (value_start, value) = required_value()?;
 
-                    filename_options.incrementing_number_starts_at = start_num;
+                    filename_options.incrementing_number_step = value.trim()
+                        .parse()
+                        .map_err(|_| malformed_value(value_start, value))?;
                 }
-                _ => return Err(FilyError::new_with_context(OptionsParseError::UnknownOption, || format!("Unknown option {:?}", option))),
-            };
+                "incrementing_number_per_folder" => {
+                    if let Some((value_start, value)) = value {
+                        return Err(malformed_value(value_start, value));
+                    }
+
+                    filename_options.incrementing_number_per_folder = true;
+                }
+                "incrementing_number_order" => {
+                    let (value_start, value) = required_value()?;
+
+                    filename_options.incrementing_number_order = match value.trim() {
+                        "input" => NumberingOrder::Input,
+                        "name" => NumberingOrder::Name,
+                        "modified" => NumberingOrder::Modified,
+                        "size" => NumberingOrder::Size,
+                        _ => return Err(malformed_value(value_start, value)),
+                    };
+                }
+                "match" => {
+                    let (value_start, value) = required_value()?;
+                    let regex = value.trim();
+
+                    if regex.starts_with(['"', '\'']) {
+                        if unquote(regex) == regex {
+                            return Err(FilyError::new_with_context(OptionsParseError::MalformedValue(value_start..value_start + value.len()), || format!("The quote of the regex {:?} is never closed or followed by more than a `|`", regex)));
+                        }
+                    } else if let Some(option_name) = regex.split('|').skip(1).map(|part| part.split('=').next().unwrap_or_default().trim()).find(|part| OPTIONS.contains(part)) {
+                        // Without quotes the regex goes until the end of the template and would swallow the option
+                        return Err(FilyError::new_with_context(OptionsParseError::MalformedValue(value_start..value_start + value.len()), || format!("The regex {:?} contains the option {}, put the regex in quotes to use options after it", regex, option_name)));
+                    }
+
+                    filename_options.match_regex = Some(Regex::new(unquote(regex))
+                        .map_err(|e| FilyError::new_with_context(OptionsParseError::MalformedValue(value_start..value_start + value.len()), || format!("{:?} isn't a valid regex {}", regex, e)))?);
+                }
+                "match_against" => {
+                    let (value_start, value) = required_value()?;
+
+                    filename_options.match_against = match value.trim() {
+                        "filename" => MatchTarget::Filename,
+                        "path" => MatchTarget::Path,
+                        _ => return Err(malformed_value(value_start, value)),
+                    };
+                }
+                "no_match" => {
+                    let (value_start, value) = required_value()?;
+
+                    filename_options.no_match_policy = match value.trim() {
+                        "skip" => NoMatchPolicy::Skip,
+                        "abort" => NoMatchPolicy::Abort,
+                        _ => return Err(malformed_value(value_start, value)),
+                    };
+                }
+                "timezone" => {
+                    let (value_start, value) = required_value()?;
+
+                    filename_options.timezone = match value.trim() {
+                        "local" => Timezone::Local,
+                        "utc" => Timezone::Utc,
+                        _ => return Err(malformed_value(value_start, value)),
+                    };
+                }
+                _ => {
+                    let suggestion = closest_name(name, OPTIONS.iter().copied());
+                    return Err(FilyError::new_with_context(OptionsParseError::UnknownOption(start..start + name.len(), suggestion), || format!("Unknown option {:?}", name)));
+                }
+            }
         }
 
         Ok(filename_options)
     }
 }
+
+/// Returns the length of the `match` option at the start of `rest`
+///
+/// A regex in `"` or `'` ends at the closing quote, like `match="^(a|b)"|timezone=utc`. Without quotes it goes until the end of
+/// the template, since a regex can contain a `|`
+fn match_option_length(rest: &str) -> usize {
+    let value_start = rest.find('=').map_or(0, |i| i + 1);
+    let value = rest[value_start..].trim_start();
+    let quote_start = rest.len() - value.len();
+
+    let closing_quote = value.chars()
+        .next()
+        .filter(|&c| c == '"' || c == '\'')
+        .and_then(|quote| value[1..].find(quote));
+
+    match closing_quote {
+        Some(i) => {
+            let quote_end = quote_start + i + 2;
+            rest[quote_end..].find('|').map_or(rest.len(), |i| quote_end + i)
+        }
+        None => rest.len(),
+    }
+}
+
+/// Returns the name out of `names` that is the most similar to `name` if it is similar enough to be a typo of it
+fn closest_name(name: &str, names: impl Iterator<Item = &'static str>) -> Option<&'static str> {
+    let max_distance = (name.chars().count() / 3).max(1);
+
+    names.map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|&(distance, _)| distance <= max_distance)
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// The Levenshtein distance between `a` and `b`
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous_row: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, char_a) in a.chars().enumerate() {
+        let mut row = vec![i + 1];
+
+        for (j, char_b) in b.iter().enumerate() {
+            let substitution_cost = usize::from(char_a != *char_b);
+            row.push((previous_row[j] + substitution_cost).min(previous_row[j + 1] + 1).min(row[j] + 1));
+        }
+
+        previous_row = row;
+    }
+
+    previous_row[b.len()]
+}
@@ -43,3 +43,862 @@ fn crc32_from_bytes_test() {
 
     assert_eq!(crc32_from_bytes(input), 0x28873A5C);
 }
+
+use crate::find::{Condition, ConditionParsingError, SearchCriteria, Filename};
+use std::convert::TryFrom;
+
+#[test]
+fn condition_try_from_test() {
+    let condition = Condition::try_from(r#"!"filename_exact"=a | "filename_contains"="b c" & ("filename_exact"=d)"#).unwrap();
+
+    match condition {
+        Condition::Or(left, right) => {
+            assert!(matches!(*left, Condition::Not(ref not) if matches!(**not, Condition::Value(SearchCriteria::Filename(Filename::Exact(ref name))) if name == "a")));
+
+            match *right {
+                Condition::And(left, right) => {
+                    assert!(matches!(*left, Condition::Value(SearchCriteria::Filename(Filename::Contains(ref name))) if name == "b c"));
+                    assert!(matches!(*right, Condition::Value(SearchCriteria::Filename(Filename::Exact(ref name))) if name == "d"));
+                }
+                _ => panic!("& should bind stronger than |"),
+            }
+        }
+        _ => panic!("| should be the outermost condition"),
+    }
+
+    assert_eq!(Condition::try_from("  ").unwrap_err(), ConditionParsingError::Empty);
+    assert_eq!(Condition::try_from(r#"("filename_exact"=a"#).unwrap_err(), ConditionParsingError::UnclosedParenthesis(0));
+    assert_eq!(Condition::try_from(r#""filename_exact"=a &"#).unwrap_err(), ConditionParsingError::UnexpectedEnd);
+    assert_eq!(Condition::try_from(r#""filename_exact"="a"#).unwrap_err(), ConditionParsingError::UnclosedQuote(17));
+    assert_eq!(Condition::try_from(r#""filename_exact"=a b"#).unwrap_err(), ConditionParsingError::UnexpectedCharacter(19));
+}
+
+use crate::find::{find, FindOptions, DirectoryContains, Sibling, Reference, ReferenceFile, SearchCriteriaParsingError};
+
+#[test]
+fn directory_contains_sibling_reference_test() {
+    let dir = std::env::temp_dir().join(format!("fily_directory_contains_test_{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("project")).unwrap();
+
+    for (name, modified) in [("project/Cargo.toml", 1000), ("photo.jpg", 1000), ("photo.xmp", 1000), ("lonely.jpg", 3000), ("reference", 2000)] {
+        std::fs::write(dir.join(name), "").unwrap();
+        filetime::set_file_mtime(dir.join(name), filetime::FileTime::from_unix_time(modified, 0)).unwrap();
+    }
+
+    // Creating the files changed the modification time of the folders
+    for folder in [dir.join("project"), dir.clone()] {
+        filetime::set_file_mtime(folder, filetime::FileTime::from_unix_time(1000, 0)).unwrap();
+    }
+
+    let reference_file = ReferenceFile::new(dir.join("reference")).unwrap();
+
+    let found_names = |criteria| {
+        let find_options = FindOptions {
+            options: vec![Condition::Value(criteria)],
+            ..FindOptions::default()
+        };
+
+        let (found, errors) = find(&[&dir], &find_options);
+        assert!(errors.is_empty());
+
+        let mut names: Vec<String> = found.iter().map(|path| path.strip_prefix(&dir).unwrap().to_string_lossy().into_owned()).collect();
+        names.sort();
+        names
+    };
+
+    let contains_exact = found_names(SearchCriteria::DirectoryContains(DirectoryContains::Exact("Cargo.toml".to_string())));
+    let contains_outside = found_names(SearchCriteria::DirectoryContains(DirectoryContains::Exact("../photo.jpg".to_string())));
+    let contains_regex = found_names(SearchCriteria::DirectoryContains(DirectoryContains::Regex(regex::Regex::new(r"\.xmp$").unwrap())));
+    let sibling_exact = found_names(SearchCriteria::Sibling(Sibling::Exact("photo.xmp".to_string())));
+    let sibling_extension = found_names(SearchCriteria::Sibling(Sibling::WithExtension("xmp".to_string())));
+    let newer_than = found_names(SearchCriteria::Reference(Reference::NewerThan(reference_file.clone())));
+    let older_than = found_names(SearchCriteria::Reference(Reference::OlderThan(reference_file)));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(contains_exact, ["project"]);
+    assert!(contains_outside.is_empty());
+    assert_eq!(contains_regex, [""]);
+    assert_eq!(sibling_exact, ["lonely.jpg", "photo.jpg", "project", "reference"]);
+    assert_eq!(sibling_extension, ["photo.jpg"]);
+    assert_eq!(newer_than, ["lonely.jpg"]);
+    assert_eq!(older_than, ["", "photo.jpg", "photo.xmp", "project", "project/Cargo.toml"]);
+
+    assert!(matches!(SearchCriteria::try_from(r#""directory_contains"=Cargo.toml"#), Ok(SearchCriteria::DirectoryContains(DirectoryContains::Exact(_)))));
+
+    for name in ["/etc/passwd", "../x", "a/b", "..", ".", ""] {
+        assert!(matches!(SearchCriteria::try_from(format!(r#""directory_contains"={}"#, name).as_str()), Err(SearchCriteriaParsingError::NotAFileName)), "{}", name);
+    }
+}
+
+use crate::find::{read_paths_from_file, read_patterns_from_file, ListSeparator, ListFileError, FindOptionsBuilder};
+
+#[test]
+fn list_file_test() {
+    let path = std::env::temp_dir().join(format!("fily_list_file_test_{}", std::process::id()));
+
+    std::fs::write(&path, "# comment\n  \na/b\r\nc d\n").unwrap();
+    let paths = read_paths_from_file(&path, ListSeparator::Newline);
+    let patterns = read_patterns_from_file(&path, ListSeparator::Newline);
+
+    std::fs::write(&path, "x\0\0# y\nz\0").unwrap();
+    let nul_separated_paths = read_paths_from_file(&path, ListSeparator::Nul);
+
+    std::fs::write(&path, "^a\n\n[b").unwrap();
+    let invalid_pattern = read_patterns_from_file(&path, ListSeparator::Newline);
+
+    std::fs::write(&path, "# only a comment\n\n").unwrap();
+    let mut find_options_builder = FindOptionsBuilder::new();
+    find_options_builder.add_exclude_patterns_from_file(&path, ListSeparator::Newline).unwrap();
+    let comments_only = find_options_builder.build();
+
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(paths.unwrap(), [PathBuf::from("a/b"), PathBuf::from("c d")]);
+    assert_eq!(patterns.unwrap().iter().map(regex::Regex::as_str).collect::<Vec<_>>(), ["a/b", "c d"]);
+    assert_eq!(nul_separated_paths.unwrap(), [PathBuf::from("x"), PathBuf::from("# y\nz")]);
+
+    let invalid_pattern = invalid_pattern.unwrap_err();
+    assert!(matches!(invalid_pattern.get_error(), ListFileError::InvalidPattern(_)));
+    assert!(invalid_pattern.get_context().contains("Entry 3"));
+
+    assert!(comments_only.options.is_empty());
+}
+
+use crate::find::{NameLint, Depth};
+
+#[test]
+fn name_lint_and_depth_test() {
+    let dir = std::env::temp_dir().join(format!("fily_name_lint_test_{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("sub/deep")).unwrap();
+
+    for name in ["Aux.txt", " lead", "tab\tname", "\u{fc}mlaut", "what?", "ok.txt", "sub/deep/file"] {
+        std::fs::write(dir.join(name), "").unwrap();
+    }
+
+    let found_names_in = |path: &Path, criteria| {
+        let find_options = FindOptions {
+            options: vec![Condition::Value(criteria)],
+            ..FindOptions::default()
+        };
+
+        let (found, errors) = find(&[path], &find_options);
+        assert!(errors.is_empty());
+
+        let mut names: Vec<String> = found.iter().map(|path| path.strip_prefix(&dir).unwrap().to_string_lossy().into_owned()).collect();
+        names.sort();
+        names
+    };
+    let found_names = |criteria| found_names_in(&dir, criteria);
+
+    let whitespace = found_names(SearchCriteria::NameLint(NameLint::LeadingOrTrailingWhitespace));
+    let control_characters = found_names(SearchCriteria::NameLint(NameLint::ControlCharacters));
+    let non_ascii = found_names(SearchCriteria::NameLint(NameLint::NonAscii));
+    let reserved_name = found_names(SearchCriteria::NameLint(NameLint::WindowsReservedName));
+    let invalid_windows_characters = found_names(SearchCriteria::NameLint(NameLint::InvalidWindowsCharacters));
+    let name_longer_than = found_names(SearchCriteria::NameLint(NameLint::NameLongerThan(6)));
+    let exact_depth = found_names(SearchCriteria::Depth(Depth::Exact(2)));
+    let over_depth = found_names(SearchCriteria::Depth(Depth::Over(1)));
+    let under_depth = found_names(SearchCriteria::Depth(Depth::Under(1)));
+    let exact_depth_in_sub = found_names_in(&dir.join("sub"), SearchCriteria::Depth(Depth::Exact(1)));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(whitespace, [" lead"]);
+    assert_eq!(control_characters, ["tab\tname"]);
+    assert_eq!(non_ascii, ["\u{fc}mlaut"]);
+    assert_eq!(reserved_name, ["Aux.txt"]);
+    assert_eq!(invalid_windows_characters, ["tab\tname", "what?"]);
+    // The name of the temporary folder is long too and ü is two bytes
+    assert_eq!(name_longer_than, ["", "Aux.txt", "tab\tname", "\u{fc}mlaut"]);
+    assert_eq!(exact_depth, ["sub/deep"]);
+    assert_eq!(over_depth, ["sub/deep", "sub/deep/file"]);
+    assert_eq!(under_depth, [""]);
+    assert_eq!(exact_depth_in_sub, ["sub/deep"]);
+}
+
+use crate::index::Index;
+use crate::find::SearchEntry;
+
+#[test]
+fn index_refresh_test() {
+    let dir = std::env::temp_dir().join(format!("fily_index_refresh_test_{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("sub")).unwrap();
+    std::fs::write(dir.join("a"), "a").unwrap();
+    std::fs::write(dir.join("sub/b"), "b").unwrap();
+
+    let set_folder_mtime = |folder: &Path, seconds| filetime::set_file_mtime(folder, filetime::FileTime::from_unix_time(seconds, 0)).unwrap();
+    set_folder_mtime(&dir, 1000);
+    set_folder_mtime(&dir.join("sub"), 1000);
+
+    let (mut index, errors) = Index::create(&[&dir], true);
+    assert!(errors.is_empty());
+
+    // A new file changes the modification time of its folder but changing the contents
+    // of a file doesn't
+    std::fs::write(dir.join("c"), "c").unwrap();
+    set_folder_mtime(&dir, 2000);
+    std::fs::write(dir.join("sub/b"), "changed").unwrap();
+    set_folder_mtime(&dir.join("sub"), 1000);
+
+    let errors = index.refresh();
+
+    let size_of = |name| index.entries().iter().find(|entry| entry.path().ends_with(name)).map(|entry| entry.size().unwrap());
+    let c_size = size_of("c");
+    let b_size = size_of("sub/b");
+    let number_of_entries = index.entries().len();
+    let all_hashed = index.entries().iter().filter(|entry| !entry.is_dir()).all(|entry| entry.crc32().is_some());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert!(errors.is_empty());
+    assert_eq!(number_of_entries, 5);
+    assert_eq!(c_size, Some(1));
+    assert_eq!(b_size, Some(1));
+    assert!(all_hashed);
+}
+
+use crate::index::find_in_index;
+
+#[test]
+fn find_in_index_overlapping_paths_test() {
+    let dir = std::env::temp_dir().join(format!("fily_find_in_index_test_{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("sub/deep")).unwrap();
+    std::fs::write(dir.join("sub/b"), "b").unwrap();
+    std::fs::write(dir.join("sub/deep/c"), "c").unwrap();
+    let dir = dir.canonicalize().unwrap();
+
+    let (index, errors) = Index::create(&[&dir], false);
+    assert!(errors.is_empty());
+
+    // The depth is counted from sub even though sub/b is 2 deep in dir
+    let find_options = FindOptions {
+        min_depth_from_start: 2,
+        ..FindOptions::default()
+    };
+
+    let (found, errors) = find_in_index(&index, &[dir.clone(), dir.join("sub")], &find_options);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert!(errors.is_empty());
+    assert_eq!(found, [dir.join("sub/deep/c")]);
+}
+
+use crate::find::LineCount;
+use std::io::Write;
+
+#[test]
+fn search_in_archives_test() {
+    let dir = std::env::temp_dir().join(format!("fily_search_in_archives_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("d.txt"), "d").unwrap();
+
+    let mut inner_zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    for name in ["b.txt", "dir/c.txt"] {
+        inner_zip.start_file(name, zip::write::FileOptions::default()).unwrap();
+        inner_zip.write_all(name.as_bytes()).unwrap();
+    }
+    let inner_zip = inner_zip.finish().unwrap().into_inner();
+
+    let mut outer_tar = tar::Builder::new(std::fs::File::create(dir.join("outer.tar")).unwrap());
+    for (name, contents) in [("a.txt", b"a".as_ref()), ("inner.zip", &inner_zip)] {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(contents.len() as u64);
+        header.set_cksum();
+        outer_tar.append_data(&mut header, name, contents).unwrap();
+    }
+    outer_tar.finish().unwrap();
+
+    let found_paths = |max_archive_depth, criterias| {
+        let find_options = FindOptions {
+            options: vec![Condition::build_all_of_condition(criterias)],
+            search_in_archives: true,
+            max_archive_depth,
+            ..FindOptions::default()
+        };
+
+        let (found, errors) = find(&[&dir], &find_options);
+        assert!(errors.is_empty());
+
+        let mut paths: Vec<String> = found.iter().map(|path| path.strip_prefix(&dir).unwrap().to_string_lossy().into_owned()).collect();
+        paths.sort();
+        paths
+    };
+
+    let txt_files = || vec![SearchCriteria::Filename(Filename::Contains(".txt".to_string()))];
+
+    let depth_0 = found_paths(0, txt_files());
+    let depth_1 = found_paths(1, txt_files());
+    let depth_2 = found_paths(2, txt_files());
+    let needs_filesystem = found_paths(2, vec![SearchCriteria::Filename(Filename::Contains(".txt".to_string())), SearchCriteria::LineCount(LineCount::Exact(1))]);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(depth_0, ["d.txt"]);
+    assert_eq!(depth_1, ["d.txt", "outer.tar!/a.txt"]);
+    assert_eq!(depth_2, ["d.txt", "outer.tar!/a.txt", "outer.tar!/inner.zip!/b.txt", "outer.tar!/inner.zip!/dir/c.txt"]);
+    assert_eq!(needs_filesystem, ["d.txt"]);
+}
+
+use crate::rename::{rename_files, RenameFilesError, TokenizeError, OptionsParseError, ConflictPolicy};
+
+#[test]
+fn rename_template_error_test() {
+    let no_files: &[&str] = &[];
+
+    let tokenize_error = |template| match rename_files(no_files, template, ConflictPolicy::Abort).unwrap_err() {
+        RenameFilesError::TokenizeError(e) => e.get_error().clone(),
+        e => panic!("Expected a tokenize error, got {:?}", e),
+    };
+
+    assert_eq!(tokenize_error("{filename"), TokenizeError::UnclosedBrace(0..9));
+    assert_eq!(tokenize_error("a}b"), TokenizeError::StrayClosingBrace(1..2));
+    assert_eq!(tokenize_error("{file{name}"), TokenizeError::NestedBrace(5..6));
+    assert_eq!(tokenize_error("x{}"), TokenizeError::EmptyVariable(1..3));
+    assert_eq!(tokenize_error("a_{filenme}.txt"), TokenizeError::UnknownVariable(3..10, Some("filename")));
+    assert_eq!(tokenize_error("{xyz}"), TokenizeError::UnknownVariable(1..4, None));
+
+    match rename_files(no_files, "{incrementing_number}| incrementing_number_start_at=4", ConflictPolicy::Abort).unwrap_err() {
+        RenameFilesError::OptionsParsingError(e) => assert_eq!(*e.get_error(), OptionsParseError::UnknownOption(23..51, Some("incrementing_number_starts_at"))),
+        e => panic!("Expected an options error, got {:?}", e),
+    }
+
+    assert_eq!(rename_files(no_files, "{filename}|incrementing_number_starts_at=x", ConflictPolicy::Abort).unwrap_err().template_span(), Some(41..42));
+    assert!(rename_files(no_files, "{filename_base}_{incrementing_number}.{filename_extension}|incrementing_number_starts_at=-3", ConflictPolicy::Abort).is_ok());
+}
+
+use crate::rename::{plan_renames, RenameProblem};
+use std::path::Path;
+
+#[test]
+fn plan_renames_test() {
+    let plan = plan_renames(&["dir/a.txt", "dir/b.txt", "dir/c.md", "dir/x.png"], "x.{filename_extension}").unwrap();
+    let problems: Vec<&[RenameProblem]> = plan.renames.iter().map(|planned_rename| planned_rename.problems.as_slice()).collect();
+
+    assert_eq!(plan.renames[2].new_path, PathBuf::from("dir/x.md"));
+    assert_eq!(problems, [&[RenameProblem::Collision][..], &[RenameProblem::Collision], &[], &[RenameProblem::Unchanged]]);
+    assert!(plan.has_problems());
+
+    let plan = plan_renames(&["a.txt"], "{filename_base}/").unwrap();
+    assert_eq!(plan.renames[0].problems, [RenameProblem::InvalidCharacters]);
+
+    let files = ["dir/a.txt", "dir/b.txt", "dir/c.txt"];
+
+    let plan = plan_renames(&files, "x.txt").unwrap().resolve_conflicts(ConflictPolicy::Suffix).unwrap();
+    let new_paths: Vec<&Path> = plan.renames.iter().map(|planned_rename| planned_rename.new_path.as_path()).collect();
+    assert_eq!(new_paths, [Path::new("dir/x.txt"), Path::new("dir/x_1.txt"), Path::new("dir/x_2.txt")]);
+
+    let plan = plan_renames(&files, "x.txt").unwrap().resolve_conflicts(ConflictPolicy::Skip).unwrap();
+    assert_eq!(plan.renames.len(), 1);
+    assert_eq!(plan.conflicts.len(), 2);
+
+    assert!(matches!(plan_renames(&files, "x.txt").unwrap().resolve_conflicts(ConflictPolicy::Abort), Err(RenameFilesError::Conflicts(conflicts)) if conflicts.len() == 2));
+}
+
+#[test]
+fn rename_cycle_test() {
+    let dir = std::env::temp_dir().join(format!("fily_rename_cycle_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    for name in ["1", "2", "3"] {
+        std::fs::write(dir.join(name), name).unwrap();
+    }
+
+    // 3 -> 1, 1 -> 2 and 2 -> 3
+    let files = [dir.join("3"), dir.join("1"), dir.join("2")];
+    rename_files(&files, "{incrementing_number}|incrementing_number_starts_at=1", ConflictPolicy::Abort).unwrap();
+
+    let contents: Vec<String> = ["1", "2", "3"].iter().map(|name| std::fs::read_to_string(dir.join(name)).unwrap()).collect();
+    let number_of_files = std::fs::read_dir(&dir).unwrap().count();
+
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(contents, ["3", "1", "2"]);
+    assert_eq!(number_of_files, 3);
+}
+
+#[test]
+fn rename_overwrite_collision_test() {
+    let dir = std::env::temp_dir().join(format!("fily_rename_overwrite_collision_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    for name in ["a", "b", "x"] {
+        std::fs::write(dir.join(name), name).unwrap();
+    }
+
+    // Both want x, only the existing x is replaced
+    let files = [dir.join("a"), dir.join("b")];
+    let renamed = rename_files(&files, "x", ConflictPolicy::Overwrite).unwrap();
+
+    let x = std::fs::read_to_string(dir.join("x")).unwrap();
+    let b = std::fs::read_to_string(dir.join("b")).unwrap();
+    let number_of_files = std::fs::read_dir(&dir).unwrap().count();
+
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(renamed, [(dir.join("a"), dir.join("x"))]);
+    assert_eq!(x, "a");
+    assert_eq!(b, "b");
+    assert_eq!(number_of_files, 2);
+}
+
+use crate::journal::{Journal, JournalError, Operation};
+
+#[test]
+fn journal_test() {
+    let dir = std::env::temp_dir().join(format!("fily_journal_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    for name in ["a", "b"] {
+        std::fs::write(dir.join(name), name).unwrap();
+    }
+
+    let files = [dir.join("a"), dir.join("b")];
+    let renamed = rename_files(&files, "new\t{filename}\\", ConflictPolicy::Abort).unwrap();
+
+    let journal_path = dir.join("fily.journal");
+    Journal::record(Operation::Rename, &renamed).save(&journal_path).unwrap();
+    let journal = Journal::load(&journal_path).unwrap();
+
+    // A changed file isn't moved back
+    std::fs::write(dir.join("new\tb\\"), "changed").unwrap();
+    let failed_entries = journal.undo();
+
+    let a_is_back = dir.join("a").exists();
+    let b_is_back = dir.join("b").exists();
+    let canonical_dir = dir.canonicalize().unwrap();
+
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(journal.entries.len(), 2);
+    assert_eq!(journal.entries[1].new_path, canonical_dir.join("new\tb\\"));
+    assert!(a_is_back);
+    assert!(!b_is_back);
+    assert_eq!(failed_entries.len(), 1);
+    assert!(matches!(failed_entries[0].1.get_error(), JournalError::NewPathChanged));
+
+    let header_error = |header: &str| {
+        let journal_path = dir.with_extension("journal");
+        std::fs::write(&journal_path, header).unwrap();
+        let error = Journal::load(&journal_path).unwrap_err();
+        std::fs::remove_file(&journal_path).unwrap();
+        error
+    };
+
+    assert!(matches!(header_error("fily journal 2\n").get_error(), JournalError::UnsupportedVersion));
+    assert!(matches!(header_error("fily journal one\n").get_error(), JournalError::Malformed(1)));
+    assert!(matches!(header_error("fily journal 0\n").get_error(), JournalError::Malformed(1)));
+}
+
+#[test]
+fn rename_match_test() {
+    let files = [Path::new("IMG_20210405_1234.jpg"), Path::new("notes.txt")];
+
+    let plan = plan_renames(&files, "{d}.{m}.{y}_{4}.jpg|match=^IMG_(?P<y>\\d{4})(?P<m>\\d{2})(?P<d>\\d{2})_(\\d+)|^$").unwrap();
+    assert_eq!(plan.renames[0].new_path, Path::new("05.04.2021_1234.jpg"));
+    assert_eq!(plan.skipped.len(), 1);
+
+    match plan_renames(&files, "{1}|no_match=abort|match=(\\d+)").unwrap_err() {
+        RenameFilesError::ParsingError(e) => assert!(matches!(e.get_error(), crate::rename::ParseError::NoMatch)),
+        e => panic!("Expected a parsing error, got {:?}", e),
+    }
+
+    match plan_renames(&files, "{2}|match=(\\d+)").unwrap_err() {
+        RenameFilesError::TokenizeError(e) => assert_eq!(*e.get_error(), TokenizeError::UnknownVariable(1..2, None)),
+        e => panic!("Expected a tokenize error, got {:?}", e),
+    }
+
+    // Options after a quoted regex are used, after an unquoted one they're an error instead of a part of the regex
+    let plan = plan_renames(&files, "{incrementing_number}_{1}_{2}|match=\"^IMG_(\\d+)_(\\d+|x)\" | incrementing_number_starts_at=5").unwrap();
+    assert_eq!(plan.renames[0].new_path, Path::new("5_20210405_1234"));
+    assert_eq!(plan.skipped.len(), 1);
+
+    match plan_renames(&files, "{1}|match=^IMG_(\\d+)|timezone=utc").unwrap_err() {
+        RenameFilesError::OptionsParsingError(e) => assert_eq!(*e.get_error(), OptionsParseError::MalformedValue(10..33)),
+        e => panic!("Expected an options parse error, got {:?}", e),
+    }
+}
+
+#[test]
+fn rename_filter_test() {
+    let files = [Path::new("  My Holiday: Photos.JPG")];
+
+    let new_path = |template| plan_renames(&files, template).unwrap().renames.remove(0).new_path;
+
+    assert_eq!(new_path("{filename_base:trim:lower:replace(\" \",\"_\")}"), Path::new("my_holiday:_photos"));
+    assert_eq!(new_path("{filename_base:slug}.{filename_extension:lower}"), Path::new("my-holiday-photos.jpg"));
+    assert_eq!(new_path("{filename_base:trim:slice(3,10):upper}"), Path::new("HOLIDAY"));
+    assert_eq!(new_path("{filename_base:trim:lower:title:replace(':', '')}"), Path::new("My Holiday Photos"));
+
+    // A `|` inside of a variable doesn't start the options
+    let files = [Path::new("a|b c.txt")];
+    assert_eq!(plan_renames(&files, "{filename_base:replace(\"|\",\"_\")}").unwrap().renames[0].new_path, Path::new("a_b c"));
+    assert_eq!(plan_renames(&files, "{incrementing_number}_{filename_base:replace('|', ''):replace(\" \",\"|\")}|incrementing_number_starts_at=5").unwrap().renames[0].new_path, Path::new("5_ab|c"));
+
+    let tokenize_error = |template| match plan_renames(&files, template).unwrap_err() {
+        RenameFilesError::TokenizeError(e) => e.get_error().clone(),
+        e => panic!("Expected a tokenize error, got {:?}", e),
+    };
+
+    assert_eq!(tokenize_error("{filename:lowr}"), TokenizeError::UnknownFilter(10..14, Some("lower")));
+    assert_eq!(tokenize_error("{filename:slice(a)}"), TokenizeError::MalformedFilter(10..18));
+    assert_eq!(tokenize_error("{filename:replace(\"a)}"), TokenizeError::MalformedFilter(10..21));
+}
+
+#[test]
+fn rename_incrementing_number_test() {
+    let files = [Path::new("a/x"), Path::new("b/y"), Path::new("a/w")];
+
+    let new_names = |template| -> Vec<String> {
+        plan_renames(&files, template).unwrap()
+            .renames
+            .iter()
+            .map(|planned_rename| planned_rename.new_path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect()
+    };
+
+    assert_eq!(new_names("{incrementing_number:03}|incrementing_number_starts_at=9"), ["009", "010", "011"]);
+    assert_eq!(new_names("{incrementing_number:x:upper}|incrementing_number_starts_at=9|incrementing_number_step=3"), ["9", "C", "F"]);
+    assert_eq!(new_names("{incrementing_number:roman}|incrementing_number_starts_at=1|incrementing_number_per_folder"), ["I", "I", "II"]);
+    assert_eq!(new_names("{incrementing_number:3}|incrementing_number_order=name|incrementing_number_step=-1"), [" -1", " -2", "  0"]);
+
+    match plan_renames(&files, "{incrementing_number:04z}").unwrap_err() {
+        RenameFilesError::TokenizeError(e) => assert_eq!(*e.get_error(), TokenizeError::MalformedFormat(21..24)),
+        e => panic!("Expected a tokenize error, got {:?}", e),
+    }
+}
+
+#[test]
+fn rename_date_test() {
+    let path = std::env::temp_dir().join(format!("fily_rename_date_test_{}", std::process::id()));
+    std::fs::write(&path, "").unwrap();
+
+    // 2021-04-05 12:34:56 UTC
+    filetime::set_file_mtime(&path, filetime::FileTime::from_unix_time(1_617_626_096, 0)).unwrap();
+
+    let new_filename = |template| plan_renames(&[&path], template).map(|mut plan| plan.renames.remove(0).new_path.file_name().unwrap().to_string_lossy().into_owned());
+
+    let with_default_format = new_filename("{modified}|timezone=utc");
+    let with_format = new_filename("{modified:%Y%m%d_%H%M%S}|timezone=utc");
+    let with_colons = new_filename("{modified:'%H:%M':replace(':','-')}|timezone=utc");
+    let malformed_format = new_filename("{modified:%Y-%}");
+
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(with_default_format.unwrap(), "2021-04-05");
+    assert_eq!(with_format.unwrap(), "20210405_123456");
+    assert_eq!(with_colons.unwrap(), "12-34");
+
+    match malformed_format.unwrap_err() {
+        RenameFilesError::TokenizeError(e) => assert_eq!(*e.get_error(), TokenizeError::MalformedFormat(10..14)),
+        e => panic!("Expected a tokenize error, got {:?}", e),
+    }
+}
+
+#[test]
+fn rename_image_variables_test() {
+    let path = std::env::temp_dir().join(format!("fily_rename_image_test_{}.jpg", std::process::id()));
+    image::RgbImage::new(3, 2).save_with_format(&path, image::ImageFormat::Png).unwrap();
+
+    let plan = plan_renames(&[&path], "photo_{image_width}x{image_height}.{actual_extension}");
+    let without_exif = plan_renames(&[&path], "{camera_model}");
+
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(plan.unwrap().renames[0].new_path.file_name().unwrap(), "photo_3x2.png");
+    assert!(matches!(without_exif.unwrap().skipped[0].1.get_error(), crate::rename::ParseError::ExifTagsError(_)));
+}
+
+use crate::exif_tags::parse_date_time;
+
+#[test]
+fn parse_date_time_test() {
+    let date_time = |text| parse_date_time(text).map(|date_time| date_time.to_string());
+
+    assert_eq!(date_time("2021-04-05").unwrap(), "2021-04-05 00:00:00");
+    assert_eq!(date_time("2021-04-05T12:34").unwrap(), "2021-04-05 12:34:00");
+    assert_eq!(date_time("2021-04-05T12:34:56").unwrap(), "2021-04-05 12:34:56");
+    assert!(date_time("2021-02-30").is_err());
+    assert!(date_time("1617626096").is_err());
+}
+
+use crate::exif_tags::parse_date_time_range;
+use crate::find::ExifTag;
+
+#[test]
+fn taken_at_test() {
+    let path = std::env::temp_dir().join(format!("fily_taken_at_test_{}.tif", std::process::id()));
+
+    let date_time_original = exif::Field {
+        tag: exif::Tag::DateTimeOriginal,
+        ifd_num: exif::In::PRIMARY,
+        value: exif::Value::Ascii(vec![b"2021:04:05 12:34:56".to_vec()]),
+    };
+
+    let mut writer = exif::experimental::Writer::new();
+    let mut tiff = Cursor::new(Vec::new());
+    writer.push_field(&date_time_original);
+    writer.write(&mut tiff, false).unwrap();
+    std::fs::write(&path, tiff.into_inner()).unwrap();
+
+    let is_found = |taken_at| {
+        let find_options = FindOptions {
+            options: vec![Condition::Value(SearchCriteria::ExifTag(ExifTag::TakenAt(parse_date_time_range(taken_at).unwrap())))],
+            ..FindOptions::default()
+        };
+
+        !find(&[&path], &find_options).0.is_empty()
+    };
+
+    let found = ["2021-04-05", "2021-04-05T12:34", "2021-04-05T12:34:56", "2021-04-06", "2021-04-05T12:35", "2021-04-05T12:34:55"].map(is_found);
+
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(found, [true, true, true, false, false, false]);
+}
+
+use crate::audio_tags::AudioTags;
+
+#[test]
+fn id3v1_test() {
+    let path = std::env::temp_dir().join(format!("fily_id3v1_test_{}.mp3", std::process::id()));
+
+    // Silent MPEG-1 Layer III frames with 128 kbit/s at 44.1 kHz are 417 bytes long
+    let mut mp3 = Vec::new();
+
+    for _ in 0..10 {
+        mp3.extend_from_slice(&[0xFF, 0xFB, 0x90, 0x00]);
+        mp3.resize(mp3.len() + 413, 0);
+    }
+
+    let padded = |text: &str, length| {
+        let mut bytes = text.as_bytes().to_vec();
+        bytes.resize(length, b' ');
+        bytes
+    };
+
+    mp3.extend_from_slice(b"TAG");
+    mp3.extend(padded("Title", 30));
+    mp3.extend(padded("Artist", 30));
+    mp3.extend(padded("Album", 30));
+    mp3.extend_from_slice(b"1999");
+    mp3.extend(padded("", 30));
+    mp3.push(17);
+    std::fs::write(&path, mp3).unwrap();
+
+    let audio_tags = AudioTags::read(&path);
+
+    std::fs::remove_file(&path).unwrap();
+
+    let audio_tags = audio_tags.unwrap();
+    assert_eq!(audio_tags.title.as_deref(), Some("Title"));
+    assert_eq!(audio_tags.artist.as_deref(), Some("Artist"));
+    assert_eq!(audio_tags.album.as_deref(), Some("Album"));
+    assert_eq!(audio_tags.year, Some(1999));
+    assert_eq!(audio_tags.genre.as_deref(), Some("Rock"));
+}
+
+use crate::find::Xattr;
+
+#[test]
+#[cfg(target_os = "linux")]
+fn xattr_symlink_test() {
+    let dir = std::env::temp_dir().join(format!("fily_xattr_symlink_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("file"), "").unwrap();
+    ::xattr::set(dir.join("file"), "user.fily_test", b"tagged").unwrap();
+    std::os::unix::fs::symlink(dir.join("file"), dir.join("link")).unwrap();
+    std::os::unix::fs::symlink(dir.join("missing"), dir.join("dangling")).unwrap();
+
+    let found_names = |follow_symlinks| {
+        let find_options = FindOptions {
+            options: vec![Condition::Value(SearchCriteria::Xattr(Xattr::Exists(String::from("user.fily_test"))))],
+            follow_symlinks,
+            ..FindOptions::default()
+        };
+
+        let (found, errors) = find(&[&dir], &find_options);
+
+        let mut names: Vec<String> = found.iter().map(|path| path.strip_prefix(&dir).unwrap().to_string_lossy().into_owned()).collect();
+        names.sort();
+        (names, errors.len())
+    };
+
+    // Without following them the symlinks themselves are checked, which don't have the attribute
+    let not_followed = found_names(false);
+    let followed = found_names(true);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(not_followed, (vec![String::from("file")], 0));
+    assert_eq!(followed.0, ["file", "link"]);
+}
+
+use crate::find::ConditionEvalError;
+
+#[test]
+#[cfg(unix)]
+fn traversal_errors_test() {
+    let dir = std::env::temp_dir().join(format!("fily_traversal_errors_test_{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("sub")).unwrap();
+    std::fs::write(dir.join("sub/file"), "").unwrap();
+    std::os::unix::fs::symlink(&dir, dir.join("sub/loop")).unwrap();
+    std::os::unix::fs::symlink(dir.join("missing"), dir.join("dangling")).unwrap();
+
+    let find_options = FindOptions {
+        follow_symlinks: true,
+        ..FindOptions::default()
+    };
+
+    let (found, errors) = find(&[&dir], &find_options);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let mut names: Vec<String> = found.iter().map(|path| path.strip_prefix(&dir).unwrap().to_string_lossy().into_owned()).collect();
+    names.sort();
+
+    // Neither the loop nor the dangling symlink are searched or reported as found
+    assert_eq!(names, ["", "sub", "sub/file"]);
+    assert_eq!(errors.len(), 2);
+
+    let symlink_loop = errors.iter().find(|(path, _)| path == &dir.join("sub/loop")).unwrap();
+    assert!(matches!(&symlink_loop.1, ConditionEvalError::SymlinkLoop { ancestor, child } if ancestor == &dir && child == &dir.join("sub/loop")));
+
+    let dangling = errors.iter().find(|(path, _)| path == &dir.join("dangling")).unwrap();
+    assert!(matches!(&dangling.1, ConditionEvalError::TraversalErr(e) if e.get_error().kind() == std::io::ErrorKind::NotFound));
+}
+
+#[test]
+#[cfg(unix)]
+fn overlapping_paths_and_hard_links_test() {
+    let dir = std::env::temp_dir().join(format!("fily_overlapping_paths_test_{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("sub")).unwrap();
+    std::fs::write(dir.join("sub/file"), "").unwrap();
+    std::fs::hard_link(dir.join("sub/file"), dir.join("sub/link")).unwrap();
+
+    let found_names = |merge_overlapping_paths, unique_inodes| {
+        let find_options = FindOptions {
+            merge_overlapping_paths,
+            unique_inodes,
+            ..FindOptions::default()
+        };
+
+        let (found, errors) = find(&[dir.join("sub"), dir.clone(), dir.join("sub/")], &find_options);
+        assert!(errors.is_empty());
+
+        let mut names: Vec<String> = found.iter().map(|path| path.strip_prefix(&dir).unwrap().to_string_lossy().into_owned()).collect();
+        names.sort();
+        names
+    };
+
+    let overlapping = found_names(false, false);
+    let merged = found_names(true, false);
+    let merged_unique = found_names(true, true);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(overlapping.iter().filter(|name| *name == "sub/file").count(), 3);
+    assert_eq!(merged, ["", "sub", "sub/file", "sub/link"]);
+
+    // Which of the two links is reported depends on the order they are read in
+    assert_eq!(merged_unique.len(), 3);
+    assert!(merged_unique[2] == "sub/file" || merged_unique[2] == "sub/link");
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn find_watcher_new_folder_test() {
+    use crate::find::FindWatcher;
+    use std::{sync::mpsc, time::Duration};
+
+    let dir = std::env::temp_dir().join(format!("fily_find_watcher_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let find_options = FindOptions {
+        options: vec![Condition::Value(SearchCriteria::Filename(Filename::Exact(String::from("new_file"))))],
+        ..FindOptions::default()
+    };
+
+    let (mut find_watcher, errors) = FindWatcher::new(&[&dir], find_options, Duration::from_millis(50)).unwrap();
+    assert!(errors.is_empty());
+
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || sender.send(find_watcher.wait_for_matches().unwrap()));
+
+    // The watch on the subfolder has to be added before the file is created in it
+    std::fs::create_dir(dir.join("sub")).unwrap();
+    std::thread::sleep(Duration::from_millis(200));
+    std::fs::write(dir.join("sub/new_file"), "").unwrap();
+
+    let result = receiver.recv_timeout(Duration::from_secs(10));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let (matches, errors) = result.expect("The new file wasn't reported");
+    assert_eq!(matches, [dir.join("sub/new_file")]);
+    assert!(errors.is_empty());
+}
+
+use crate::find::{Git, GitState, GitRepositories};
+
+#[test]
+fn git_state_test() {
+    let dir = std::env::temp_dir().join(format!("fily_git_state_test_{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("build")).unwrap();
+
+    let repository = git2::Repository::init(&dir).unwrap();
+
+    for (name, content) in [(".gitignore", "build/\n"), ("committed", "a"), ("modified", "b"), ("build/output", "c")] {
+        std::fs::write(dir.join(name), content).unwrap();
+    }
+
+    let mut index = repository.index().unwrap();
+
+    for name in [".gitignore", "committed", "modified"] {
+        index.add_path(Path::new(name)).unwrap();
+    }
+
+    index.write().unwrap();
+    let tree = repository.find_tree(index.write_tree().unwrap()).unwrap();
+    let signature = git2::Signature::new("fily", "fily@example.com", &git2::Time::new(1000, 0)).unwrap();
+    repository.commit(Some("HEAD"), &signature, &signature, "Initial commit", &tree, &[]).unwrap();
+
+    std::fs::write(dir.join("modified"), "changed").unwrap();
+    std::fs::write(dir.join("staged"), "d").unwrap();
+    std::fs::write(dir.join("untracked"), "e").unwrap();
+    index.add_path(Path::new("staged")).unwrap();
+    index.write().unwrap();
+
+    let repositories = GitRepositories::new();
+
+    let found_names = |state| {
+        let find_options = FindOptions {
+            options: vec![Condition::Value(SearchCriteria::Git(Git::with_repositories(state, repositories.clone())))],
+            ..FindOptions::default()
+        };
+
+        let (found, errors) = find(&[&dir], &find_options);
+        assert!(errors.is_empty());
+
+        let mut names: Vec<String> = found.iter().map(|path| path.strip_prefix(&dir).unwrap().to_string_lossy().into_owned()).collect();
+        names.sort();
+        names
+    };
+
+    let untracked = found_names(GitState::Untracked);
+    let ignored = found_names(GitState::Ignored);
+    let modified = found_names(GitState::Modified);
+    let staged = found_names(GitState::Staged);
+    let committed_before = found_names(GitState::LastCommitBefore(2000));
+    let committed_before_first_commit = found_names(GitState::LastCommitBefore(1000));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(untracked, ["untracked"]);
+    assert_eq!(ignored, ["build", "build/output"]);
+    assert_eq!(modified, ["modified"]);
+    assert_eq!(staged, ["staged"]);
+    assert_eq!(committed_before, [".gitignore", "committed", "modified"]);
+    assert!(committed_before_first_commit.is_empty());
+}
@@ -1,10 +1,12 @@
-use std::ffi::OsString;
+use std::{ffi::{OsStr, OsString}, time::Duration, convert::TryFrom, error::Error};
 use regex::Regex;
 use clap::{crate_name, crate_version, App, AppSettings, Arg, SubCommand};
 
 use fily_lib::{
-    find::{Filename, FilePath, Filesize, Modified, Accessed, Created, Ignore, Condition, SearchCriteria},
+    find::{Filename, FilePath, Filesize, Modified, Accessed, Created, DirectoryContains, Sibling, Reference, ReferenceFile, TextOrBinary, LineCount, TextEncoding, LineEnding, NameLint, Depth, Entropy, EntropySample, AudioTag, ExifTag, TagText, Xattr, Git, GitState, GitRepositories, ListSeparator, Ignore, Condition, SearchCriteria},
     similar_images::{HashAlg, FilterType},
+    rename::ConflictPolicy,
+    exif_tags::{parse_date_time, parse_date_time_range},
 };
 
 #[derive(Debug, Clone)]
@@ -19,24 +21,30 @@ pub enum Subcommand {
         use_hash_version: bool,
     },
 
-    Find {
-        paths_to_search_in: Vec<OsString>,
-        conditions: Vec<Condition<SearchCriteria>>,
-        max_num_results: usize,
-        max_search_depth: usize,
-        min_depth_from_start: usize,
-        ignore: Option<Ignore>,
-        ignore_hidden_files: bool,
-        follow_symlinks: bool,
-        output_separator: String,
+    Find(Box<FindArgs>),
+
+    Index {
+        paths_to_index: Vec<OsString>,
+        database: OsString,
+        hash_contents: bool,
+        refresh: bool,
     },
 
     Move {
         move_to: OsString,
+
+        /// `None` if no journal should be written
+        journal: Option<OsString>,
     },
 
     Rename {
         template: String,
+        dry_run: bool,
+        preview_format: PreviewFormat,
+        conflict_policy: ConflictPolicy,
+
+        /// `None` if no journal should be written
+        journal: Option<OsString>,
     },
 
     SimilarImages {
@@ -46,6 +54,54 @@ pub enum Subcommand {
         hash_height: u32,
         threshold: u32,
     },
+
+    Undo {
+        journal: OsString,
+    },
+}
+
+/// The options of the `find` subcommand. Boxed in `Subcommand` since they are much larger than the other ones
+#[derive(Debug, Clone)]
+pub struct FindArgs {
+    pub preset: Option<String>,
+    pub config: Option<OsString>,
+    pub paths_to_search_in: Vec<OsString>,
+    pub paths_from: Option<OsString>,
+    pub exclude_from: Option<OsString>,
+    pub list_separator: ListSeparator,
+    pub conditions: Vec<Condition<SearchCriteria>>,
+    pub max_num_results: Option<usize>,
+    pub max_search_depth: Option<usize>,
+    pub min_depth_from_start: Option<usize>,
+    pub ignore: Option<Ignore>,
+    pub ignore_hidden_files: Option<bool>,
+    pub follow_symlinks: Option<bool>,
+    pub merge_overlapping_paths: Option<bool>,
+    pub unique_inodes: Option<bool>,
+    pub search_in_archives: Option<bool>,
+    pub max_archive_depth: Option<usize>,
+    pub output_separator: Option<String>,
+    pub print_xattrs: Vec<String>,
+    pub print_errors: Option<bool>,
+    pub index: Option<OsString>,
+    pub watch: bool,
+    pub watch_debounce: u64,
+}
+
+/// The journal `rename`, `move` and `undo` use if no other one is passed
+pub const DEFAULT_JOURNAL: &str = "fily.journal";
+
+/// How `rename --dry_run` prints the renames it would do
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum PreviewFormat {
+    /// `old -> new` with the problems behind it, one rename per line
+    Table,
+
+    /// A JSON object with the renames and the skipped files
+    Json,
+
+    /// The old name with a `-` in front of it and the new name with a `+` in front of it
+    Diff,
 }
 
 #[derive(Debug, Clone)]
@@ -58,8 +114,7 @@ pub struct CLIOptions {
 
 impl CLIOptions {
     /// Creates a new `CLIOptions` from the cli arguments
-    // TODO: Not sure about the error type. Maybe I'll have to change that later
-    pub fn new() -> Result<Self, &'static str> {
+    pub fn new() -> Result<Self, Box<dyn Error>> {
         let app = App::new(crate_name!())
             .about("Does stuff with files")
             .version(crate_version!())
@@ -103,12 +158,50 @@ impl CLIOptions {
                     .arg(
                         Arg::with_name("paths_to_search_in")
                             .value_name("paths_to_search_in")
-                            .required(true)
+                            .required_unless_one(&["paths_from", "preset"])
                             .multiple(true)
                             .short("p")
                             .long("paths_to_search_in")
                             .help("Search starts at this/these path(s). If the path points to a file instead of a folder it will find that file and only that file for that path")
                     )
+                    .arg(
+                        Arg::with_name("preset")
+                            .value_name("@preset")
+                            .index(1)
+                            .validator(|input| {
+                                if input.starts_with('@') && input.len() > 1 {
+                                    Ok(())
+                                } else {
+                                    Err("The name of a preset has to start with @, i.e. @stale-builds".to_string())
+                                }
+                            })
+                            .help("Runs a search that is saved in the config file. Paths to search in and conditions that are passed as well are added to the ones of the preset, every other option overrides the one in the preset. Has to come before --paths_to_search_in")
+                    )
+                    .arg(
+                        Arg::with_name("config")
+                            .value_name("config")
+                            .requires("preset")
+                            .long("config")
+                            .help("Reads the preset from this config file instead of the default one")
+                    )
+                    .arg(
+                        Arg::with_name("paths_from")
+                            .value_name("paths_from")
+                            .long("paths_from")
+                            .help("Reads more paths to search in from this file. Every line is a path. Empty lines and lines starting with # are skipped")
+                    )
+                    .arg(
+                        Arg::with_name("exclude_from")
+                            .value_name("exclude_from")
+                            .long("exclude_from")
+                            .help("Reads regexes from this file. Every line is a regex and a filename must not match any of them, like filename_regex_ignore. Empty lines and lines starting with # are skipped")
+                    )
+                    .arg(
+                        Arg::with_name("null_separated")
+                            .short("0")
+                            .long("null_separated")
+                            .help("The entries in the files passed to paths_from and exclude_from are separated by null bytes instead of newlines. Nothing but empty entries is skipped then")
+                    )
                     .arg(
                         Arg::with_name("filename_exact")
                             .value_name("filename_exact")
@@ -287,6 +380,435 @@ impl CLIOptions {
                             .long("created_after")
                             .help("The file has to be created after this time. Value should be in seconds relative to the unix epoch. Note: Not all Unix platforms have this field available which results in an error")
                     )
+                    .arg(
+                        Arg::with_name("directory_contains")
+                            .value_name("directory_contains")
+                            .multiple(true)
+                            .long("directory_contains")
+                            .validator(|input| {
+                                if DirectoryContains::is_file_name(&input) {
+                                    Ok(())
+                                } else {
+                                    Err("directory_contains has to be the name of a file or folder, not a path".to_string())
+                                }
+                            })
+                            .help("A folder has to contain a file or folder with each of the passed names to be considered a match. Files never match")
+                    )
+                    .arg(
+                        Arg::with_name("has_sibling")
+                            .value_name("has_sibling")
+                            .multiple(true)
+                            .long("has_sibling")
+                            .help("The folder a file is in has to contain another file or folder with each of the passed names to be considered a match")
+                    )
+                    .arg(
+                        Arg::with_name("has_sibling_extension")
+                            .value_name("has_sibling_extension")
+                            .multiple(true)
+                            .long("has_sibling_extension")
+                            .help("There has to be a file with the same name but with each of the passed extensions instead of its own one next to the file. Extensions are expected without the '.'")
+                    )
+                    .arg(
+                        Arg::with_name("missing_sibling_extension")
+                            .value_name("missing_sibling_extension")
+                            .multiple(true)
+                            .long("missing_sibling_extension")
+                            .help("There must NOT be a file with the same name but with any of the passed extensions instead of its own one next to the file. Useful to find files with missing sidecar files. Extensions are expected without the '.'")
+                    )
+                    .arg(
+                        Arg::with_name("newer_than")
+                            .value_name("newer_than")
+                            .long("newer_than")
+                            .help("The file has to be modified more recently than the file this path points to")
+                    )
+                    .arg(
+                        Arg::with_name("older_than")
+                            .value_name("older_than")
+                            .long("older_than")
+                            .help("The file has to be modified less recently than the file this path points to")
+                    )
+                    .arg(
+                        Arg::with_name("content")
+                            .value_name("content")
+                            .possible_values(&["text", "binary"])
+                            .long("content")
+                            .help("The file has to contain text or binary data. This is guessed from the first 8KB of the file. Folders never match")
+                    )
+                    .arg(
+                        Arg::with_name("lines_exact")
+                            .value_name("lines_exact")
+                            .conflicts_with_all(&["lines_over", "lines_under"])
+                            .validator(|input| {
+                                input.parse::<u64>().map_err(|_| "lines_exact has to be a valid positive number".to_string())?;
+                                Ok(())
+                            })
+                            .long("lines_exact")
+                            .help("The file has to have exactly this many lines")
+                    )
+                    .arg(
+                        Arg::with_name("lines_over")
+                            .value_name("lines_over")
+                            .validator(|input| {
+                                input.parse::<u64>().map_err(|_| "lines_over has to be a valid positive number".to_string())?;
+                                Ok(())
+                            })
+                            .long("lines_over")
+                            .help("The file has to have more lines than this")
+                    )
+                    .arg(
+                        Arg::with_name("lines_under")
+                            .value_name("lines_under")
+                            .validator(|input| {
+                                input.parse::<u64>().map_err(|_| "lines_under has to be a valid positive number".to_string())?;
+                                Ok(())
+                            })
+                            .long("lines_under")
+                            .help("The file has to have less lines than this")
+                    )
+                    .arg(
+                        Arg::with_name("encoding")
+                            .value_name("encoding")
+                            .possible_values(&["ascii", "utf8", "utf8_bom", "utf16_le", "utf16_be", "unknown"])
+                            .long("encoding")
+                            .help("The file has to be text in this encoding. This is guessed from the first 8KB of the file. utf8 also matches files that only contain ASCII characters")
+                    )
+                    .arg(
+                        Arg::with_name("line_ending")
+                            .value_name("line_ending")
+                            .possible_values(&["lf", "crlf", "mixed", "none"])
+                            .long("line_ending")
+                            .help("The file has to use these line endings")
+                    )
+                    .arg(
+                        Arg::with_name("name_longer_than")
+                            .value_name("name_longer_than")
+                            .validator(|input| {
+                                input.parse::<usize>().map_err(|_| "name_longer_than has to be a valid positive number".to_string())?;
+                                Ok(())
+                            })
+                            .long("name_longer_than")
+                            .help("The name of the file has to be longer than this many bytes. Most filesystems only allow names with up to 255 bytes")
+                    )
+                    .arg(
+                        Arg::with_name("path_longer_than")
+                            .value_name("path_longer_than")
+                            .validator(|input| {
+                                input.parse::<usize>().map_err(|_| "path_longer_than has to be a valid positive number".to_string())?;
+                                Ok(())
+                            })
+                            .long("path_longer_than")
+                            .help("The path of the file has to be longer than this many bytes. Linux only allows paths with up to 4096 bytes")
+                    )
+                    .arg(
+                        Arg::with_name("name_lint")
+                            .value_name("name_lint")
+                            .multiple(true)
+                            .possible_values(&["leading_trailing_whitespace", "control_characters", "non_ascii", "windows_reserved_name", "invalid_windows_characters"])
+                            .long("name_lint")
+                            .help("The name of the file has to have at least one of the passed problems that can break it on other systems. windows_reserved_name are names like CON or aux.txt, invalid_windows_characters are characters that aren't allowed on FAT or NTFS and names that end with a '.' or a space")
+                    )
+                    .arg(
+                        Arg::with_name("depth_exact")
+                            .value_name("depth_exact")
+                            .conflicts_with_all(&["depth_over", "depth_under"])
+                            .validator(|input| {
+                                input.parse::<usize>().map_err(|_| "depth_exact has to be a valid positive number".to_string())?;
+                                Ok(())
+                            })
+                            .long("depth_exact")
+                            .help("The file has to be exactly this many folders deep below the path that is searched in. The files directly in it have a depth of 1")
+                    )
+                    .arg(
+                        Arg::with_name("depth_over")
+                            .value_name("depth_over")
+                            .validator(|input| {
+                                input.parse::<usize>().map_err(|_| "depth_over has to be a valid positive number".to_string())?;
+                                Ok(())
+                            })
+                            .long("depth_over")
+                            .help("The file has to be more than this many folders deep")
+                    )
+                    .arg(
+                        Arg::with_name("depth_under")
+                            .value_name("depth_under")
+                            .validator(|input| {
+                                input.parse::<usize>().map_err(|_| "depth_under has to be a valid positive number".to_string())?;
+                                Ok(())
+                            })
+                            .long("depth_under")
+                            .help("The file has to be less than this many folders deep")
+                    )
+                    .arg(
+                        Arg::with_name("entropy_over")
+                            .value_name("entropy_over")
+                            .validator(|input| {
+                                input.parse::<f64>().map_err(|_| "entropy_over has to be a valid number".to_string())?;
+                                Ok(())
+                            })
+                            .long("entropy_over")
+                            .help("The Shannon entropy of the contents of the file has to be higher than this. It is between 0 and 8 bits per byte. Encrypted and compressed files have an entropy close to 8. Folders never match")
+                    )
+                    .arg(
+                        Arg::with_name("entropy_under")
+                            .value_name("entropy_under")
+                            .validator(|input| {
+                                input.parse::<f64>().map_err(|_| "entropy_under has to be a valid number".to_string())?;
+                                Ok(())
+                            })
+                            .long("entropy_under")
+                            .help("The Shannon entropy of the contents of the file has to be lower than this")
+                    )
+                    .arg(
+                        Arg::with_name("entropy_sample")
+                            .value_name("entropy_sample")
+                            .default_value("blocks")
+                            .possible_values(&["head", "blocks", "full"])
+                            .long("entropy_sample")
+                            .help("Which part of the file is read to calculate the entropy. head reads the first entropy_sample_size bytes, blocks reads entropy_blocks blocks with entropy_sample_size bytes each that are spread out over the file and full reads the whole file")
+                    )
+                    .arg(
+                        Arg::with_name("entropy_sample_size")
+                            .value_name("entropy_sample_size")
+                            .default_value("4096")
+                            .validator(|input| {
+                                input.parse::<u64>().map_err(|_| "entropy_sample_size has to be a valid positive number".to_string())?;
+                                Ok(())
+                            })
+                            .long("entropy_sample_size")
+                            .help("How many bytes are read for head or for every block of blocks")
+                    )
+                    .arg(
+                        Arg::with_name("entropy_blocks")
+                            .value_name("entropy_blocks")
+                            .default_value("16")
+                            .validator(|input| {
+                                input.parse::<usize>().map_err(|_| "entropy_blocks has to be a valid positive number".to_string())?;
+                                Ok(())
+                            })
+                            .long("entropy_blocks")
+                            .help("How many blocks are read for blocks")
+                    )
+                    .arg(
+                        Arg::with_name("has_any_xattr")
+                            .long("has_any_xattr")
+                            .help("If this flag is set the file has to have at least one extended attribute. Only supported on Linux and macOS")
+                    )
+                    .arg(
+                        Arg::with_name("has_xattr")
+                            .value_name("has_xattr")
+                            .multiple(true)
+                            .long("has_xattr")
+                            .help("The file has to have an extended attribute with each of the passed names, i.e. user.tag. Only supported on Linux and macOS")
+                    )
+                    .arg(
+                        Arg::with_name("xattr_equals")
+                            .value_name("xattr_equals")
+                            .multiple(true)
+                            .validator(|input| {
+                                if input.contains('=') {
+                                    Ok(())
+                                } else {
+                                    Err("xattr_equals has to look like <name>=<value>".to_string())
+                                }
+                            })
+                            .long("xattr_equals")
+                            .help("The extended attribute has to have exactly this value. Expects <name>=<value>, i.e. user.tag=done")
+                    )
+                    .arg(
+                        Arg::with_name("xattr_regex")
+                            .value_name("xattr_regex")
+                            .multiple(true)
+                            .validator(|input| {
+                                let name_and_regex: Vec<&str> = input.splitn(2, '=').collect();
+
+                                match name_and_regex.get(1) {
+                                    Some(regex) => {
+                                        Regex::new(regex).map_err(|_| "xattr_regex has to contain a valid regex".to_string())?;
+                                        Ok(())
+                                    }
+                                    None => Err("xattr_regex has to look like <name>=<regex>".to_string()),
+                                }
+                            })
+                            .long("xattr_regex")
+                            .help("The value of the extended attribute has to match the regex. Expects <name>=<regex>, i.e. user.tag=^todo")
+                    )
+                    .arg(
+                        Arg::with_name("artist")
+                            .value_name("artist")
+                            .validator(|input| {
+                                Regex::new(&input).map_err(|_| "artist has to be a valid regex".to_string())?;
+                                Ok(())
+                            })
+                            .long("artist")
+                            .help("The artist tag of an MP3, FLAC or OGG file has to match this regex. Files without the tag never match")
+                    )
+                    .arg(
+                        Arg::with_name("album")
+                            .value_name("album")
+                            .validator(|input| {
+                                Regex::new(&input).map_err(|_| "album has to be a valid regex".to_string())?;
+                                Ok(())
+                            })
+                            .long("album")
+                            .help("Same as artist but for the album tag")
+                    )
+                    .arg(
+                        Arg::with_name("title")
+                            .value_name("title")
+                            .validator(|input| {
+                                Regex::new(&input).map_err(|_| "title has to be a valid regex".to_string())?;
+                                Ok(())
+                            })
+                            .long("title")
+                            .help("Same as artist but for the title tag")
+                    )
+                    .arg(
+                        Arg::with_name("genre")
+                            .value_name("genre")
+                            .validator(|input| {
+                                Regex::new(&input).map_err(|_| "genre has to be a valid regex".to_string())?;
+                                Ok(())
+                            })
+                            .long("genre")
+                            .help("Same as artist but for the genre tag")
+                    )
+                    .arg(
+                        Arg::with_name("year_at")
+                            .value_name("year_at")
+                            .validator(|input| {
+                                input.parse::<i32>().map_err(|_| "year_at has to be a valid number".to_string())?;
+                                Ok(())
+                            })
+                            .long("year_at")
+                            .help("The year an MP3, FLAC or OGG file was recorded or released in has to be this year. It is read from the date tags")
+                    )
+                    .arg(
+                        Arg::with_name("year_before")
+                            .value_name("year_before")
+                            .validator(|input| {
+                                input.parse::<i32>().map_err(|_| "year_before has to be a valid number".to_string())?;
+                                Ok(())
+                            })
+                            .long("year_before")
+                            .help("The year the audio file was recorded or released in has to be before this year")
+                    )
+                    .arg(
+                        Arg::with_name("year_after")
+                            .value_name("year_after")
+                            .validator(|input| {
+                                input.parse::<i32>().map_err(|_| "year_after has to be a valid number".to_string())?;
+                                Ok(())
+                            })
+                            .long("year_after")
+                            .help("The year the audio file was recorded or released in has to be after this year")
+                    )
+                    .arg(
+                        Arg::with_name("duration_over")
+                            .value_name("duration_over")
+                            .validator(|input| {
+                                input.parse::<u64>().map_err(|_| "duration_over has to be a valid positive number".to_string())?;
+                                Ok(())
+                            })
+                            .long("duration_over")
+                            .help("An MP3, FLAC or OGG file has to be longer than this many seconds")
+                    )
+                    .arg(
+                        Arg::with_name("duration_under")
+                            .value_name("duration_under")
+                            .validator(|input| {
+                                input.parse::<u64>().map_err(|_| "duration_under has to be a valid positive number".to_string())?;
+                                Ok(())
+                            })
+                            .long("duration_under")
+                            .help("An MP3, FLAC or OGG file has to be shorter than this many seconds")
+                    )
+                    .arg(
+                        Arg::with_name("taken_at")
+                            .value_name("taken_at")
+                            .conflicts_with_all(&["taken_before", "taken_after"])
+                            .validator(|input| {
+                                parse_date_time_range(&input).map_err(|_| "taken_at has to be a date like 2021-04-05 or 2021-04-05T12:00".to_string())?;
+                                Ok(())
+                            })
+                            .long("taken_at")
+                            .help("The time the photo was taken at according to its EXIF data. Unlike the modification time this doesn't change when the file is copied. Value should be a date like 2021-04-05, which matches the whole day, or a date with a time like 2021-04-05T12:00, which matches the whole minute, and is compared to the time the camera showed. Files without EXIF data never match")
+                    )
+                    .arg(
+                        Arg::with_name("taken_before")
+                            .value_name("taken_before")
+                            .validator(|input| {
+                                parse_date_time(&input).map_err(|_| "taken_before has to be a date like 2021-04-05 or 2021-04-05T12:00".to_string())?;
+                                Ok(())
+                            })
+                            .long("taken_before")
+                            .help("The photo has to be taken before this time. Value should be a date like 2021-04-05 or a date with a time like 2021-04-05T12:00")
+                    )
+                    .arg(
+                        Arg::with_name("taken_after")
+                            .value_name("taken_after")
+                            .validator(|input| {
+                                parse_date_time(&input).map_err(|_| "taken_after has to be a date like 2021-04-05 or 2021-04-05T12:00".to_string())?;
+                                Ok(())
+                            })
+                            .long("taken_after")
+                            .help("The photo has to be taken after this time. Value should be a date like 2021-04-05 or a date with a time like 2021-04-05T12:00")
+                    )
+                    .arg(
+                        Arg::with_name("camera_make")
+                            .value_name("camera_make")
+                            .validator(|input| {
+                                Regex::new(&input).map_err(|_| "camera_make has to be a valid regex".to_string())?;
+                                Ok(())
+                            })
+                            .long("camera_make")
+                            .help("The manufacturer of the camera that took the photo has to match this regex. It is read from the EXIF data")
+                    )
+                    .arg(
+                        Arg::with_name("camera_model")
+                            .value_name("camera_model")
+                            .validator(|input| {
+                                Regex::new(&input).map_err(|_| "camera_model has to be a valid regex".to_string())?;
+                                Ok(())
+                            })
+                            .long("camera_model")
+                            .help("The model of the camera that took the photo has to match this regex")
+                    )
+                    .arg(
+                        Arg::with_name("has_gps")
+                            .conflicts_with("no_gps")
+                            .long("has_gps")
+                            .help("If this flag is set the photo has to have a GPS position in its EXIF data")
+                    )
+                    .arg(
+                        Arg::with_name("no_gps")
+                            .long("no_gps")
+                            .help("If this flag is set the photo must not have a GPS position in its EXIF data. Files without EXIF data never match")
+                    )
+                    .arg(
+                        Arg::with_name("orientation")
+                            .value_name("orientation")
+                            .possible_values(&["1", "2", "3", "4", "5", "6", "7", "8"])
+                            .long("orientation")
+                            .help("The EXIF orientation of the photo has to be this. 1 is upright, 3 is upside down and 6 and 8 are rotated by 90 degrees")
+                    )
+                    .arg(
+                        Arg::with_name("git_status")
+                            .value_name("git_status")
+                            .multiple(true)
+                            .possible_values(&["untracked", "ignored", "modified", "staged"])
+                            .long("git_status")
+                            .help("The file has to be in each of the passed states in the git repository it is in. Files outside of a git repository never match. Folders only match ignored")
+                    )
+                    .arg(
+                        Arg::with_name("git_not_committed_for_days")
+                            .value_name("git_not_committed_for_days")
+                            .validator(|input| {
+                                input.parse::<u32>().map_err(|_| "git_not_committed_for_days has to be a valid positive number".to_string())?;
+                                Ok(())
+                            })
+                            .long("git_not_committed_for_days")
+                            .help("The last commit that changed the file has to be older than this many days. Files that were never committed don't match")
+                    )
                     .arg(
                         Arg::with_name("max_num_results")
                             .value_name("max_num_results")
@@ -336,12 +858,71 @@ impl CLIOptions {
                             .long("ignore_hidden_files")
                             .help("If this flag is set all files that start with a '.' (a dot) will be ignored")
                     )
+                    .arg(
+                        Arg::with_name("no_ignore_hidden_files")
+                            .conflicts_with("ignore_hidden_files")
+                            .long("no_ignore_hidden_files")
+                            .help("Doesn't ignore files that start with a '.' even if the preset does")
+                    )
                     .arg(
                         Arg::with_name("follow_symlinks")
                             .short("f")
                             .long("follow_symlinks")
                             .help("If this flag is set any symlinks will be followed")
                     )
+                    .arg(
+                        Arg::with_name("no_follow_symlinks")
+                            .conflicts_with("follow_symlinks")
+                            .long("no_follow_symlinks")
+                            .help("Doesn't follow symlinks even if the preset does")
+                    )
+                    .arg(
+                        Arg::with_name("merge_overlapping_paths")
+                            .long("merge_overlapping_paths")
+                            .help("If this flag is set paths to search in that point to the same folder or to a folder inside of another path are merged so files in them are only found once")
+                    )
+                    .arg(
+                        Arg::with_name("no_merge_overlapping_paths")
+                            .conflicts_with("merge_overlapping_paths")
+                            .long("no_merge_overlapping_paths")
+                            .help("Doesn't merge overlapping paths to search in even if the preset does")
+                    )
+                    .arg(
+                        Arg::with_name("unique_inodes")
+                            .long("unique_inodes")
+                            .help("If this flag is set files with multiple hard links are only returned once. Only supported on Unix platforms")
+                    )
+                    .arg(
+                        Arg::with_name("no_unique_inodes")
+                            .conflicts_with("unique_inodes")
+                            .long("no_unique_inodes")
+                            .help("Returns every hard link to a file even if the preset only returns one")
+                    )
+                    .arg(
+                        Arg::with_name("search_in_archives")
+                            .long("search_in_archives")
+                            .help("If this flag is set it also searches inside of .zip, .tar, .tar.gz and .tgz archives. Files inside of an archive are printed as archive.zip!/path/inside/archive")
+                    )
+                    .arg(
+                        Arg::with_name("no_search_in_archives")
+                            .conflicts_with("search_in_archives")
+                            .long("no_search_in_archives")
+                            .help("Doesn't search inside of archives even if the preset does")
+                    )
+                    .arg(
+                        Arg::with_name("max_archive_depth")
+                            .value_name("max_archive_depth")
+                            .default_value("1")
+                            .validator(|input| {
+                                match input.parse::<usize>() {
+                                    Ok(0) => Err("max_archive_depth has to be at least 1. Leave out --search_in_archives to not search in archives".to_string()),
+                                    Ok(_) => Ok(()),
+                                    Err(_) => Err("max_archive_depth has to be a valid positive number".to_string()),
+                                }
+                            })
+                            .long("max_archive_depth")
+                            .help("Limits how many archives deep the search goes if archives contain other archives. Archives inside of archives that are larger than 100 MiB are skipped")
+                    )
                     .arg(
                         Arg::with_name("output_separator")
                             .value_name("output_separator")
@@ -352,6 +933,86 @@ impl CLIOptions {
                             .long("output_separator")
                             .help("Sets what is used to separate the paths to files that were found. Defaults to \\n")
                     )
+                    .arg(
+                        Arg::with_name("print_xattr")
+                            .value_name("print_xattr")
+                            .multiple(true)
+                            .number_of_values(1)
+                            .long("print_xattr")
+                            .help("Prints the value of the extended attribute with this name after the path of every file that was found, separated by a tab. Can be passed multiple times. Files without the attribute get an empty value")
+                    )
+                    .arg(
+                        Arg::with_name("print_errors")
+                            .long("print_errors")
+                            .help("Prints every error that occured (i.e. missing permissions, symlink loops or files that vanished during the search) to stderr and exits with a non-zero exit code if there were any")
+                    )
+                    .arg(
+                        Arg::with_name("no_print_errors")
+                            .conflicts_with("print_errors")
+                            .long("no_print_errors")
+                            .help("Doesn't print errors even if the preset does")
+                    )
+                    .arg(
+                        Arg::with_name("index")
+                            .value_name("index")
+                            .long("index")
+                            .help("Searches the files in the index this path points to instead of the filesystem. Create one with the index subcommand. Paths in the index are absolute so every path that is found is absolute too")
+                    )
+                    .arg(
+                        Arg::with_name("watch")
+                            .conflicts_with("index")
+                            .long("watch")
+                            .help("Keeps running after the search and prints every file that is created, modified or moved into one of the paths to search in and matches. Folders that are created later are watched too. Only supported on Linux")
+                    )
+                    .arg(
+                        Arg::with_name("watch_debounce")
+                            .value_name("watch_debounce")
+                            .default_value("500")
+                            .validator(|input| {
+                                input.parse::<u64>().map_err(|_| "watch_debounce has to be a valid positive number".to_string())?;
+                                Ok(())
+                            })
+                            .long("watch_debounce")
+                            .help("How many milliseconds nothing has to happen to a file before it gets checked in watch mode. Stops files that are still being written to from being printed multiple times")
+                    )
+            )
+            .subcommand(
+                SubCommand::with_name("index")
+                    .about("Stores the metadata of every file in one or more folders in an index that can be searched with find --index")
+                    .setting(AppSettings::ArgRequiredElseHelp)
+                    .setting(AppSettings::DeriveDisplayOrder)
+                    .setting(AppSettings::WaitOnError)
+                    .setting(AppSettings::UnifiedHelpMessage)
+                    .arg(
+                        Arg::with_name("paths_to_index")
+                            .value_name("paths_to_index")
+                            .required_unless("refresh")
+                            .multiple(true)
+                            .short("p")
+                            .long("paths_to_index")
+                            .help("The folder(s) that should be indexed")
+                    )
+                    .arg(
+                        Arg::with_name("database")
+                            .value_name("database")
+                            .required(true)
+                            .env("FILY_INDEX")
+                            .short("d")
+                            .long("database")
+                            .help("Path to the file the index is stored in")
+                    )
+                    .arg(
+                        Arg::with_name("hash_contents")
+                            .long("hash_contents")
+                            .help("Also stores the crc32 of the contents of every file. This can take a long time since every file has to be read")
+                    )
+                    .arg(
+                        Arg::with_name("refresh")
+                            .conflicts_with_all(&["paths_to_index", "hash_contents"])
+                            .short("r")
+                            .long("refresh")
+                            .help("Updates an existing index instead of creating a new one. Only the contents of folders that changed since the last time are read again. Files in unchanged folders keep their old metadata even if they were modified")
+                    )
             )
             .subcommand(
                 SubCommand::with_name("rename")
@@ -368,6 +1029,48 @@ impl CLIOptions {
                             .long("template")
                             .help("Template which will be used to rename the files")
                     )
+                    .arg(
+                        Arg::with_name("dry_run")
+                            .short("n")
+                            .long("dry_run")
+                            .help("Prints what would be renamed without renaming anything. Collisions, files that already exist, names that don't change and names with invalid characters are flagged")
+                    )
+                    .arg(
+                        Arg::with_name("preview_format")
+                            .requires("dry_run")
+                            .value_name("format")
+                            .short("f")
+                            .long("preview_format")
+                            .possible_values(&["table", "json", "diff"])
+                            .help("How the renames of --dry_run are printed. Default is table")
+                    )
+                    .arg(
+                        Arg::with_name("on_conflict")
+                            .value_name("policy")
+                            .short("c")
+                            .long("on_conflict")
+                            .possible_values(&["abort", "skip", "suffix"])
+                            .help("What happens if a new name is already taken by an existing file or another file that is renamed. abort renames nothing, skip leaves these files out and suffix adds _1, _2 and so on to the new name. Default is abort")
+                    )
+                    .arg(
+                        Arg::with_name("overwrite")
+                            .conflicts_with("on_conflict")
+                            .long("overwrite")
+                            .help("Replaces files that already have a new name instead of aborting. The replaced files are gone. Files that would get the same new name as another one are left out")
+                    )
+                    .arg(
+                        Arg::with_name("journal")
+                            .value_name("journal")
+                            .short("j")
+                            .long("journal")
+                            .help("The file in which the renames get written down so they can be undone with the undo subcommand. Defaults to fily.journal")
+                    )
+                    .arg(
+                        Arg::with_name("no_journal")
+                            .conflicts_with("journal")
+                            .long("no_journal")
+                            .help("Doesn't write a journal. The renames can't be undone then")
+                    )
             )
             .subcommand(
                 SubCommand::with_name("duplicates")
@@ -397,6 +1100,19 @@ impl CLIOptions {
                             .long("move_to")
                             .help("A path to which the files get moved to. Has to point to a folder")
                     )
+                    .arg(
+                        Arg::with_name("journal")
+                            .value_name("journal")
+                            .short("j")
+                            .long("journal")
+                            .help("The file in which the moves get written down so they can be undone with the undo subcommand. Defaults to fily.journal")
+                    )
+                    .arg(
+                        Arg::with_name("no_journal")
+                            .conflicts_with("journal")
+                            .long("no_journal")
+                            .help("Doesn't write a journal. The moves can't be undone then")
+                    )
             )
             .subcommand(
                 SubCommand::with_name("similar_images")
@@ -469,6 +1185,20 @@ impl CLIOptions {
                     .setting(AppSettings::WaitOnError)
                     .setting(AppSettings::UnifiedHelpMessage)
             )
+            .subcommand(
+                SubCommand::with_name("undo")
+                    .about("Undoes the renames or moves written down in a journal. Entries that can't be undone stay in the journal")
+                    .setting(AppSettings::DeriveDisplayOrder)
+                    .setting(AppSettings::WaitOnError)
+                    .setting(AppSettings::UnifiedHelpMessage)
+                    .arg(
+                        Arg::with_name("journal")
+                            .value_name("journal")
+                            .short("j")
+                            .long("journal")
+                            .help("The journal to undo. Defaults to fily.journal")
+                    )
+            )
             .subcommand(
                 SubCommand::with_name("delete")
                     .about("Deletes files and folders")
@@ -487,7 +1217,7 @@ impl CLIOptions {
         let subcommand = match app.subcommand() {
             ("find", Some(args)) => {
                 let paths_to_search_in: Vec<OsString> = args.values_of_os("paths_to_search_in")
-                    .expect("paths_to_search_in didn't exist")
+                    .unwrap_or_default()
                     .map(|os_str| os_str.to_os_string())
                     .collect();
 
@@ -520,7 +1250,7 @@ impl CLIOptions {
                         .expect("filesize_under parse failed");
 
                     if over_this_size >= under_this_size {
-                        return Err("filesize_over has to be less than filesize_under");
+                        return Err("filesize_over has to be less than filesize_under".into());
                     }
 
                     conditions.push(
@@ -564,7 +1294,7 @@ impl CLIOptions {
                         .expect("modified_after parse failed");
 
                     if after_this_time >= before_this_time {
-                        return Err("modified_after has to be less than modified_before");
+                        return Err("modified_after has to be less than modified_before".into());
                     }
 
                     conditions.push(
@@ -608,7 +1338,7 @@ impl CLIOptions {
                         .expect("accessed_after parse failed");
 
                     if after_this_time >= before_this_time {
-                        return Err("accessed_after has to be less than accessed_before");
+                        return Err("accessed_after has to be less than accessed_before".into());
                     }
 
                     conditions.push(
@@ -652,7 +1382,7 @@ impl CLIOptions {
                         .expect("created_after parse failed");
 
                     if after_this_time >= before_this_time {
-                        return Err("created_after has to be less than created_before");
+                        return Err("created_after has to be less than created_before".into());
                     }
 
                     conditions.push(
@@ -713,31 +1443,332 @@ impl CLIOptions {
                     conditions.push(Condition::build_none_of_condition(regex_ignore_criterias));
                 }
 
-                let max_num_results = if args.is_present("max_num_results") {
-                    args.value_of("max_num_results")
-                        .expect("max_num_results didn't exist")
-                        .parse()
-                        .expect("max_num_results parse failed")
-                } else {
-                    usize::MAX
-                };
+                let directory_contains: Vec<SearchCriteria> = args.values_of("directory_contains")
+                    .unwrap_or_default()
+                    .map(|name| SearchCriteria::DirectoryContains(DirectoryContains::Exact(name.into())))
+                    .collect();
 
-                let max_search_depth = if args.is_present("max_search_depth") {
-                    args.value_of("max_search_depth")
-                        .expect("max_search_depth didn't exist")
-                        .parse()
-                        .expect("max_search_depth parse failed")
-                } else {
-                    usize::MAX
+                if !directory_contains.is_empty() {
+                    conditions.push(Condition::build_all_of_condition(directory_contains));
+                }
+
+                let has_sibling: Vec<SearchCriteria> = args.values_of("has_sibling")
+                    .unwrap_or_default()
+                    .map(|name| SearchCriteria::Sibling(Sibling::Exact(name.into())))
+                    .chain(args.values_of("has_sibling_extension")
+                        .unwrap_or_default()
+                        .map(|extension| SearchCriteria::Sibling(Sibling::WithExtension(extension.into())))
+                    )
+                    .collect();
+
+                if !has_sibling.is_empty() {
+                    conditions.push(Condition::build_all_of_condition(has_sibling));
+                }
+
+                let missing_sibling: Vec<SearchCriteria> = args.values_of("missing_sibling_extension")
+                    .unwrap_or_default()
+                    .map(|extension| SearchCriteria::Sibling(Sibling::WithExtension(extension.into())))
+                    .collect();
+
+                if !missing_sibling.is_empty() {
+                    conditions.push(Condition::build_none_of_condition(missing_sibling));
+                }
+
+                if let Some(newer_than) = args.value_of_os("newer_than") {
+                    let reference_file = ReferenceFile::new(newer_than)?;
+
+                    conditions.push(Condition::Value(SearchCriteria::Reference(Reference::NewerThan(reference_file))));
+                }
+
+                if let Some(older_than) = args.value_of_os("older_than") {
+                    let reference_file = ReferenceFile::new(older_than)?;
+
+                    conditions.push(Condition::Value(SearchCriteria::Reference(Reference::OlderThan(reference_file))));
+                }
+
+                if let Some(content) = args.value_of("content") {
+                    let text_or_binary = match content {
+                        "text" => TextOrBinary::Text,
+                        "binary" => TextOrBinary::Binary,
+                        _ => unreachable!("Someone messed with the possible values of content"),
+                    };
+
+                    conditions.push(Condition::Value(SearchCriteria::TextOrBinary(text_or_binary)));
+                }
+
+                if let Some(lines_exact) = args.value_of("lines_exact") {
+                    let lines_exact = lines_exact.parse().expect("lines_exact parse failed");
+
+                    conditions.push(Condition::Value(SearchCriteria::LineCount(LineCount::Exact(lines_exact))));
+                }
+
+                if let Some(lines_over) = args.value_of("lines_over") {
+                    let lines_over = lines_over.parse().expect("lines_over parse failed");
+
+                    conditions.push(Condition::Value(SearchCriteria::LineCount(LineCount::Over(lines_over))));
+                }
+
+                if let Some(lines_under) = args.value_of("lines_under") {
+                    let lines_under = lines_under.parse().expect("lines_under parse failed");
+
+                    conditions.push(Condition::Value(SearchCriteria::LineCount(LineCount::Under(lines_under))));
+                }
+
+                if let Some(encoding) = args.value_of("encoding") {
+                    let encoding = match encoding {
+                        "ascii" => TextEncoding::Ascii,
+                        "utf8" => TextEncoding::Utf8,
+                        "utf8_bom" => TextEncoding::Utf8Bom,
+                        "utf16_le" => TextEncoding::Utf16Le,
+                        "utf16_be" => TextEncoding::Utf16Be,
+                        "unknown" => TextEncoding::Unknown,
+                        _ => unreachable!("Someone messed with the possible values of encoding"),
+                    };
+
+                    conditions.push(Condition::Value(SearchCriteria::Encoding(encoding)));
+                }
+
+                if let Some(line_ending) = args.value_of("line_ending") {
+                    let line_ending = match line_ending {
+                        "lf" => LineEnding::Lf,
+                        "crlf" => LineEnding::Crlf,
+                        "mixed" => LineEnding::Mixed,
+                        "none" => LineEnding::None,
+                        _ => unreachable!("Someone messed with the possible values of line_ending"),
+                    };
+
+                    conditions.push(Condition::Value(SearchCriteria::LineEnding(line_ending)));
+                }
+
+                if let Some(name_longer_than) = args.value_of("name_longer_than") {
+                    let name_longer_than = name_longer_than.parse().expect("name_longer_than parse failed");
+
+                    conditions.push(Condition::Value(SearchCriteria::NameLint(NameLint::NameLongerThan(name_longer_than))));
+                }
+
+                if let Some(path_longer_than) = args.value_of("path_longer_than") {
+                    let path_longer_than = path_longer_than.parse().expect("path_longer_than parse failed");
+
+                    conditions.push(Condition::Value(SearchCriteria::NameLint(NameLint::PathLongerThan(path_longer_than))));
+                }
+
+                if let Some(name_lints) = args.values_of("name_lint") {
+                    let name_lints: Vec<SearchCriteria> = name_lints
+                        .map(|name_lint| match name_lint {
+                            "leading_trailing_whitespace" => NameLint::LeadingOrTrailingWhitespace,
+                            "control_characters" => NameLint::ControlCharacters,
+                            "non_ascii" => NameLint::NonAscii,
+                            "windows_reserved_name" => NameLint::WindowsReservedName,
+                            "invalid_windows_characters" => NameLint::InvalidWindowsCharacters,
+                            _ => unreachable!("Someone messed with the possible values of name_lint"),
+                        })
+                        .map(SearchCriteria::NameLint)
+                        .collect();
+
+                    conditions.push(Condition::build_any_of_condition(name_lints));
+                }
+
+                if let Some(depth_exact) = args.value_of("depth_exact") {
+                    let depth_exact = depth_exact.parse().expect("depth_exact parse failed");
+
+                    conditions.push(Condition::Value(SearchCriteria::Depth(Depth::Exact(depth_exact))));
+                }
+
+                if let Some(depth_over) = args.value_of("depth_over") {
+                    let depth_over = depth_over.parse().expect("depth_over parse failed");
+
+                    conditions.push(Condition::Value(SearchCriteria::Depth(Depth::Over(depth_over))));
+                }
+
+                if let Some(depth_under) = args.value_of("depth_under") {
+                    let depth_under = depth_under.parse().expect("depth_under parse failed");
+
+                    conditions.push(Condition::Value(SearchCriteria::Depth(Depth::Under(depth_under))));
+                }
+
+                let entropy_sample_size = args.value_of("entropy_sample_size")
+                    .expect("entropy_sample_size didn't exist")
+                    .parse()
+                    .expect("entropy_sample_size parse failed");
+
+                let entropy_sample = match args.value_of("entropy_sample").expect("entropy_sample didn't exist") {
+                    "head" => EntropySample::Head(entropy_sample_size),
+                    "blocks" => EntropySample::Blocks {
+                        count: args.value_of("entropy_blocks")
+                            .expect("entropy_blocks didn't exist")
+                            .parse()
+                            .expect("entropy_blocks parse failed"),
+                        block_size: entropy_sample_size,
+                    },
+                    "full" => EntropySample::Full,
+                    _ => unreachable!("Someone messed with the possible values of entropy_sample"),
                 };
 
-                let min_depth_from_start = if args.is_present("min_depth_from_start") {
-                    args.value_of("min_depth_from_start")
+                if let Some(entropy_over) = args.value_of("entropy_over") {
+                    let entropy_over = entropy_over.parse().expect("entropy_over parse failed");
+
+                    conditions.push(Condition::Value(SearchCriteria::Entropy(Entropy::Over(entropy_over, entropy_sample))));
+                }
+
+                if let Some(entropy_under) = args.value_of("entropy_under") {
+                    let entropy_under = entropy_under.parse().expect("entropy_under parse failed");
+
+                    conditions.push(Condition::Value(SearchCriteria::Entropy(Entropy::Under(entropy_under, entropy_sample))));
+                }
+
+                if args.is_present("has_any_xattr") {
+                    conditions.push(Condition::Value(SearchCriteria::Xattr(Xattr::Any)));
+                }
+
+                conditions.extend(args.values_of("has_xattr")
+                    .unwrap_or_default()
+                    .map(|name| Condition::Value(SearchCriteria::Xattr(Xattr::Exists(name.to_string()))))
+                );
+
+                for xattr_equals in args.values_of("xattr_equals").unwrap_or_default() {
+                    let (name, value) = xattr_equals.split_once('=').expect("xattr_equals validation failed");
+
+                    conditions.push(Condition::Value(SearchCriteria::Xattr(Xattr::Equals(name.to_string(), value.to_string()))));
+                }
+
+                for xattr_regex in args.values_of("xattr_regex").unwrap_or_default() {
+                    let (name, regex) = xattr_regex.split_once('=').expect("xattr_regex validation failed");
+                    let regex = Regex::new(regex).expect("xattr_regex validation failed");
+
+                    conditions.push(Condition::Value(SearchCriteria::Xattr(Xattr::Regex(name.to_string(), regex))));
+                }
+
+                if let Some(artist) = args.value_of("artist") {
+                    let artist = Regex::new(artist).expect("artist validation failed");
+
+                    conditions.push(Condition::Value(SearchCriteria::AudioTag(AudioTag::Artist(TagText::Regex(artist)))));
+                }
+
+                if let Some(album) = args.value_of("album") {
+                    let album = Regex::new(album).expect("album validation failed");
+
+                    conditions.push(Condition::Value(SearchCriteria::AudioTag(AudioTag::Album(TagText::Regex(album)))));
+                }
+
+                if let Some(title) = args.value_of("title") {
+                    let title = Regex::new(title).expect("title validation failed");
+
+                    conditions.push(Condition::Value(SearchCriteria::AudioTag(AudioTag::Title(TagText::Regex(title)))));
+                }
+
+                if let Some(genre) = args.value_of("genre") {
+                    let genre = Regex::new(genre).expect("genre validation failed");
+
+                    conditions.push(Condition::Value(SearchCriteria::AudioTag(AudioTag::Genre(TagText::Regex(genre)))));
+                }
+
+                if let Some(year_at) = args.value_of("year_at") {
+                    let year_at = year_at.parse().expect("year_at parse failed");
+
+                    conditions.push(Condition::Value(SearchCriteria::AudioTag(AudioTag::YearAt(year_at))));
+                }
+
+                if let Some(year_before) = args.value_of("year_before") {
+                    let year_before = year_before.parse().expect("year_before parse failed");
+
+                    conditions.push(Condition::Value(SearchCriteria::AudioTag(AudioTag::YearBefore(year_before))));
+                }
+
+                if let Some(year_after) = args.value_of("year_after") {
+                    let year_after = year_after.parse().expect("year_after parse failed");
+
+                    conditions.push(Condition::Value(SearchCriteria::AudioTag(AudioTag::YearAfter(year_after))));
+                }
+
+                if let Some(duration_over) = args.value_of("duration_over") {
+                    let duration_over = Duration::from_secs(duration_over.parse().expect("duration_over parse failed"));
+
+                    conditions.push(Condition::Value(SearchCriteria::AudioTag(AudioTag::DurationOver(duration_over))));
+                }
+
+                if let Some(duration_under) = args.value_of("duration_under") {
+                    let duration_under = Duration::from_secs(duration_under.parse().expect("duration_under parse failed"));
+
+                    conditions.push(Condition::Value(SearchCriteria::AudioTag(AudioTag::DurationUnder(duration_under))));
+                }
+
+                if let Some(taken_at) = args.value_of("taken_at") {
+                    let taken_at = parse_date_time_range(taken_at).expect("taken_at parse failed");
+
+                    conditions.push(Condition::Value(SearchCriteria::ExifTag(ExifTag::TakenAt(taken_at))));
+                }
+
+                if let Some(taken_before) = args.value_of("taken_before") {
+                    let taken_before = parse_date_time(taken_before).expect("taken_before parse failed");
+
+                    conditions.push(Condition::Value(SearchCriteria::ExifTag(ExifTag::TakenBefore(taken_before))));
+                }
+
+                if let Some(taken_after) = args.value_of("taken_after") {
+                    let taken_after = parse_date_time(taken_after).expect("taken_after parse failed");
+
+                    conditions.push(Condition::Value(SearchCriteria::ExifTag(ExifTag::TakenAfter(taken_after))));
+                }
+
+                if let Some(camera_make) = args.value_of("camera_make") {
+                    let camera_make = Regex::new(camera_make).expect("camera_make validation failed");
+
+                    conditions.push(Condition::Value(SearchCriteria::ExifTag(ExifTag::Make(TagText::Regex(camera_make)))));
+                }
+
+                if let Some(camera_model) = args.value_of("camera_model") {
+                    let camera_model = Regex::new(camera_model).expect("camera_model validation failed");
+
+                    conditions.push(Condition::Value(SearchCriteria::ExifTag(ExifTag::Model(TagText::Regex(camera_model)))));
+                }
+
+                if args.is_present("has_gps") {
+                    conditions.push(Condition::Value(SearchCriteria::ExifTag(ExifTag::HasGps(true))));
+                }
+
+                if args.is_present("no_gps") {
+                    conditions.push(Condition::Value(SearchCriteria::ExifTag(ExifTag::HasGps(false))));
+                }
+
+                if let Some(orientation) = args.value_of("orientation") {
+                    let orientation = orientation.parse().expect("orientation parse failed");
+
+                    conditions.push(Condition::Value(SearchCriteria::ExifTag(ExifTag::Orientation(orientation))));
+                }
+
+                // All git criterias share the repositories so each one is only read once
+                let git_repositories = GitRepositories::new();
+
+                if let Some(git_states) = args.values_of("git_status") {
+                    for git_state in git_states {
+                        let git_state = GitState::try_from(git_state)
+                            .unwrap_or_else(|_| unreachable!("Someone messed with the possible values of git_status"));
+
+                        conditions.push(Condition::Value(SearchCriteria::Git(Git::with_repositories(git_state, git_repositories.clone()))));
+                    }
+                }
+
+                if let Some(days) = args.value_of("git_not_committed_for_days") {
+                    let days: i64 = days.parse().expect("git_not_committed_for_days parse failed");
+                    let before_this_time = chrono::Utc::now().timestamp() - days * 24 * 60 * 60;
+
+                    conditions.push(Condition::Value(SearchCriteria::Git(Git::with_repositories(GitState::LastCommitBefore(before_this_time), git_repositories))));
+                }
+
+                // These are None if they weren't passed so the values of a preset don't get overridden by the defaults
+                let max_num_results = args.value_of("max_num_results")
+                    .map(|max_num_results| max_num_results.parse().expect("max_num_results parse failed"));
+
+                let max_search_depth = args.value_of("max_search_depth")
+                    .map(|max_search_depth| max_search_depth.parse().expect("max_search_depth parse failed"));
+
+                let min_depth_from_start = if args.occurrences_of("min_depth_from_start") > 0 {
+                    Some(args.value_of("min_depth_from_start")
                         .expect("min_depth_from_start didn't exist")
                         .parse()
-                        .expect("min_depth_from_start parse failed")
+                        .expect("min_depth_from_start parse failed"))
                 } else {
-                    0
+                    None
                 };
 
                 let ignore = args.value_of("ignore")
@@ -747,16 +1778,75 @@ impl CLIOptions {
                         _ => unreachable!("Someone messed with the possible values ignore"),
                     });
 
-                let ignore_hidden_files = args.is_present("ignore_hidden_files");
+                let ignore_hidden_files = flag_or_negation(args, "ignore_hidden_files");
 
-                let follow_symlinks = args.is_present("follow_symlinks");
+                let follow_symlinks = flag_or_negation(args, "follow_symlinks");
 
-                let output_separator = args.value_of("output_separator")
-                    .expect("output_separator didn't exist")
-                    .to_string();
+                let merge_overlapping_paths = flag_or_negation(args, "merge_overlapping_paths");
+
+                let unique_inodes = flag_or_negation(args, "unique_inodes");
+
+                let search_in_archives = flag_or_negation(args, "search_in_archives");
+
+                let max_archive_depth = if args.occurrences_of("max_archive_depth") > 0 {
+                    Some(args.value_of("max_archive_depth")
+                        .expect("max_archive_depth didn't exist")
+                        .parse()
+                        .expect("max_archive_depth parse failed"))
+                } else {
+                    None
+                };
+
+                let preset = args.value_of("preset")
+                    .map(|preset| preset.trim_start_matches('@').to_string());
+
+                let config = args.value_of_os("config")
+                    .map(OsStr::to_os_string);
+
+                let paths_from = args.value_of_os("paths_from")
+                    .map(OsStr::to_os_string);
+
+                let exclude_from = args.value_of_os("exclude_from")
+                    .map(OsStr::to_os_string);
+
+                let list_separator = if args.is_present("null_separated") {
+                    ListSeparator::Nul
+                } else {
+                    ListSeparator::Newline
+                };
+
+                let output_separator = if args.occurrences_of("output_separator") > 0 {
+                    Some(args.value_of("output_separator")
+                        .expect("output_separator didn't exist")
+                        .to_string())
+                } else {
+                    None
+                };
+
+                let print_xattrs = args.values_of("print_xattr")
+                    .unwrap_or_default()
+                    .map(ToString::to_string)
+                    .collect();
+
+                let print_errors = flag_or_negation(args, "print_errors");
 
-                Subcommand::Find {
+                let index = args.value_of_os("index")
+                    .map(OsStr::to_os_string);
+
+                let watch = args.is_present("watch");
+
+                let watch_debounce = args.value_of("watch_debounce")
+                    .expect("watch_debounce didn't exist")
+                    .parse()
+                    .expect("watch_debounce parse failed");
+
+                Subcommand::Find(Box::new(FindArgs {
+                    preset,
+                    config,
                     paths_to_search_in,
+                    paths_from,
+                    exclude_from,
+                    list_separator,
                     conditions,
                     max_num_results,
                     max_search_depth,
@@ -764,7 +1854,37 @@ impl CLIOptions {
                     ignore,
                     ignore_hidden_files,
                     follow_symlinks,
+                    merge_overlapping_paths,
+                    unique_inodes,
+                    search_in_archives,
+                    max_archive_depth,
                     output_separator,
+                    print_xattrs,
+                    print_errors,
+                    index,
+                    watch,
+                    watch_debounce,
+                }))
+            }
+            ("index", Some(args)) => {
+                let paths_to_index: Vec<OsString> = args.values_of_os("paths_to_index")
+                    .unwrap_or_default()
+                    .map(OsStr::to_os_string)
+                    .collect();
+
+                let database = args.value_of_os("database")
+                    .expect("database didn't exist")
+                    .to_os_string();
+
+                let hash_contents = args.is_present("hash_contents");
+
+                let refresh = args.is_present("refresh");
+
+                Subcommand::Index {
+                    paths_to_index,
+                    database,
+                    hash_contents,
+                    refresh,
                 }
             }
             ("rename", Some(args)) => {
@@ -772,8 +1892,31 @@ impl CLIOptions {
                     .expect("new_filename_template didn't exist")
                     .to_string();
 
+                let dry_run = args.is_present("dry_run");
+
+                let preview_format = match args.value_of("preview_format") {
+                    None | Some("table") => PreviewFormat::Table,
+                    Some("json") => PreviewFormat::Json,
+                    Some("diff") => PreviewFormat::Diff,
+                    _ => unreachable!("Someone messed with the possible values of preview_format"),
+                };
+
+                let conflict_policy = match args.value_of("on_conflict") {
+                    _ if args.is_present("overwrite") => ConflictPolicy::Overwrite,
+                    None | Some("abort") => ConflictPolicy::Abort,
+                    Some("skip") => ConflictPolicy::Skip,
+                    Some("suffix") => ConflictPolicy::Suffix,
+                    _ => unreachable!("Someone messed with the possible values of on_conflict"),
+                };
+
+                let journal = journal_path(args);
+
                 Subcommand::Rename {
                     template,
+                    dry_run,
+                    preview_format,
+                    conflict_policy,
+                    journal,
                 }
             }
             ("duplicates", Some(args)) => {
@@ -788,8 +1931,11 @@ impl CLIOptions {
                     .expect("move_to didn't exist")
                     .to_os_string();
 
+                let journal = journal_path(args);
+
                 Subcommand::Move {
                     move_to,
+                    journal,
                 }
             }
             ("similar_images", Some(args)) => {
@@ -835,6 +1981,15 @@ impl CLIOptions {
                 }
             }
             ("check_image_formats", _) => Subcommand::CheckImageFormats,
+            ("undo", Some(args)) => {
+                let journal = args.value_of_os("journal")
+                    .unwrap_or_else(|| OsStr::new(DEFAULT_JOURNAL))
+                    .to_os_string();
+
+                Subcommand::Undo {
+                    journal,
+                }
+            }
             ("delete", Some(args)) => {
                 let safe_delete_files = args.is_present("safe_delete");
 
@@ -842,7 +1997,7 @@ impl CLIOptions {
                     safe_delete_files,
                 }
             }
-            _ => return Err("Unknown Subcommand"),
+            _ => return Err("Unknown Subcommand".into()),
         };
 
         let log_level = match app.value_of("log_level").expect("log_level doesn't exist") {
@@ -870,3 +2025,26 @@ impl CLIOptions {
         Ok(options)
     }
 }
+
+/// Returns `Some(true)` if the flag `name` was passed, `Some(false)` if `no_<name>` was passed and
+/// `None` if neither was so the value of a preset is kept
+fn flag_or_negation(args: &clap::ArgMatches<'_>, name: &str) -> Option<bool> {
+    if args.is_present(name) {
+        Some(true)
+    } else if args.is_present(format!("no_{name}")) {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+/// Returns the journal that was passed to `rename` or `move` or `None` if none should be written
+fn journal_path(args: &clap::ArgMatches<'_>) -> Option<OsString> {
+    if args.is_present("no_journal") {
+        return None;
+    }
+
+    Some(args.value_of_os("journal")
+        .unwrap_or_else(|| OsStr::new(DEFAULT_JOURNAL))
+        .to_os_string())
+}
@@ -1,30 +1,35 @@
 #![warn(clippy::cargo, clippy::pedantic)]
 #![warn(rust_2018_idioms)]
 
-use std::{error::Error, io::{self, stdin, Read, BufRead}};
+use std::{error::Error, ffi::OsStr, path::{Path, PathBuf}, io::{self, stdin, Read, BufRead}};
+#[cfg(target_os = "linux")]
+use std::{io::{stdout, Write}, time::Duration};
 #[allow(unused_imports)]
 use log::{trace, debug, info, warn, error};
 
 use fily_lib::{
-    rename::rename_files,
+    rename::{rename_files, plan_renames, RenamePlan, PlannedRename, RenameProblem, RenameFilesError, ConflictPolicy},
     duplicates::{find_duplicate_files, find_duplicate_files_hash},
-    find::{find, FindOptionsBuilder},
+    find::{find, get_xattr, FindOptionsBuilder, Config, ConditionEvalError},
+    index::{Index, find_in_index},
     move_files::move_files,
     similar_images::{find_similar_images, SimilarImagesOptions},
     check_image_formats::check_image_formats,
     delete::{delete, safe_delete},
+    journal::{Journal, JournalError, Operation},
+    fily_err::FilyError,
 };
+#[cfg(target_os = "linux")]
+use fily_lib::find::{FindWatcher, WatchError};
 
 mod cli_options;
 
-use cli_options::{CLIOptions, Subcommand};
+use cli_options::{CLIOptions, Subcommand, FindArgs, PreviewFormat};
 
 // TODO?: create a "create_file" module? How would that work? Naming? Contents?
 // TODO?: create "fill_file_with" module? what contents? where do we get them from?
 // TODO?: create a check_encoding module? checks if the input text (or text in file) has broken codepoints in it. take what encoding it is as input for each file?
-// TODO: actual error reporting on tokenizing rename template
 // TODO: find
-//       * add TryFrom<&str> for Condition<SearchCriteria<'a>> so you're able to make arbitrary combinations of conditions. Need a parser for that?
 //       * --exec and --exec_dir commands?
 //       * add only_return_directories flag? As in don't return the actual file but the directory it's in. This could enable some short circuting
 //       * max_num_results_per_folder option? do we include the results in subfolders or for every individual folder?
@@ -53,44 +58,196 @@ fn start() -> Result<(), Box<dyn Error>> {
     }
 
     match options.subcommand {
-        Subcommand::Find {
-            paths_to_search_in,
-            conditions,
-            max_num_results,
-            max_search_depth,
-            min_depth_from_start,
-            ignore,
-            ignore_hidden_files,
-            follow_symlinks,
-            output_separator,
-        } => {
-            let mut find_options_builder = FindOptionsBuilder::new();
+        Subcommand::Find(find_args) => {
+            let FindArgs {
+                preset,
+                config,
+                paths_to_search_in,
+                paths_from,
+                exclude_from,
+                list_separator,
+                conditions,
+                max_num_results,
+                max_search_depth,
+                min_depth_from_start,
+                ignore,
+                ignore_hidden_files,
+                follow_symlinks,
+                merge_overlapping_paths,
+                unique_inodes,
+                search_in_archives,
+                max_archive_depth,
+                output_separator,
+                print_xattrs,
+                print_errors,
+                index,
+                watch,
+                watch_debounce,
+            } = *find_args;
+
+            let preset = match preset {
+                Some(name) => {
+                    let config = match config {
+                        Some(config) => Config::load(config)?,
+                        None => Config::load_default()?,
+                    };
+
+                    Some(config.preset(&name)?.clone())
+                }
+                None => None,
+            };
+
+            let mut find_options_builder = match &preset {
+                Some(preset) => FindOptionsBuilder::from(preset.to_find_options()?),
+                None => FindOptionsBuilder::new(),
+            };
+
+            // Everything that was passed overrides the preset
+            find_options_builder.add_conditions(conditions);
+
+            if let Some(max_num_results) = max_num_results {
+                find_options_builder.set_max_num_results(max_num_results);
+            }
+
+            if let Some(max_search_depth) = max_search_depth {
+                find_options_builder.set_max_search_depth(max_search_depth);
+            }
 
-            find_options_builder.add_conditions(conditions)
-                .set_max_num_results(max_num_results)
-                .set_max_search_depth(max_search_depth)
-                .set_min_depth_from_start(min_depth_from_start)
-                .set_ignored_files(ignore)
-                .set_ignore_hidden_files(ignore_hidden_files)
-                .set_follow_symlinks(follow_symlinks);
+            if let Some(min_depth_from_start) = min_depth_from_start {
+                find_options_builder.set_min_depth_from_start(min_depth_from_start);
+            }
+
+            if ignore.is_some() {
+                find_options_builder.set_ignored_files(ignore);
+            }
+
+            if let Some(ignore_hidden_files) = ignore_hidden_files {
+                find_options_builder.set_ignore_hidden_files(ignore_hidden_files);
+            }
+
+            if let Some(follow_symlinks) = follow_symlinks {
+                find_options_builder.set_follow_symlinks(follow_symlinks);
+            }
+
+            if let Some(merge_overlapping_paths) = merge_overlapping_paths {
+                find_options_builder.set_merge_overlapping_paths(merge_overlapping_paths);
+            }
+
+            if let Some(unique_inodes) = unique_inodes {
+                find_options_builder.set_unique_inodes(unique_inodes);
+            }
+
+            if let Some(search_in_archives) = search_in_archives {
+                find_options_builder.set_search_in_archives(search_in_archives);
+            }
+
+            if let Some(max_archive_depth) = max_archive_depth {
+                find_options_builder.set_max_archive_depth(max_archive_depth);
+            }
+
+            let output_separator = output_separator
+                .or_else(|| preset.as_ref().and_then(|preset| preset.output_separator.clone()))
+                .unwrap_or_else(|| String::from("\n"));
+
+            let print_errors = print_errors
+                .or_else(|| preset.as_ref().and_then(|preset| preset.print_errors))
+                .unwrap_or(false);
+
+            if let Some(paths_from) = paths_from {
+                find_options_builder.add_paths_to_search_in_from_file(paths_from, list_separator)?;
+            }
+
+            if let Some(exclude_from) = exclude_from {
+                find_options_builder.add_exclude_patterns_from_file(exclude_from, list_separator)?;
+            }
 
             let find_options = find_options_builder.build();
 
-            let results = find(&paths_to_search_in, &find_options);
+            // The watches are set up before searching so no file that's created in the meantime gets missed
+            #[cfg(target_os = "linux")]
+            let watcher = if watch {
+                Some(FindWatcher::new(&paths_to_search_in, find_options.clone(), Duration::from_millis(watch_debounce))?)
+            } else {
+                None
+            };
 
-            for (path, err) in results.1 {
+            #[cfg(not(target_os = "linux"))]
+            {
+                let _ = watch_debounce;
+
+                if watch {
+                    return Err(Box::from("--watch is only supported on Linux"));
+                }
+            }
+
+            let results = if let Some(index) = index {
+                let index = Index::load(index)?;
+
+                find_in_index(&index, &paths_to_search_in, &find_options)
+            } else {
+                find(&paths_to_search_in, &find_options)
+            };
+
+            for (path, err) in &results.1 {
                 info!("{:?} {}", path.display(), err);
             }
 
             println!("{}", results.0
                 .iter()
-                .map(|path| path.display().to_string())
+                .map(|path| format_found_file(path, &print_xattrs, find_options.follow_symlinks))
                 .collect::<Vec<String>>()
                 .join(&output_separator)
             );
+
+            if print_errors && !results.1.is_empty() {
+                for (path, err) in &results.1 {
+                    match err {
+                        ConditionEvalError::SymlinkLoop { ancestor, child } => {
+                            eprintln!("Symlink loop: {:?} points to its ancestor {:?}", child.display(), ancestor.display());
+                        }
+                        _ => eprintln!("{:?} {}", path.display(), err),
+                    }
+                }
+
+                if !watch {
+                    return Err(Box::from(format!("{} errors occured during the search", results.1.len())));
+                }
+            }
+
+            #[cfg(target_os = "linux")]
+            if let Some((watcher, watch_errors)) = watcher {
+                watch_for_matches(watcher, watch_errors, &output_separator, &print_xattrs, find_options.follow_symlinks, print_errors)?;
+            }
+        }
+        Subcommand::Index {
+            paths_to_index,
+            database,
+            hash_contents,
+            refresh,
+        } => {
+            let (index, errors) = if refresh {
+                let mut index = Index::load(&database)?;
+                let errors = index.refresh();
+
+                (index, errors)
+            } else {
+                Index::create(&paths_to_index, hash_contents)
+            };
+
+            for (path, err) in errors {
+                info!("{:?} {}", path.display(), err);
+            }
+
+            index.save(&database)?;
+
+            info!("Stored {} files in {:?}", index.entries().len(), database);
         }
         Subcommand::Rename {
             template,
+            dry_run,
+            preview_format,
+            conflict_policy,
+            journal,
         } => {
             let files_to_rename = if let Some(separator) = options.input_path_separator {
                 get_stdin_split(&separator)?
@@ -98,7 +255,34 @@ fn start() -> Result<(), Box<dyn Error>> {
                 get_stdin_as_lines()?
             };
 
-            rename_files(&files_to_rename, &template)?;
+            let result = if dry_run {
+                // Aborting would hide the problems the preview is supposed to show
+                plan_renames(&files_to_rename, &template)
+                    .and_then(|plan| if conflict_policy == ConflictPolicy::Abort { Ok(plan) } else { plan.resolve_conflicts(conflict_policy) })
+                    .map(|plan| print!("{}", format_rename_plan(&plan, preview_format)))
+            } else {
+                match rename_files(&files_to_rename, &template, conflict_policy) {
+                    Ok(renamed) => {
+                        write_journal(journal.as_deref(), Operation::Rename, &renamed)?;
+                        Ok(())
+                    }
+                    Err(e) => {
+                        // The renames that couldn't be rolled back can still be undone later
+                        if let RenameFilesError::RollbackFailed(_, still_renamed) = &e {
+                            write_journal(journal.as_deref(), Operation::Rename, still_renamed)?;
+                        }
+
+                        Err(e)
+                    }
+                }
+            };
+
+            if let Err(e) = result {
+                return Err(match template_diagnostic(&template, &e) {
+                    Some(diagnostic) => Box::from(diagnostic),
+                    None => Box::from(e),
+                });
+            }
         }
         Subcommand::Duplicates {
             use_hash_version,
@@ -130,6 +314,7 @@ fn start() -> Result<(), Box<dyn Error>> {
         }
         Subcommand::Move {
             move_to,
+            journal,
         } => {
             let files_to_move = if let Some(separator) = options.input_path_separator {
                 get_stdin_split(&separator)?
@@ -137,11 +322,13 @@ fn start() -> Result<(), Box<dyn Error>> {
                 get_stdin_as_lines()?
             };
 
-            let paths_with_errors = move_files(move_to, &files_to_move)?;
+            let (moved, paths_with_errors) = move_files(move_to, &files_to_move)?;
 
             for (path, err) in paths_with_errors {
                 info!("Failed to move {:?} {}", path.display(), err);
             }
+
+            write_journal(journal.as_deref(), Operation::Move, &moved)?;
         }
         Subcommand::SimilarImages {
             hash_alg,
@@ -223,11 +410,234 @@ fn start() -> Result<(), Box<dyn Error>> {
                 }
             }
         }
+        Subcommand::Undo {
+            journal,
+        } => {
+            let failed_entries = Journal::load(&journal)?.undo();
+
+            if failed_entries.is_empty() {
+                std::fs::remove_file(&journal)?;
+                info!("Undid everything in {:?} and removed it", journal);
+                return Ok(());
+            }
+
+            let reasons: Vec<String> = failed_entries.iter()
+                .map(|(_, err)| err.get_context().to_string())
+                .collect();
+
+            let remaining_journal = Journal {
+                entries: failed_entries.into_iter().map(|(entry, _)| entry).collect(),
+            };
+
+            remaining_journal.save(&journal)?;
+
+            return Err(Box::from(format!("Failed to undo {} entries, they are still in {:?}:\n{}", reasons.len(), Path::new(&journal).display(), reasons.join("\n"))));
+        }
     };
 
     Ok(())
 }
 
+/// Saves the renames or moves in `moved` as a journal at `journal`, unless `journal` is `None` or nothing was moved
+fn write_journal(journal: Option<&OsStr>, operation: Operation, moved: &[(PathBuf, PathBuf)]) -> Result<(), FilyError<JournalError>> {
+    trace!("write_journal journal: {:?} operation: {:?} moved: {}", journal, operation, moved.len());
+
+    let journal = match journal {
+        Some(journal) if !moved.is_empty() => journal,
+        _ => return Ok(()),
+    };
+
+    Journal::record(operation, moved).save(journal)?;
+
+    info!("Wrote {} entries to the journal {:?}", moved.len(), journal);
+
+    Ok(())
+}
+
+/// Prints every file `watcher` finds as soon as it is found. Only returns if an error occurs
+///
+/// `initial_errors` are the errors that occured while setting up `watcher`
+#[cfg(target_os = "linux")]
+fn watch_for_matches(mut watcher: FindWatcher, initial_errors: Vec<(PathBuf, WatchError)>, output_separator: &str, print_xattrs: &[String], follow_symlinks: bool, print_errors: bool) -> Result<(), Box<dyn Error>> {
+    let mut errors = initial_errors;
+
+    loop {
+        for (path, err) in &errors {
+            info!("{:?} {}", path.display(), err);
+
+            if print_errors {
+                eprintln!("{:?} {}", path.display(), err);
+            }
+        }
+
+        let (matches, new_errors) = watcher.wait_for_matches()?;
+
+        let mut stdout = stdout();
+
+        for path in matches {
+            write!(stdout, "{}{}", format_found_file(&path, print_xattrs, follow_symlinks), output_separator)?;
+        }
+
+        stdout.flush()?;
+
+        errors = new_errors;
+    }
+}
+
+/// Formats a file `find` found for printing
+///
+/// The values of the extended attributes in `print_xattrs` get appended to the path, separated by a tab.
+/// Those of a symlink are read from the file it points to if `follow_symlinks` is `true`
+fn format_found_file(path: &Path, print_xattrs: &[String], follow_symlinks: bool) -> String {
+    let mut formatted = path.display().to_string();
+
+    for name in print_xattrs {
+        let value = match get_xattr(path, name, follow_symlinks) {
+            Ok(value) => value.map(|value| String::from_utf8_lossy(&value).into_owned()).unwrap_or_default(),
+            Err(e) => {
+                info!("{}", e);
+                String::new()
+            }
+        };
+
+        formatted.push('\t');
+        formatted.push_str(&value);
+    }
+
+    formatted
+}
+
+/// Formats the renames `rename --dry_run` would do
+fn format_rename_plan(plan: &RenamePlan, preview_format: PreviewFormat) -> String {
+    use std::fmt::Write;
+
+    fn problem_name(problem: RenameProblem) -> &'static str {
+        match problem {
+            RenameProblem::Unchanged => "unchanged",
+            RenameProblem::Collision => "collision",
+            RenameProblem::TargetExists => "target_exists",
+            RenameProblem::InvalidCharacters => "invalid_characters",
+        }
+    }
+
+    let mut formatted = String::new();
+
+    match preview_format {
+        PreviewFormat::Table => {
+            let old_path_width = plan.renames.iter()
+                .chain(&plan.conflicts)
+                .map(|planned_rename| &planned_rename.old_path)
+                .chain(plan.skipped.iter().map(|(path, _)| path))
+                .map(|path| path.display().to_string().chars().count())
+                .max()
+                .unwrap_or_default();
+
+            for (planned_rename, is_conflict) in plan.renames.iter().map(|planned_rename| (planned_rename, false)).chain(plan.conflicts.iter().map(|conflict| (conflict, true))) {
+                let old_path = planned_rename.old_path.display().to_string();
+                let padding = " ".repeat(old_path_width - old_path.chars().count());
+                let problems: Vec<&str> = planned_rename.problems.iter().map(|&problem| problem_name(problem)).collect();
+
+                formatted.push_str(format!("{}{} -> {}", old_path, padding, planned_rename.new_path.display()).trim_end());
+
+                if !problems.is_empty() {
+                    let _ = write!(formatted, "  [{}]", problems.join(", "));
+                }
+
+                if is_conflict {
+                    formatted.push_str(" skipped");
+                }
+
+                formatted.push('\n');
+            }
+
+            for (path, e) in &plan.skipped {
+                let old_path = path.display().to_string();
+                let padding = " ".repeat(old_path_width - old_path.chars().count());
+
+                let _ = writeln!(formatted, "{}{} skipped: {}", old_path, padding, e.get_context());
+            }
+        }
+        PreviewFormat::Json => {
+            let to_json = |planned_rename: &PlannedRename| serde_json::json!({
+                "old_path": planned_rename.old_path.to_string_lossy(),
+                "new_path": planned_rename.new_path.to_string_lossy(),
+                "problems": planned_rename.problems.iter().map(|&problem| problem_name(problem)).collect::<Vec<&str>>(),
+            });
+
+            let renames: Vec<serde_json::Value> = plan.renames.iter().map(to_json).collect();
+            let conflicts: Vec<serde_json::Value> = plan.conflicts.iter().map(to_json).collect();
+
+            let skipped: Vec<serde_json::Value> = plan.skipped.iter()
+                .map(|(path, e)| serde_json::json!({
+                    "path": path.to_string_lossy(),
+                    "reason": e.get_context(),
+                }))
+                .collect();
+
+            formatted = serde_json::json!({
+                "renames": renames,
+                "conflicts": conflicts,
+                "skipped": skipped,
+            }).to_string();
+
+            formatted.push('\n');
+        }
+        PreviewFormat::Diff => {
+            for planned_rename in &plan.renames {
+                if planned_rename.problems == [RenameProblem::Unchanged] {
+                    continue;
+                }
+
+                let _ = writeln!(formatted, "-{}\n+{}", planned_rename.old_path.display(), planned_rename.new_path.display());
+
+                for &problem in &planned_rename.problems {
+                    let _ = writeln!(formatted, "! {}", problem_name(problem));
+                }
+            }
+
+            for conflict in &plan.conflicts {
+                let problems: Vec<&str> = conflict.problems.iter().map(|&problem| problem_name(problem)).collect();
+
+                let _ = writeln!(formatted, "-{}\n! skipped: {}", conflict.old_path.display(), problems.join(", "));
+            }
+
+            for (path, e) in &plan.skipped {
+                let _ = writeln!(formatted, "-{}\n! skipped: {}", path.display(), e.get_context());
+            }
+        }
+    }
+
+    formatted
+}
+
+/// Formats an error in a rename template with the template below it and the part that is wrong underlined like this:
+///
+/// ```text
+/// Invalid template: unknown variable, did you mean "filename"?
+///   {filenme}.txt
+///    ^^^^^^^
+/// ```
+///
+/// Returns `None` if the error isn't in the template itself
+fn template_diagnostic(template: &str, err: &RenameFilesError) -> Option<String> {
+    let span = err.template_span()?;
+
+    let reason = match err {
+        RenameFilesError::TokenizeError(e) => e.get_error().to_string(),
+        RenameFilesError::OptionsParsingError(e) => e.get_error().to_string(),
+        RenameFilesError::ParsingError(_)
+        | RenameFilesError::Conflicts(_)
+        | RenameFilesError::RenameFailed(_)
+        | RenameFilesError::RollbackFailed(..) => return None,
+    };
+
+    // Columns are counted in chars so non ASCII characters in front of the error don't move the carets
+    let column = template[..span.start].chars().count();
+    let width = template[span].chars().count().max(1);
+
+    Some(format!("Invalid template: {}\n  {}\n  {}{}", reason, template, " ".repeat(column), "^".repeat(width)))
+}
+
 /// Sets up the logger backend for `log`
 ///
 /// Sends all logs to a file called `fily.log`